@@ -3,28 +3,69 @@ use std::num::NonZeroU8;
 use bracket_lib::random::RandomNumberGenerator;
 use specs::prelude::*;
 
+pub use components::AuraEffect;
+pub use components::CropStage;
 pub use components::Glyph;
 pub use components::Moving as Direction;
+pub use fov::TileVisibility;
+pub use map::Terrain;
+pub use resources::{cost, Resources};
 
-use map::Map;
+use faction::{Relation, Relations};
+use fov::FogOfWar;
+use map::{Layer, Map, Neighborhood};
 use specs::shred::FetchMut;
 
 use self::level_generator::LevelGenerator;
 use self::level_generator::LevelInsert;
 use self::level_generator::LevelItem;
+use self::level_generator::TerrainParameters;
+use self::level_strategy::LevelStrategy;
 use self::logger::LogMessage;
 use self::logger::Logs;
 
 mod ai;
+mod aura;
+pub mod board;
+mod boss;
 mod combat;
 mod components;
+mod death;
 #[allow(dead_code)]
 mod demo;
+mod faction;
+mod farming;
+mod fov;
 mod level_generator;
+pub mod level_strategy;
 pub mod logger;
 mod map;
 mod movement;
+mod pathfinding;
+mod resources;
+#[cfg(test)]
+mod scenario;
+mod theft;
 mod tree_growth;
+mod village;
+
+/// Every how many rounds a boss joins the fight.
+const BOSS_EVERY: u8 = 5;
+
+/// Amount of food a harvest of ripe crops is worth (and what crops are worth when stolen).
+const HARVEST_FOOD: u8 = 2;
+
+/// Food in store at the start of a game, to get through the rounds before the first harvest.
+const STARTING_FOOD: u8 = 6;
+
+/// Width and height of a map when none are chosen.
+pub const DEFAULT_MAP_SIZE: usize = 12;
+
+/// Smallest width or height of a generated map.
+pub const MIN_MAP_SIZE: usize = 6;
+
+/// Largest width or height of a generated map (positions are stored as `u8` while generating).
+pub const MAX_MAP_SIZE: usize = u8::MAX as usize;
 
 /// Our external world state, i.e. how it will be drawn to the screen.
 #[derive(Debug)]
@@ -33,6 +74,31 @@ pub struct DrawEntity {
     pub y: i32,
     pub glyph: Glyph,
     pub hp: (u8, u8),
+
+    /// Whether the entity is a threat to the player (i.e. hostile to the town).
+    pub hostile: bool,
+
+    /// Whether the entity occupies its tile, rather than lying on the ground.
+    pub blocks: bool,
+
+    /// Whether the entity flies above its tile (and so is drawn over whatever occupies it).
+    pub flying: bool,
+
+    /// Which way the entity will move (or attack) on its next turn, if it has planned it.
+    pub intent: Option<Direction>,
+
+    /// Where the entity will shoot on its next turn, if it is taking aim.
+    pub aim: Option<(i32, i32)>,
+
+    /// The center of the area the entity is winding up to smash, if any, and how many of its
+    /// turns are left until it lands (1 for the next one).
+    pub smash: Option<((i32, i32), u8)>,
+
+    /// The combined effects of the auras the entity is in.
+    pub buffs: Vec<AuraEffect>,
+
+    /// How far the crops on it have grown, if it is a farm.
+    pub crops: Option<CropStage>,
 }
 
 /// Statistics used to draw the player's UI.
@@ -44,8 +110,8 @@ pub struct GameStats {
     /// Current and maximum health.
     pub health: (u8, u8),
 
-    /// Amount of $ for the player.
-    pub money: u8,
+    /// Resources in store for the player.
+    pub resources: Resources,
 
     /// Farms remaining.
     pub farms: u8,
@@ -53,12 +119,15 @@ pub struct GameStats {
     /// Houses remaining.
     pub houses: u8,
 
+    /// Current and maximum health of the boss, if one is in the fight.
+    pub boss: Option<(u8, u8)>,
+
     /// State of the game.
     pub state: GameState,
 }
 
 /// Possible states that the game can be in.
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum GameState {
     /// The player has been defeated.
     GameOver,
@@ -98,6 +167,9 @@ pub enum MovementDenied {
     /// Either would be out of bounds or moving through impassable terrain.
     Impassable,
 
+    /// The player is trying to move diagonally, but only four-directional movement is enabled.
+    Diagonal,
+
     /// The player is trying to move into a friendly unit or building.
     ///
     /// The next time this same directional input is given, the player will attack the unit/terrain.
@@ -105,29 +177,36 @@ pub enum MovementDenied {
 }
 
 impl WorldState {
-    pub fn new() -> Self {
-        let mut ecs = World::new();
+    /// Creates a new game on a map of the given dimensions, generated with the given strategy.
+    ///
+    /// # Panics
+    ///
+    /// If width or height is not between [`MIN_MAP_SIZE`] and [`MAX_MAP_SIZE`].
+    pub fn new(width: usize, height: usize, strategy: Box<dyn LevelStrategy>) -> Self {
+        assert!((MIN_MAP_SIZE..=MAX_MAP_SIZE).contains(&width));
+        assert!((MIN_MAP_SIZE..=MAX_MAP_SIZE).contains(&height));
 
-        // Register all of our components.
-        ecs.register::<components::Position>();
-        ecs.register::<components::Renderable>();
-        ecs.register::<components::Player>();
-        ecs.register::<components::Monster>();
-        ecs.register::<components::Town>();
-        ecs.register::<components::AI>();
-        ecs.register::<components::Moving>();
-        ecs.register::<components::Health>();
-        ecs.register::<components::Attacking>();
-        ecs.register::<components::Defeated>();
+        let mut ecs = Self::create_world();
 
         // Start the demo.
         let mut rng = RandomNumberGenerator::new();
-        let mut level_generator = LevelGenerator::new(12, 12);
-        let level_items = level_generator.generate(&mut rng, 2, 0.15);
-        let player_entity = LevelGenerator::insert(&mut ecs, level_items);
-
-        // Insert the map and initial running state.
-        ecs.insert(Map::new(12, 12));
+        // Small maps are too cramped for rivers; larger ones get one more per 10 tiles.
+        let mut level_generator = LevelGenerator::new(width, height)
+            .with_parameters(TerrainParameters {
+                rivers: (width.min(height) / 10) as u8,
+                ..TerrainParameters::default()
+            })
+            .with_strategy(strategy);
+        let level = level_generator
+            .generate(&mut rng, 2, 0.15)
+            .expect("maps of any supported size can be generated");
+        let player_entity = LevelGenerator::insert(&mut ecs, level.items);
+
+        // Insert the map (with some food in store) and initial running state.
+        let mut map = Map::with_terrain(level.terrain);
+        map.resources.food = STARTING_FOOD;
+        ecs.insert(FogOfWar::new(width, height));
+        ecs.insert(map);
         ecs.insert(RunState::PreRun);
         ecs.insert(Logs::new());
         ecs.insert(rng);
@@ -138,12 +217,120 @@ impl WorldState {
             player_entity: player_entity.expect("A player entity must be present"),
         };
 
-        // Spawn the monsters.
+        // Move the villagers in, then spawn the monsters.
+        village::spawn_villagers(&mut it.ecs);
         it.spawn_monsters();
 
         it
     }
 
+    /// Imports a board (see [`board`]) as a new world, without spawning any additional monsters.
+    ///
+    /// Fails if the board cannot be parsed, or its width or height is not between
+    /// [`MIN_MAP_SIZE`] and [`MAX_MAP_SIZE`].
+    pub fn import_board(text: &str) -> Result<Self, board::BoardError> {
+        let board = board::parse(text)?;
+        board::check_size(&board)?;
+        Ok(Self::from_board(board))
+    }
+
+    /// Creates a new world from a parsed board, whatever its size (e.g. a small test scenario).
+    fn from_board(board: board::Board) -> Self {
+        let mut ecs = Self::create_world();
+        let player_entity = board::insert(&mut ecs, &board);
+
+        // Insert the map and initial running state.
+        ecs.insert(FogOfWar::new(board.width, board.height));
+        ecs.insert(Map::with_terrain(board.terrain));
+        ecs.insert(RunState::PreRun);
+        ecs.insert(Logs::new());
+        ecs.insert(RandomNumberGenerator::new());
+
+        // Keep generating with the strategy the board was generated with (e.g. for new houses).
+        let mut generator = LevelGenerator::new(board.width, board.height);
+        if let Some(strategy) = board.level.as_deref().and_then(level_strategy::by_name) {
+            generator = generator.with_strategy(strategy);
+        }
+        ecs.insert(generator);
+
+        Self {
+            ecs,
+            player_entity: player_entity.expect("A parsed board always has a player"),
+        }
+    }
+
+    /// Turns on fog of war: only what the player and houses can see is shown.
+    pub fn with_fog_of_war(self) -> Self {
+        self.ecs.fetch_mut::<FogOfWar>().set_enabled(true);
+        self
+    }
+
+    /// Turns on eight-directional movement: the player and monsters can also move diagonally.
+    pub fn with_diagonal_movement(self) -> Self {
+        self.ecs
+            .fetch_mut::<Map>()
+            .set_movement(Neighborhood::Eight);
+        self
+    }
+
+    /// Exports the current board as text (see [`board`]).
+    ///
+    /// Everything on the map is exported, even what is hidden by fog of war, but villagers
+    /// sheltering inside houses are not.
+    pub fn export_board(&self) -> String {
+        let map = self.ecs.fetch::<Map>();
+        let generator = self.ecs.fetch::<LevelGenerator>();
+
+        // Only record the strategy if it isn't the default.
+        let level = generator.strategy().name();
+        let level = level_strategy::all()
+            .first()
+            .filter(|default| default.name() != level)
+            .map(|_| level);
+
+        board::export(&self.entities(), &map.terrain_rows(), level)
+    }
+
+    /// Creates an empty world with all of our components registered.
+    fn create_world() -> World {
+        let mut ecs = World::new();
+
+        // Register all of our components.
+        ecs.register::<components::Position>();
+        ecs.register::<components::Renderable>();
+        ecs.register::<components::Player>();
+        ecs.register::<components::Faction>();
+        ecs.register::<components::Blocks>();
+        ecs.register::<components::Flying>();
+        ecs.register::<components::Vision>();
+        ecs.register::<components::AI>();
+        ecs.register::<components::Intent>();
+        ecs.register::<components::Memory>();
+        ecs.register::<components::Moving>();
+        ecs.register::<components::Health>();
+        ecs.register::<components::Attacking>();
+        ecs.register::<components::Defeated>();
+        ecs.register::<components::Boss>();
+        ecs.register::<components::Smashing>();
+        ecs.register::<components::Summoning>();
+        ecs.register::<components::Ranged>();
+        ecs.register::<components::Shooting>();
+        ecs.register::<components::Stolen>();
+        ecs.register::<components::Raided>();
+        ecs.register::<components::OnDeath>();
+        ecs.register::<components::Aura>();
+        ecs.register::<components::Buffs>();
+        ecs.register::<components::Tending>();
+        ecs.register::<components::Growth>();
+        ecs.register::<components::Sheltering>();
+        ecs.register::<components::Occupants>();
+
+        // Set the town and the monsters against each other.
+        ecs.insert(Relations::new());
+
+        ecs
+    }
+
     fn rng(&self) -> FetchMut<RandomNumberGenerator> {
         self.ecs.fetch_mut::<RandomNumberGenerator>()
     }
@@ -177,11 +364,16 @@ impl WorldState {
                 self.run_systems();
 
                 // If monsters have been eliminated, switch to building turn.
-                let monsters = self
-                    .ecs
-                    .read_storage::<components::Monster>()
-                    .join()
-                    .count();
+                let monsters = {
+                    let relations = self.ecs.fetch::<Relations>();
+                    self.ecs
+                        .read_storage::<components::Faction>()
+                        .join()
+                        .filter(|f| {
+                            relations.get(components::Faction::Town, **f) == Relation::Hostile
+                        })
+                        .count()
+                };
                 if monsters == 0 {
                     self.switch_to_building_turn();
 
@@ -220,11 +412,25 @@ impl WorldState {
                 h.reset();
             }
 
-            // Give 1 $ for each surviving house glyph.
-            map.money += map.houses;
+            // Crops grow a stage between rounds, then whatever is ripe is brought in as food.
+            let mut growth = self.ecs.write_storage::<components::Growth>();
+            let mut harvests = 0_usize;
+            for growth in (&mut growth).join() {
+                growth.advance();
+                if growth.harvest() {
+                    harvests += 1;
+                }
+            }
+            let harvested = u8::try_from(harvests)
+                .unwrap_or(u8::MAX)
+                .saturating_mul(HARVEST_FOOD);
+            map.resources.food = map.resources.food.saturating_add(harvested);
+            self.ecs.write_storage::<components::Raided>().clear();
 
-            // Give 2 $ for each surviving farm glyph.
-            map.money += map.farms * 2;
+            // Every surviving house eats 1 food, and pays 1 gold if it did not go hungry.
+            let fed = map.houses.min(map.resources.food);
+            map.resources.food -= fed;
+            map.resources.gold = map.resources.gold.saturating_add(fed);
 
             // Move to turn building phase.
             let mut run_state = self.ecs.fetch_mut::<RunState>();
@@ -242,29 +448,42 @@ impl WorldState {
             return Err(MovementDenied::NotPlayerTurn);
         }
 
-        // If the move is out of bounds, don't do anything.
+        // If the move is out of bounds or onto impassable terrain, don't do anything.
         let mut map = self.ecs.fetch_mut::<Map>();
         let position = self.ecs.read_storage::<components::Position>();
         let position = position.get(self.player_entity);
         if position.is_none() {
             return Err(MovementDenied::GameOver);
         }
-        let position = position.unwrap();
-        let position = position.after(&direction);
-        if !map.in_bounds(position.x, position.y) {
+        let from = position.unwrap();
+        if direction.is_diagonal() && map.movement() == Neighborhood::Four {
+            return Err(MovementDenied::Diagonal);
+        }
+        if !map.can_step(from, &direction) {
             return Err(MovementDenied::Impassable);
         }
+        let position = from.after(&direction);
 
         // If the game is over, don't do anything.
         if map.houses == 0 {
             return Err(MovementDenied::GameOver);
         }
 
-        // If the player is trying to move into a non-monster, ignore the first time.
-        let entity = map.get_entity(position.x, position.y);
+        // If the player is trying to move into something not hostile, ignore the first time (unless
+        // it is ripe crops, which are harvested right away).
+        let entity = map.get_blocker(position.x, position.y, Layer::Ground);
         if let Some(entity) = entity {
-            let monster = self.ecs.read_storage::<components::Monster>();
-            if monster.get(entity).is_none() && !map.allow_move_into_friendly(direction.clone()) {
+            let relations = self.ecs.fetch::<Relations>();
+            let factions = self.ecs.read_storage::<components::Faction>();
+            let ripe = self
+                .ecs
+                .read_storage::<components::Growth>()
+                .get(entity)
+                .is_some_and(|growth| growth.is_ripe());
+            if !ripe
+                && !relations.is_hostile(&factions, self.player_entity, entity)
+                && !map.allow_move_into_friendly(direction.clone())
+            {
                 return Err(MovementDenied::Friendly);
             }
         }
@@ -280,7 +499,7 @@ impl WorldState {
 
     /// Build a structure at the given position.
     ///
-    /// Note that only walls and farms can be built.
+    /// Note that only walls, farms, and watchtowers can be built.
     ///
     /// Returns true if the build was successful, false otherwise.
     #[allow(dead_code)]
@@ -295,30 +514,23 @@ impl WorldState {
         {
             let mut map = self.ecs.fetch_mut::<Map>();
 
-            // Check if the player has enough money.
+            // Check if the player has enough resources.
             let cost = match what {
-                Glyph::Wall => 1,
-                Glyph::Farm => 2,
+                Glyph::Wall | Glyph::Farm | Glyph::Tower => resources::cost(what).unwrap(),
                 _ => return false,
             };
-            if map.money < cost {
+            if !map.resources.can_afford(&cost) {
                 return false;
             }
 
             // Check if the position is valid.
             let (x, y) = position;
-            if map.get_entity(x, y).is_some() {
+            if map.get_entity(x, y).is_some() || !map.get_terrain(x, y).can_build(what) {
                 return false;
             }
 
             // Subtract the cost.
-            map.money -= cost;
-
-            // If we are out of money
-            if map.money == 0 {
-                drop(map);
-                self.next_round();
-            }
+            map.resources.spend(&cost);
         }
 
         // Build the structure.
@@ -331,9 +543,81 @@ impl WorldState {
             Glyph::Farm => {
                 demo::configure_farm(entity, x, y).build();
             }
+            Glyph::Tower => {
+                demo::configure_tower(entity, x, y).build();
+            }
             _ => return false,
         }
 
+        // If nothing else can be afforded, start the next round (after building, so nothing
+        // spawns here).
+        if !resources::can_afford_anything(&self.ecs.fetch::<Map>().resources) {
+            self.next_round();
+        }
+
+        true
+    }
+
+    /// Recruit an allied unit from the house at the given position.
+    ///
+    /// Note that only militia, guards, and rangers can be recruited. The recruit takes the first
+    /// free tile next to the house.
+    ///
+    /// Returns true if the recruitment was successful, false otherwise.
+    #[allow(dead_code)]
+    pub fn player_recruit(&mut self, position: (i32, i32), what: Glyph) -> bool {
+        // If we're not in the building phase, don't do anything.
+        let run_state = { *self.ecs.fetch_mut::<RunState>() };
+        if run_state != RunState::BuildingTurn {
+            return false;
+        }
+
+        // Find where the recruit goes.
+        let spot = {
+            let mut map = self.ecs.fetch_mut::<Map>();
+
+            // Check if the player has enough resources.
+            let cost = match what {
+                Glyph::Militia | Glyph::Guard | Glyph::Ranger => resources::cost(what).unwrap(),
+                _ => return false,
+            };
+            if !map.resources.can_afford(&cost) {
+                return false;
+            }
+
+            // Check if there is a house with room next to it.
+            let (x, y) = position;
+            let renderables = self.ecs.read_storage::<components::Renderable>();
+            let is_house = map
+                .get_entity(x, y)
+                .and_then(|e| renderables.get(e))
+                .is_some_and(|r| r.glyph() == Glyph::House);
+            if !is_house {
+                return false;
+            }
+            let spot = map
+                .neighbors(&components::Position::new(x, y), map.movement())
+                .into_iter()
+                .find(|p| map.get_entity(p.x, p.y).is_none() && map.is_passable(p.x, p.y));
+            let Some(spot) = spot else {
+                return false;
+            };
+
+            // Subtract the cost.
+            map.resources.spend(&cost);
+            spot
+        };
+
+        // Recruit the unit.
+        demo::configure(what, self.ecs.create_entity(), spot.x, spot.y).build();
+        map::MapIndexingSystem.run_now(&self.ecs);
+
+        // If nothing else can be afforded, start the next round (after recruiting, so nothing
+        // spawns here).
+        if !resources::can_afford_anything(&self.ecs.fetch::<Map>().resources) {
+            self.next_round();
+        }
+
         true
     }
 
@@ -343,6 +627,9 @@ impl WorldState {
         let mut run_state = self.ecs.fetch_mut::<RunState>();
         *run_state = RunState::PreRun;
         drop(run_state);
+
+        // Move villagers into every house that lost its own (or is new), then call the monsters.
+        village::spawn_villagers(&mut self.ecs);
         self.spawn_monsters();
     }
 
@@ -356,6 +643,15 @@ impl WorldState {
             vec![None; map.width()]; map.height()
         ];
 
+        // Add placeholders (e.g. trees) to the grid for terrain that cannot be built on.
+        for (y, row) in map.terrain_rows().into_iter().enumerate() {
+            for (x, terrain) in row.into_iter().enumerate() {
+                if !terrain.can_build(Glyph::House) {
+                    grid[y][x] = Some(LevelItem::Tree);
+                }
+            }
+        }
+
         // Add placeholders (e.g. trees) to the grid for every entity.
         let positions = self.ecs.read_storage::<components::Position>();
         for position in (&positions).join() {
@@ -375,15 +671,17 @@ impl WorldState {
             grid[y][x] = Some(LevelItem::House);
         }
 
-        // Re-use the level generator to find a new house position.
-        let mut generator = self.ecs.fetch_mut::<LevelGenerator>();
-        let position = generator.find_somewhat_adjacent_position(
+        // Re-use the level generator to find a new house position (if the map isn't full).
+        let generator = self.ecs.fetch::<LevelGenerator>();
+        let Some(position) = generator.find_somewhat_adjacent_position(
             &mut self.rng(),
             2,
             5,
             &LevelItem::House,
             &grid,
-        );
+        ) else {
+            return;
+        };
 
         // This is hacky but so is this entire function.
         drop(map);
@@ -407,6 +705,7 @@ impl WorldState {
     /// Indicates the player is ready, spawning goblins.
     #[allow(dead_code)]
     pub fn player_ready(&mut self) {
+        village::spawn_villagers(&mut self.ecs);
         self.spawn_monsters();
 
         // Change state to start the game again.
@@ -415,11 +714,21 @@ impl WorldState {
     }
 
     /// For the given round number, spawn R+3 goblins at the edge of the map.
+    ///
+    /// The number of goblins scales with the area of the map, relative to the default map.
     fn spawn_monsters(&mut self) {
-        let round_number = self.ecs.fetch::<Map>().round().get();
+        // Make sure the map knows about every entity, so monsters only spawn on open tiles.
+        map::MapIndexingSystem.run_now(&self.ecs);
+
+        let (round_number, area) = {
+            let map = self.ecs.fetch::<Map>();
+            (map.round().get(), map.width() * map.height())
+        };
 
         // Get the round number to determine how many goblins to spawn.
-        let mut monsters_to_spawn = round_number as usize + 3;
+        let default_area = DEFAULT_MAP_SIZE * DEFAULT_MAP_SIZE;
+        let mut monsters_to_spawn =
+            (((round_number as usize + 3) * area + default_area / 2) / default_area).max(1);
         let mut rats_to_spawn = 0;
 
         // After round 2, a goblin has a 20% chance of being 2 rats instead.
@@ -433,10 +742,10 @@ impl WorldState {
             monsters_to_spawn += rats_to_spawn;
         }
 
-        let positions: Vec<(i32, i32)> = {
+        let positions: Vec<components::Position> = {
             // Get the map and level generator.
             let map = self.ecs.fetch::<Map>();
-            let mut generator = self.ecs.fetch_mut::<LevelGenerator>();
+            let generator = self.ecs.fetch::<LevelGenerator>();
 
             // Start at the edge of the map, and move inwards if we can't find a position.
             let mut from_edge = 0;
@@ -445,8 +754,7 @@ impl WorldState {
             let mut positions = Vec::new();
             while positions.len() < monsters_to_spawn {
                 // Make a list of all positions from_edge tiles away from the edge.
-                let mut positions_to_try = Vec::new();
-
+                //
                 // For example in the following grid:
                 // x x x x
                 // x     x
@@ -458,34 +766,24 @@ impl WorldState {
                 //
                 //   x x
                 //   x x
+                let mut positions_to_try = map.ring_from_edge(from_edge);
 
-                // Top row.
-                for x in from_edge..(map.width() - from_edge) {
-                    positions_to_try.push((x, from_edge));
-                }
-
-                // Bottom row.
-                for x in from_edge..(map.width() - from_edge) {
-                    positions_to_try.push((x, map.height() - from_edge - 1));
-                }
-
-                // Left column.
-                for y in from_edge..(map.height() - from_edge) {
-                    positions_to_try.push((from_edge, y));
-                }
-
-                // Right column.
-                for y in from_edge..(map.height() - from_edge) {
-                    positions_to_try.push((map.width() - from_edge - 1, y));
+                // Every tile has been tried, the map is full.
+                if positions_to_try.is_empty() {
+                    break;
                 }
 
                 // Shuffle the list.
                 generator.shuffle(&mut self.rng(), &mut positions_to_try);
 
-                // Try to find a position that is not occupied.
-                for (x, y) in positions_to_try {
-                    if map.get_entity(x as i32, y as i32).is_none() {
-                        positions.push((x as i32, y as i32));
+                // Try to find a position that is not occupied and can be walked on.
+                for position in positions_to_try {
+                    if map
+                        .get_blocker(position.x, position.y, Layer::Ground)
+                        .is_none()
+                        && map.is_passable(position.x, position.y)
+                    {
+                        positions.push(position);
                     }
                 }
 
@@ -498,20 +796,39 @@ impl WorldState {
 
         // Spawn the goblins.
         // After level 2, (e.g. starting at 3) L - 2 goblins are actually orcs.
-        let mut orcs = {
+        // From level 2, one goblin for every two farms (rounded up) is actually a thief.
+        // After level 3, (e.g. starting at 4) L - 3 goblins are actually archers.
+        // Every few levels, the first goblin is actually a boss.
+        // From level 3, one goblin in every three levels (rounded down) is actually a bat.
+        // From level 4, one goblin in every four levels (rounded down) is actually a slime.
+        // After level 5, one goblin in every two levels (rounded down) is actually a shaman.
+        let (mut orcs, mut thieves, mut archers, mut bosses, mut bats, mut slimes, mut shamans) = {
             let map = self.ecs.fetch::<Map>();
             let round = map.round().get();
-            if round >= 3 {
-                round - 2
-            } else {
-                0
-            }
+            let orcs = if round >= 3 { round - 2 } else { 0 };
+            let thieves = if round >= 2 { map.farms.div_ceil(2) } else { 0 };
+            let archers = round.saturating_sub(3);
+            let bosses = u8::from(round.is_multiple_of(BOSS_EVERY));
+            let bats = round / 3;
+            let slimes = round / 4;
+            let shamans = round.saturating_sub(4) / 2;
+            (orcs, thieves, archers, bosses, bats, slimes, shamans)
         };
-        for (x, y) in positions.into_iter().take(monsters_to_spawn) {
+        for components::Position { x, y } in positions.into_iter().take(monsters_to_spawn) {
             // Create a blank entity.
             let entity = self.ecs.create_entity();
 
-            // First, spawn rats.
+            // First, spawn the boss.
+            if bosses > 0 {
+                bosses -= 1;
+                demo::configure_ogre(entity, x, y).build();
+                self.ecs
+                    .fetch_mut::<Logs>()
+                    .add(LogMessage::BossArrived { boss: Glyph::Ogre });
+                continue;
+            }
+
+            // Next, spawn rats.
             if rats_to_spawn > 0 {
                 rats_to_spawn -= 1;
                 demo::configure_rat(entity, x, y).build();
@@ -525,6 +842,41 @@ impl WorldState {
                 continue;
             }
 
+            // Next, spawn thieves.
+            if thieves > 0 {
+                thieves -= 1;
+                demo::configure_thief(entity, x, y).build();
+                continue;
+            }
+
+            // Next, spawn bats.
+            if bats > 0 {
+                bats -= 1;
+                demo::configure_bat(entity, x, y).build();
+                continue;
+            }
+
+            // Next, spawn archers.
+            if archers > 0 {
+                archers -= 1;
+                demo::configure_archer(entity, x, y).build();
+                continue;
+            }
+
+            // Next, spawn slimes.
+            if slimes > 0 {
+                slimes -= 1;
+                demo::configure_slime(entity, x, y).build();
+                continue;
+            }
+
+            // Next, spawn shamans.
+            if shamans > 0 {
+                shamans -= 1;
+                demo::configure_shaman(entity, x, y).build();
+                continue;
+            }
+
             // Otherwise, spawn goblins.
             demo::configure_goblin(entity, x, y).build();
         }
@@ -534,8 +886,20 @@ impl WorldState {
         // Index the map.
         map::MapIndexingSystem.run_now(&self.ecs);
 
-        // Let the monsters do their thing.
-        ai::AISystem.run_now(&self.ecs);
+        // Heal whatever stands in a regeneration aura.
+        aura::RegenerationSystem.run_now(&self.ecs);
+
+        // Let the monsters do what they planned to do.
+        ai::ExecuteIntentSystem.run_now(&self.ecs);
+
+        // Let thieves leave the map.
+        theft::EscapeSystem.run_now(&self.ecs);
+
+        // Convert shots into ranged combat if the target is still in the line of fire.
+        combat::RangedAttackSystem.run_now(&self.ecs);
+
+        // Let watchtowers shoot at fliers.
+        combat::InterceptSystem.run_now(&self.ecs);
 
         // Convert movement into combat if necessary.
         combat::ConvertMovementToMeleeAttackSystem.run_now(&self.ecs);
@@ -543,41 +907,142 @@ impl WorldState {
         // Apply movement.
         movement::MovementSystem.run_now(&self.ecs);
 
+        // Grow crops, faster where villagers work the farms, and let villagers hide in their homes.
+        farming::GrowthSystem.run_now(&self.ecs);
+        village::TendSystem.run_now(&self.ecs);
+        village::ShelterSystem.run_now(&self.ecs);
+
+        // Pick up anything the player walked onto.
+        theft::PickUpSystem.run_now(&self.ecs);
+
+        // Steal from farms instead of attacking them.
+        theft::StealSystem.run_now(&self.ecs);
+
+        // Harvest ripe crops the player walked into, and trample those monsters attacked.
+        farming::HarvestSystem.run_now(&self.ecs);
+
+        // Cut wood from the trees the player hits.
+        resources::WoodcuttingSystem.run_now(&self.ecs);
+
         // Apply combat.
         combat::ApplyAttackSystem.run_now(&self.ecs);
 
+        // Bring down smashes.
+        boss::SmashSystem.run_now(&self.ecs);
+
         // Defeat entities.
         combat::DefeatSystem.run_now(&self.ecs);
 
+        // Trigger death effects, e.g. split slimes or drop stolen crops.
+        death::trigger_death_effects(&mut self.ecs);
+
         // Remove defeated entities.
         combat::RemoveDefeatedSystem.run_now(&self.ecs);
 
         // Maintain the ECS (i.e. built-in systems).
         self.ecs.maintain();
+
+        // Call in minions, now that the defeated are out of the way.
+        boss::summon_minions(&mut self.ecs);
+
+        // Let villagers out of houses that are safe again.
+        village::leave_shelter(&mut self.ecs);
+
+        // Update what can be seen from where everything ended up.
+        map::MapIndexingSystem.run_now(&self.ecs);
+        fov::FieldOfViewSystem.run_now(&self.ecs);
+
+        // Recalculate what every aura reaches.
+        aura::AuraSystem.run_now(&self.ecs);
+
+        // Let the monsters plan their next turn (shown to the player before it happens).
+        ai::DecideIntentSystem.run_now(&self.ecs);
     }
 
     /// Convert the world state into a representation that can be drawn to the screen.
+    ///
+    /// With fog of war, nothing on unexplored tiles is shown, and monsters are only shown while
+    /// they are in sight.
     pub fn to_render(&self) -> Vec<DrawEntity> {
+        let fog = self.ecs.fetch::<FogOfWar>();
+        self.entities()
+            .into_iter()
+            .filter(|d| match fog.get(d.x, d.y) {
+                TileVisibility::Visible => true,
+                TileVisibility::Remembered => !d.hostile,
+                TileVisibility::Unexplored => false,
+            })
+            .collect()
+    }
+
+    /// Returns every entity that can be drawn, ignoring fog of war.
+    fn entities(&self) -> Vec<DrawEntity> {
         let mut drawables = Vec::new();
 
         // Get all of the entities that have a position and renderable component.
         let positions = self.ecs.read_storage::<components::Position>();
         let renderables = self.ecs.read_storage::<components::Renderable>();
         let health = self.ecs.read_storage::<components::Health>();
+        let relations = self.ecs.fetch::<Relations>();
+        let factions = self.ecs.read_storage::<components::Faction>();
+        let blocks = self.ecs.read_storage::<components::Blocks>();
+        let intents = self.ecs.read_storage::<components::Intent>();
+        let flying = self.ecs.read_storage::<components::Flying>();
+        let buffs = self.ecs.read_storage::<components::Buffs>();
+        let growth = self.ecs.read_storage::<components::Growth>();
 
         // Iterate over all of the entities that have a position and renderable component.
-        for (pos, render, hp) in (&positions, &renderables, &health).join() {
+        for (pos, render, hp, faction, blocks, intent, flying, buffs, growth) in (
+            &positions,
+            &renderables,
+            health.maybe(),
+            factions.maybe(),
+            blocks.maybe(),
+            intents.maybe(),
+            flying.maybe(),
+            buffs.maybe(),
+            growth.maybe(),
+        )
+            .join()
+        {
             drawables.push(DrawEntity {
                 x: pos.x,
                 y: pos.y,
                 glyph: render.glyph(),
-                hp: (hp.amount(), hp.maximum()),
+                hp: hp.map_or((1, 1), |hp| (hp.amount(), hp.maximum())),
+                hostile: faction.is_some_and(|f| {
+                    relations.get(components::Faction::Town, *f) == Relation::Hostile
+                }),
+                blocks: blocks.is_some(),
+                flying: flying.is_some(),
+                intent: intent.and_then(|i| i.direction().cloned()),
+                aim: intent
+                    .and_then(|i| i.target())
+                    .and_then(|t| positions.get(t))
+                    .map(|p| (p.x, p.y)),
+                smash: intent
+                    .and_then(|i| i.smash())
+                    .map(|s| ((s.center.x, s.center.y), s.turns)),
+                buffs: buffs.map_or_else(Vec::new, |b| b.effects()),
+                crops: growth.map(|g| g.stage()),
             });
         }
 
+        // Items on the ground first, so whatever occupies the tile is drawn on top, then fliers.
+        drawables.sort_by_key(|d| (d.flying, d.blocks));
         drawables
     }
 
+    /// Returns the terrain as rows, e.g. to be drawn underneath [`WorldState::to_render`].
+    pub fn terrain(&self) -> Vec<Vec<Terrain>> {
+        self.ecs.fetch::<Map>().terrain_rows()
+    }
+
+    /// Returns how much of each tile can be seen as rows (all visible without fog of war).
+    pub fn visibility(&self) -> Vec<Vec<TileVisibility>> {
+        self.ecs.fetch::<FogOfWar>().rows()
+    }
+
     /// Returns the current game logs, clearing them in the process.
     pub fn get_logs(&mut self) -> Vec<LogMessage> {
         // Get the logs struct.
@@ -598,11 +1063,21 @@ impl WorldState {
                 .unwrap_or((0, 0))
         };
 
-        // Get the money and round number.
-        let (money, round, farms, houses) = {
+        // Get the resources and round number.
+        let (resources, round, farms, houses) = {
             let map = self.ecs.fetch::<Map>();
 
-            (map.money, map.round(), map.farms, map.houses)
+            (map.resources, map.round(), map.farms, map.houses)
+        };
+
+        // Get the boss's HP, if there is one.
+        let boss = {
+            let bosses = self.ecs.read_storage::<components::Boss>();
+            let health = self.ecs.read_storage::<components::Health>();
+            (&bosses, &health)
+                .join()
+                .next()
+                .map(|(_, h)| (h.amount(), h.maximum()))
         };
 
         let state = {
@@ -622,9 +1097,10 @@ impl WorldState {
         GameStats {
             round,
             health,
-            money,
+            resources,
             farms,
             houses,
+            boss,
             state,
         }
     }
@@ -634,3 +1110,132 @@ impl WorldState {
         (map.width() as i32, map.height() as i32)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        board::BoardErrorKind,
+        level_strategy::{Fortress, Village},
+        scenario::{Input, InputResult, Scenario},
+        Direction, GameState, Glyph, Resources, WorldState, MAX_MAP_SIZE, MIN_MAP_SIZE,
+    };
+
+    #[test]
+    fn building_spends_resources_on_open_tiles() {
+        let outcome = Scenario::new(
+            "
+            .  . . . . .
+            h2 . . . . .
+            @5 g . . . .
+            ---
+            , , , , , ~
+            , , , , , ,
+            , , , , % ,
+            ",
+        )
+        .resources(Resources::new(2, 2, 1))
+        .input(Input::Move(Direction::Right))
+        .input(Input::Build((5, 0), Glyph::Wall))
+        .input(Input::Build((5, 2), Glyph::Farm))
+        .input(Input::Build((2, 1), Glyph::Wall))
+        .input(Input::Build((4, 2), Glyph::Farm))
+        .run();
+
+        // Killing the last goblin feeds the house, which pays 1 gold, and a new house is spawned.
+        // Nothing can be built on water, farms need soil, and the new house is in the way.
+        assert!(matches!(
+            outcome.results[..],
+            [
+                InputResult::Moved(Ok(())),
+                InputResult::Built(false),
+                InputResult::Built(false),
+                InputResult::Built(false),
+                InputResult::Built(true),
+            ]
+        ));
+        assert_eq!(outcome.stats.resources, Resources::new(2, 1, 0));
+        assert_eq!(outcome.stats.state, GameState::WaitingForBuild);
+        outcome.assert_board(
+            "
+            .  . .  . . .
+            h2 . h2 . . .
+            @5 . .  . f .
+            ---
+            , , , , , ~
+            , , , , , ,
+            , , , , % ,
+            ",
+        );
+    }
+
+    #[test]
+    fn recruiting_from_houses() {
+        let outcome = Scenario::new(
+            "
+            .  . . . . .
+            h2 . . . . .
+            @5 g . . . .
+            ",
+        )
+        .resources(Resources::new(2, 1, 3))
+        .input(Input::Move(Direction::Right))
+        .input(Input::Recruit((0, 0), Glyph::Militia))
+        .input(Input::Recruit((0, 1), Glyph::Militia))
+        .input(Input::Recruit((0, 1), Glyph::Guard))
+        .input(Input::Recruit((0, 1), Glyph::Ranger))
+        .run();
+
+        // Only houses recruit, and only as long as there is food left.
+        assert!(matches!(
+            outcome.results[..],
+            [
+                InputResult::Moved(Ok(())),
+                InputResult::Recruited(false),
+                InputResult::Recruited(true),
+                InputResult::Recruited(true),
+                InputResult::Recruited(false),
+            ]
+        ));
+        assert_eq!(outcome.stats.resources, Resources::new(1, 1, 0));
+        assert!(outcome.board.contains('m') && outcome.board.contains('G'));
+    }
+
+    #[test]
+    fn boards_record_the_level_strategy() {
+        let fortress = WorldState::new(12, 12, Box::new(Fortress)).export_board();
+        assert!(fortress.starts_with("level fortress\n"));
+
+        // Importing keeps the strategy, so exporting again gives the same board.
+        let imported = WorldState::import_board(&fortress).ok().unwrap();
+        assert_eq!(imported.export_board(), fortress);
+
+        // The default strategy is not recorded.
+        let village = WorldState::new(12, 12, Box::new(Village)).export_board();
+        assert!(!village.starts_with("level"));
+    }
+
+    #[test]
+    fn imported_boards_must_be_playable_sizes() {
+        // An empty board of the given size, with the player in the corner.
+        let board = |width: usize, height: usize| {
+            let mut rows = vec![vec!["."; width]; height];
+            rows[0][0] = "@5";
+            rows.iter()
+                .map(|row| row.join(" "))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        assert!(WorldState::import_board(&board(MIN_MAP_SIZE, MIN_MAP_SIZE)).is_ok());
+        for (width, height) in [
+            (MIN_MAP_SIZE - 1, MIN_MAP_SIZE),
+            (MIN_MAP_SIZE, MIN_MAP_SIZE - 1),
+            (MAX_MAP_SIZE + 1, MIN_MAP_SIZE),
+        ] {
+            let error = WorldState::import_board(&board(width, height))
+                .err()
+                .unwrap();
+            assert_eq!(error.kind, BoardErrorKind::Size { width, height });
+        }
+    }
+}
@@ -1,112 +1,714 @@
+//! Monster AI.
+//!
+//! Monsters plan a turn ahead: [`DecideIntentSystem`] picks what each one will do and stores it
+//! as an [`Intent`] (which is shown to the player), and [`ExecuteIntentSystem`] carries it out on
+//! the following monster turn. The player can get out of the way in the meantime, but stepping
+//! next to a monster that was only going to move draws its attack instead.
+//!
+//! Deciding uses utility scoring: each [`AI`] considers a few [`Action`]s, scores how useful each
+//! one is right now, and picks the best. Anything an AI needs to remember between turns (its
+//! target, whether it is retreating, ...) is kept in its [`Memory`].
+
+use std::collections::HashMap;
+
 use bracket_lib::random::RandomNumberGenerator;
 use specs::prelude::*;
 
 use super::{
-    components::{Monster, Moving, Player, Position, Town, AI},
-    RunState,
+    combat,
+    components::{
+        Faction, Flying, Health, Intent, Memory, Moving, Player, Position, Raided, Ranged,
+        Renderable, Sheltering, Shooting, Smash, Smashing, Stolen, Summoning, Tending, AI,
+    },
+    faction::{Relation, Relations},
+    fov,
+    map::{Layer, Map, Neighborhood},
+    pathfinding::DistanceField,
+    village::DANGER_RADIUS,
+    Glyph, RunState,
 };
 
-pub struct AISystem;
+/// How far (in tiles) a patrolling monster can see the player.
+const SIGHT_RADIUS: i32 = 4;
+
+/// How many turns a wounded monster retreats for.
+const RETREAT_TURNS: u8 = 3;
+
+/// How many monsters (including itself) a pack waits for before attacking.
+const PACK_SIZE: usize = 3;
+
+/// How close allies must be to count as part of the pack.
+const REGROUP_RADIUS: f64 = 2.0;
+
+/// How close the player must be for a boss to start winding up a smash.
+const SMASH_RANGE: f64 = 2.0;
 
-impl<'a> System<'a> for AISystem {
+/// How many monster turns a smash takes to land, counting the one it lands on.
+const SMASH_WINDUP: u8 = 2;
+
+/// How many turns a boss waits between summoning minions.
+const SUMMON_COOLDOWN: u8 = 6;
+
+/// How close (in a straight line) a follower stays to the player.
+const FOLLOW_DISTANCE: f64 = 1.5;
+
+/// A system that turns intents into movement (or attacks, or shots) on the monster's turn.
+pub struct ExecuteIntentSystem;
+
+impl<'a> System<'a> for ExecuteIntentSystem {
     type SystemData = (
         Entities<'a>,
         ReadExpect<'a, RunState>,
-        ReadStorage<'a, Monster>,
-        ReadStorage<'a, Town>,
-        ReadStorage<'a, Player>,
-        ReadStorage<'a, AI>,
-        ReadStorage<'a, Position>,
+        WriteStorage<'a, Intent>,
         WriteStorage<'a, Moving>,
-        WriteExpect<'a, RandomNumberGenerator>,
+        WriteStorage<'a, Shooting>,
+        WriteStorage<'a, Smashing>,
+        WriteStorage<'a, Summoning>,
+        WriteStorage<'a, Tending>,
+        WriteStorage<'a, Sheltering>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         // Unpack the system data.
-        let (entities, state, monsters, towns, players, ai, positions, mut moving, mut rng) = data;
+        let (
+            entities,
+            state,
+            mut intents,
+            mut moving,
+            mut shooting,
+            mut smashing,
+            mut summoning,
+            mut tending,
+            mut sheltering,
+        ) = data;
 
         // If this is not the monster's turn, do nothing.
         if *state != RunState::MonsterTurn {
             return;
         }
 
-        let player = (&players, &positions).join().next();
+        for (entity, intent) in (&entities, intents.drain()).join() {
+            if let Some(direction) = intent.direction() {
+                moving.insert(entity, direction.clone()).unwrap();
+            }
+            if let Some(target) = intent.target() {
+                shooting.insert(entity, Shooting::new(target)).unwrap();
+            }
+            if let Some(smash) = intent.smash().filter(|smash| smash.turns == 1) {
+                smashing
+                    .insert(entity, Smashing::new(smash.center.clone()))
+                    .unwrap();
+            }
+            if intent.summons() {
+                summoning.insert(entity, Summoning).unwrap();
+            }
+            if let Some(farm) = intent.tends() {
+                tending.insert(entity, Tending::new(farm)).unwrap();
+            }
+            if intent.shelters() {
+                sheltering.insert(entity, Sheltering).unwrap();
+            }
+        }
+    }
+}
+
+/// Something a monster can do on its turn.
+#[derive(Clone, Debug, PartialEq)]
+enum Action {
+    /// Step towards a position, attacking whatever is in the way.
+    Approach(Position),
+
+    /// Step away from the player.
+    Flee,
+
+    /// Shoot at an entity from where it stands.
+    Shoot(Entity),
+
+    /// Wind up (or keep winding up) a smash.
+    Smash,
+
+    /// Call in minions.
+    Summon,
+
+    /// Step in a random direction.
+    Wander,
+
+    /// Head for the nearest edge of the map, and off it.
+    Escape,
+
+    /// Work a farm from where it stands.
+    Tend(Entity),
+
+    /// Hide in its home from where it stands.
+    Shelter,
+
+    /// Stay where it is.
+    Wait,
+}
+
+/// What a monster knows when deciding what to do, beyond its [`Memory`].
+struct Situation {
+    /// Where the player is.
+    player: Position,
+
+    /// Whether the player is right next to it.
+    player_adjacent: bool,
+
+    /// How far away (as the crow flies) the player is.
+    player_distance: f64,
+
+    /// Where its remembered target is, if it has one.
+    target: Option<Position>,
+
+    /// Where the nearest other monster is, if there is one.
+    ally: Option<Position>,
+
+    /// Whether the nearest other monster is right next to it.
+    ally_adjacent: bool,
+
+    /// Whether it is carrying stolen crops.
+    carrying: bool,
+
+    /// What it could shoot at from where it stands, if it can shoot at all.
+    shot: Option<Entity>,
+
+    /// Where the nearest hostile right next to it is, if there is one.
+    enemy: Option<Position>,
+
+    /// Where its post is, if it has one and is not there.
+    post: Option<Position>,
+
+    /// Whether its remembered target is right next to it.
+    target_adjacent: bool,
+
+    /// Where its home is, if it has one, and whether it is right next to it.
+    home: Option<(Position, bool)>,
+
+    /// Whether anything hostile is within [`DANGER_RADIUS`].
+    danger: bool,
+}
+
+/// Scores every action an AI would consider: higher is better, and the best one is taken.
+fn consider(ai: &AI, situation: &Situation, memory: &Memory) -> Vec<(Action, f32)> {
+    // Doing nothing is always an option, if not a very useful one.
+    let mut options = vec![(Action::Wait, 0.0)];
+
+    // Monsters attack the player when it is next to them, unless they have a better idea (or
+    // are thieves, who never fight).
+    if situation.player_adjacent && !matches!(ai, AI::Thief) {
+        options.push((Action::Approach(situation.player.clone()), 0.9));
+    }
+
+    match ai {
+        AI::Wander => {
+            if memory.alerted {
+                options.push((Action::Approach(situation.player.clone()), 0.5));
+            } else {
+                options.push((Action::Wander, 0.5));
+            }
+        }
+        AI::PrioritizeTown => {
+            if memory.retreating > 0 {
+                options.push((Action::Flee, 1.0));
+            }
+            if let Some(target) = &situation.target {
+                options.push((Action::Approach(target.clone()), 0.5));
+            }
+        }
+        AI::PrioritizePlayer => {
+            if memory.gathered {
+                options.push((Action::Approach(situation.player.clone()), 0.5));
+            } else if let Some(ally) = &situation.ally {
+                options.push((Action::Approach(ally.clone()), 0.5));
+            }
+        }
+        AI::Thief => match &situation.target {
+            Some(farm) if !situation.carrying => {
+                options.push((Action::Approach(farm.clone()), 0.5));
+            }
+            _ => options.push((Action::Escape, 0.5)),
+        },
+        AI::Archer => {
+            if situation.player_adjacent {
+                options.push((Action::Flee, 1.0));
+            }
+            if let Some(target) = situation.shot {
+                options.push((Action::Shoot(target), 0.8));
+            }
+            options.push((Action::Approach(situation.player.clone()), 0.5));
+        }
+        AI::Boss => {
+            // Once started, a smash cannot be stopped.
+            if memory.smash.is_some() || situation.player_distance <= SMASH_RANGE {
+                options.push((Action::Smash, 1.0));
+            }
+            if memory.cooldown == 0 {
+                options.push((Action::Summon, 0.95));
+            }
+            options.push((Action::Approach(situation.player.clone()), 0.5));
+        }
+        AI::Support => {
+            if situation.player_adjacent {
+                options.push((Action::Flee, 1.0));
+            }
+            match &situation.ally {
+                // Close enough already; stepping into it would do nothing.
+                Some(_) if situation.ally_adjacent => {}
+                Some(ally) => options.push((Action::Approach(ally.clone()), 0.5)),
+                None => options.push((Action::Approach(situation.player.clone()), 0.5)),
+            }
+        }
+        AI::Guard | AI::Follow => {
+            if let Some(enemy) = &situation.enemy {
+                options.push((Action::Approach(enemy.clone()), 0.9));
+            }
+            if let Some(target) = situation.shot {
+                options.push((Action::Shoot(target), 0.8));
+            }
+            if let Some(post) = &situation.post {
+                options.push((Action::Approach(post.clone()), 0.5));
+            }
+            if matches!(ai, AI::Follow) && situation.player_distance > FOLLOW_DISTANCE {
+                options.push((Action::Approach(situation.player.clone()), 0.5));
+            }
+        }
+        AI::Hunt => {
+            if let Some(target) = &situation.target {
+                options.push((Action::Approach(target.clone()), 0.5));
+            }
+        }
+        AI::Villager => {
+            match &situation.home {
+                Some((_, true)) if situation.danger => options.push((Action::Shelter, 1.0)),
+                Some((home, false)) if situation.danger => {
+                    options.push((Action::Approach(home.clone()), 1.0));
+                }
+                _ => {}
+            }
+            match (memory.target, &situation.target) {
+                (Some(farm), Some(_)) if situation.target_adjacent => {
+                    options.push((Action::Tend(farm), 0.5));
+                }
+                (_, Some(farm)) => options.push((Action::Approach(farm.clone()), 0.5)),
+                _ => {}
+            }
+        }
+    }
+
+    options
+}
+
+/// Returns the best scored action, preferring earlier ones on ties.
+fn choose(options: Vec<(Action, f32)>) -> Action {
+    options
+        .into_iter()
+        .reduce(|best, option| if option.1 > best.1 { option } else { best })
+        .map_or(Action::Wait, |(action, _)| action)
+}
+
+/// A system that decides the next intent of every AI without one.
+///
+/// On the player's turn, monsters the player stepped next to reconsider their planned move, and
+/// attack the player instead if that is now the best thing to do.
+///
+/// Must run after [`MapIndexingSystem`](super::map::MapIndexingSystem), once everything has
+/// moved, so that plans are made from where everything ended up.
+pub struct DecideIntentSystem;
+
+impl<'a> System<'a> for DecideIntentSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, Relations>,
+        ReadExpect<'a, RunState>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, AI>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Health>,
+        ReadStorage<'a, Renderable>,
+        ReadStorage<'a, Stolen>,
+        ReadStorage<'a, Raided>,
+        ReadStorage<'a, Ranged>,
+        ReadStorage<'a, Flying>,
+        WriteStorage<'a, Intent>,
+        WriteStorage<'a, Memory>,
+        WriteExpect<'a, RandomNumberGenerator>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Unpack the system data.
+        let (
+            entities,
+            map,
+            relations,
+            state,
+            factions,
+            players,
+            ai,
+            positions,
+            healths,
+            renderables,
+            stolen,
+            raided,
+            ranged,
+            flying,
+            mut intents,
+            mut memories,
+            mut rng,
+        ) = data;
 
         // If there are no players, do nothing.
-        if player.is_none() {
+        let Some((player, _, player_position)) = (&entities, &players, &positions).join().next()
+        else {
             return;
+        };
+
+        // Find the walking distance from every tile to the player.
+        let player_field = DistanceField::new(&map, std::slice::from_ref(player_position));
+
+        // Find the walking (or flying) distance to anything else on demand, once per target.
+        let mut fields = HashMap::<((i32, i32), Layer), DistanceField>::new();
+
+        // Find the walking distance to the edge of the map on demand, once for every thief.
+        let mut edge_field = None;
+
+        // Monsters move (and so attack) like the player does.
+        let movement = map.movement();
+
+        // Monsters the player just stepped next to reconsider their planned move.
+        let reconsidering: BitSet = if *state == RunState::PlayerTurn {
+            (&entities, &positions, &intents)
+                .join()
+                .filter(|(_, _, intent)| intent.is_move())
+                .filter(|(entity, position, _)| {
+                    relations.is_hostile(&factions, *entity, player)
+                        && movement.is_adjacent(player_position, position)
+                })
+                .map(|(entity, _, _)| entity.id())
+                .collect()
+        } else {
+            BitSet::new()
+        };
+
+        // Count the allies of every faction's members once, rather than once per AI. Packs only
+        // gather on the ground, so fliers (and anything off the map) are not counted.
+        let mut members = HashMap::<Faction, usize>::new();
+        for (faction, _, _) in (&factions, &positions, !&flying).join() {
+            *members.entry(*faction).or_default() += 1;
         }
+        let allies: HashMap<Faction, usize> = members
+            .keys()
+            .map(|&a| {
+                let allied: usize = members
+                    .iter()
+                    .filter(|(&b, _)| relations.get(a, b) == Relation::Allied)
+                    .map(|(_, n)| n)
+                    .sum();
+                let itself = usize::from(relations.get(a, a) == Relation::Allied);
+                (a, allied.saturating_sub(itself))
+            })
+            .collect();
+
+        // Iterate through AI that have not decided what to do yet (or are reconsidering).
+        let undecided: Vec<_> = (&entities, &ai, &positions)
+            .join()
+            .filter(|(entity, _, _)| {
+                !intents.contains(*entity) || reconsidering.contains(entity.id())
+            })
+            .collect();
+        for (entity, ai, position) in undecided {
+            let reconsidering = intents.contains(entity);
+            let memory = memories
+                .entry(entity)
+                .unwrap()
+                .or_insert_with(Memory::default);
+            let layer = Layer::of(flying.contains(entity));
+            let hostile = |e| relations.is_hostile(&factions, entity, e);
+            // Allies to gather with: on the ground, fliers cannot be kept up with.
+            let ally = |e| {
+                e != entity
+                    && relations.is_allied(&factions, entity, e)
+                    && (layer == Layer::Air || !flying.contains(e))
+            };
+
+            // Update what the AI remembers.
+            match ai {
+                AI::Wander => {
+                    // Once the player has been seen, it is not forgotten.
+                    let opaque = |x, y| fov::blocks_sight(&map, &renderables, x, y);
+                    if fov::in_sight(&map, position, player_position, SIGHT_RADIUS, opaque) {
+                        memory.alerted = true;
+                    }
+                }
+                AI::PrioritizeTown => {
+                    // Keep the same target until it is destroyed.
+                    if memory.target.is_some_and(|t| positions.get(t).is_none()) {
+                        memory.target = None;
+                    }
+                    if memory.target.is_none() {
+                        // Fliers pass over walls, so they are no target.
+                        let in_the_way = |e| {
+                            layer == Layer::Ground
+                                || positions
+                                    .get(e)
+                                    .is_some_and(|p| map.get_blocker(p.x, p.y, layer) == Some(e))
+                        };
+                        memory.target = map
+                            .nearest_entity(position, |e| {
+                                !players.contains(e) && hostile(e) && in_the_way(e)
+                            })
+                            .map(|(e, _)| e);
+                    }
 
-        // Find all town entities on the map.
-        let town_positions: Vec<Position> = {
-            let mut town_positions: Vec<Position> = Vec::new();
-            for (position, _) in (&positions, &towns).join() {
-                town_positions.push(position.clone());
+                    // Retreat for a while when wounded below half health, but only once.
+                    let wounded = healths
+                        .get(entity)
+                        .is_some_and(|h| h.amount() < h.maximum() && h.amount() <= h.maximum() / 2);
+                    if !wounded {
+                        memory.retreated = false;
+                    } else if !memory.retreated {
+                        memory.retreated = true;
+                        memory.retreating = RETREAT_TURNS;
+                    }
+                }
+                AI::PrioritizePlayer => {
+                    // Wait for the pack to gather (as far as there are allies to gather with).
+                    if !memory.gathered {
+                        let nearby = map
+                            .entities_within(position, REGROUP_RADIUS)
+                            .into_iter()
+                            .filter(|(e, _)| ally(*e))
+                            .count();
+                        let others = factions
+                            .get(entity)
+                            .and_then(|f| allies.get(f))
+                            .copied()
+                            .unwrap_or(0);
+                        memory.gathered = nearby >= others.min(PACK_SIZE - 1);
+                    }
+                }
+                AI::Thief => {
+                    // Keep the same farm until it is destroyed or someone else raids it.
+                    if memory
+                        .target
+                        .is_some_and(|t| positions.get(t).is_none() || raided.contains(t))
+                    {
+                        memory.target = None;
+                    }
+                    if memory.target.is_none() {
+                        let farm = |e| {
+                            !raided.contains(e)
+                                && renderables.get(e).is_some_and(|r| r.glyph() == Glyph::Farm)
+                        };
+                        memory.target = map.nearest_entity(position, farm).map(|(e, _)| e);
+                    }
+                }
+                AI::Archer | AI::Support | AI::Follow => {}
+                AI::Guard => {
+                    // The post is wherever it was first asked to decide.
+                    memory.post.get_or_insert_with(|| position.clone());
+                }
+                AI::Hunt => {
+                    // Keep the same target until it is destroyed.
+                    if memory.target.is_some_and(|t| positions.get(t).is_none()) {
+                        memory.target = None;
+                    }
+                    if memory.target.is_none() {
+                        memory.target = map.nearest_entity(position, hostile).map(|(e, _)| e);
+                    }
+                }
+                AI::Villager => {
+                    // Keep the same farm and home until they are destroyed.
+                    let is = |e, glyph| renderables.get(e).is_some_and(|r| r.glyph() == glyph);
+                    if memory.target.is_some_and(|t| positions.get(t).is_none()) {
+                        memory.target = None;
+                    }
+                    if memory.target.is_none() {
+                        memory.target = map
+                            .nearest_entity(position, |e| is(e, Glyph::Farm))
+                            .map(|(e, _)| e);
+                    }
+                    if memory.home.is_some_and(|h| positions.get(h).is_none()) {
+                        memory.home = None;
+                    }
+                    if memory.home.is_none() {
+                        memory.home = map
+                            .nearest_entity(position, |e| is(e, Glyph::House))
+                            .map(|(e, _)| e);
+                    }
+                }
+                AI::Boss => {
+                    // Only count down once per turn, however often it decides.
+                    if !reconsidering {
+                        memory.cooldown = memory.cooldown.saturating_sub(1);
+                    }
+                }
             }
-            town_positions
-        };
 
-        // Find the player entity on the map.
-        let player_position = player.unwrap().1;
+            let ally = map.nearest_entity(position, ally).map(|(_, p)| p);
+            let situation = Situation {
+                player: player_position.clone(),
+                player_adjacent: hostile(player) && movement.is_adjacent(player_position, position),
+                player_distance: position.distance(player_position),
+                target: memory.target.and_then(|t| positions.get(t)).cloned(),
+                ally: ally.clone(),
+                ally_adjacent: ally.is_some_and(|ally| movement.is_adjacent(&ally, position)),
+                carrying: stolen.contains(entity),
+                shot: ranged.get(entity).and_then(|ranged| {
+                    // Prefer shooting the player, then the closest town entity.
+                    let range = ranged.range();
+                    map.entities_within(position, range as f64)
+                        .into_iter()
+                        .filter(|(e, _)| hostile(*e))
+                        .filter(|(_, p)| {
+                            combat::in_line_of_fire(&map, &renderables, position, p, range)
+                        })
+                        .min_by_key(|(e, _)| !players.contains(*e))
+                        .map(|(e, _)| e)
+                }),
+                enemy: map
+                    .neighbors(position, movement)
+                    .into_iter()
+                    .find(|p| map.get_blocker(p.x, p.y, layer).is_some_and(hostile)),
+                post: memory.post.clone().filter(|post| post != position),
+                target_adjacent: memory
+                    .target
+                    .and_then(|t| positions.get(t))
+                    .is_some_and(|t| movement.is_adjacent(t, position)),
+                home: memory.home.and_then(|h| positions.get(h)).map(|home| {
+                    let adjacent = movement.is_adjacent(home, position);
+                    (home.clone(), adjacent)
+                }),
+                danger: map
+                    .entities_within(position, DANGER_RADIUS)
+                    .into_iter()
+                    .any(|(e, _)| hostile(e)),
+            };
 
-        // Iterate through AI.
-        for (entity, ai, position) in (&entities, &ai, &positions).join() {
-            // If this a monster, and the player is adjacent, attack.
-            if monsters.get(entity).is_some() && player_position.distance(position) == 1.0 {
-                moving
-                    .insert(entity, best_direction(position, player_position))
-                    .unwrap();
+            // Keep the planned move, unless attacking the player is better.
+            let action = choose(consider(ai, &situation, memory));
+            if reconsidering && action != Action::Approach(player_position.clone()) {
                 continue;
             }
 
-            // Move based on the AIs type.
-            moving
-                .insert(
-                    entity,
-                    match ai {
-                        AI::Wander => {
-                            // Pick a random direction.
-                            rng.random_slice_entry(&[
-                                Moving::Up,
-                                Moving::Down,
-                                Moving::Left,
-                                Moving::Right,
-                            ])
-                            .unwrap()
-                            .clone()
-                        }
-                        AI::PrioritizeTown => {
-                            // Find the closest town and move towards it.
-                            let mut closest_distance = i32::MAX.into();
-                            let mut closest_position = Position::new(0, 0);
-
-                            for town_position in town_positions.iter() {
-                                let distance = position.distance(town_position);
-                                if distance < closest_distance {
-                                    closest_distance = distance;
-                                    closest_position = town_position.clone();
-                                }
-                            }
-
-                            best_direction(position, &closest_position)
-                        }
-                        AI::PrioritizePlayer => {
-                            // Move towards the player.
-                            best_direction(position, player_position)
-                        }
-                    },
-                )
-                .unwrap();
+            let direction = match action {
+                Action::Shoot(target) => {
+                    intents.insert(entity, Intent::shoot(target)).unwrap();
+                    continue;
+                }
+                Action::Smash => {
+                    // Aim where the player is now; it lands on the last turn of the wind-up.
+                    let smash = memory.smash.get_or_insert_with(|| Smash {
+                        center: player_position.clone(),
+                        turns: SMASH_WINDUP,
+                    });
+                    intents
+                        .insert(entity, Intent::wind_up(smash.clone()))
+                        .unwrap();
+                    smash.turns -= 1;
+                    if smash.turns == 0 {
+                        memory.smash = None;
+                    }
+                    continue;
+                }
+                Action::Summon => {
+                    memory.cooldown = SUMMON_COOLDOWN;
+                    intents.insert(entity, Intent::summon()).unwrap();
+                    continue;
+                }
+                Action::Tend(farm) => {
+                    intents.insert(entity, Intent::tend(farm)).unwrap();
+                    continue;
+                }
+                Action::Shelter => {
+                    intents.insert(entity, Intent::shelter()).unwrap();
+                    continue;
+                }
+                Action::Approach(target) => {
+                    // Walk around impassable terrain, preferring the straightest line.
+                    let preferred = best_direction(position, &target, movement);
+                    let step = if target == *player_position && layer == Layer::Ground {
+                        player_field.step(position, &preferred)
+                    } else {
+                        fields
+                            .entry(((target.x, target.y), layer))
+                            .or_insert_with(|| DistanceField::for_layer(&map, &[target], layer))
+                            .step(position, &preferred)
+                    };
+                    Some(step.unwrap_or(preferred))
+                }
+                Action::Flee => {
+                    memory.retreating = memory.retreating.saturating_sub(1);
+
+                    // Take the open step that gets furthest from the player, if any gets further.
+                    let here = player_field.get(position.x, position.y);
+                    movement
+                        .directions()
+                        .iter()
+                        .filter(|d| map.can_step_in(position, d, layer))
+                        .map(|d| (d, position.after(d)))
+                        .filter(|(_, next)| map.get_blocker(next.x, next.y, layer).is_none())
+                        .map(|(d, next)| (player_field.get(next.x, next.y), d))
+                        .filter(|(cost, _)| *cost > here)
+                        .reduce(|best, option| if option.0 > best.0 { option } else { best })
+                        .map(|(_, d)| d.clone())
+                }
+                Action::Wander => {
+                    // Pick a random direction.
+                    rng.random_slice_entry(movement.directions()).cloned()
+                }
+                Action::Escape => Some(toward_edge(&map, &mut edge_field, position)),
+                Action::Wait => None,
+            };
+
+            intents.insert(entity, Intent::new(direction)).unwrap();
         }
     }
 }
 
+/// Returns a direction towards the nearest edge of the map, or off it when already there.
+///
+/// The walking distance to the edge is computed the first time it is needed, then reused.
+fn toward_edge(map: &Map, edge_field: &mut Option<DistanceField>, from: &Position) -> Moving {
+    let (right, bottom) = (map.width() as i32 - 1, map.height() as i32 - 1);
+    let (direction, distance) = [
+        (Moving::Up, from.y),
+        (Moving::Down, bottom - from.y),
+        (Moving::Left, from.x),
+        (Moving::Right, right - from.x),
+    ]
+    .into_iter()
+    .reduce(|best, option| if option.1 < best.1 { option } else { best })
+    .unwrap();
+
+    if distance == 0 {
+        return direction;
+    }
+
+    // Walk around impassable terrain, preferring the straightest line.
+    edge_field
+        .get_or_insert_with(|| DistanceField::new(map, &map.ring_from_edge(0)))
+        .step(from, &direction)
+        .unwrap_or(direction)
+}
+
 /// Returns a direction to move towards a target.
-fn best_direction(from: &Position, to: &Position) -> Moving {
+///
+/// With eight-directional movement, targets that are not in a straight line are approached
+/// diagonally.
+fn best_direction(from: &Position, to: &Position, movement: Neighborhood) -> Moving {
     let x_diff = to.x - from.x;
     let y_diff = to.y - from.y;
 
-    if x_diff.abs() > y_diff.abs() {
+    if movement == Neighborhood::Eight && x_diff != 0 && y_diff != 0 {
+        match (x_diff > 0, y_diff > 0) {
+            (false, false) => Moving::UpLeft,
+            (true, false) => Moving::UpRight,
+            (false, true) => Moving::DownLeft,
+            (true, true) => Moving::DownRight,
+        }
+    } else if x_diff.abs() > y_diff.abs() {
         if x_diff > 0 {
             Moving::Right
         } else {
@@ -118,3 +720,475 @@ fn best_direction(from: &Position, to: &Position) -> Moving {
         Moving::Up
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use specs::prelude::*;
+
+    use crate::game::{
+        components::{Faction, Health, Memory},
+        logger::LogMessage,
+        scenario::{self, Scenario},
+        Direction, Glyph,
+    };
+
+    #[test]
+    fn goblin_prioritizes_player() {
+        let outcome = Scenario::new(
+            "
+            @5 . . . .
+            .  . . . .
+            .  . . . .
+            h2 . g . .
+            ",
+        )
+        .moves(&[Direction::Right])
+        .run();
+
+        outcome.assert_board(
+            "
+            .  @5 . . .
+            .  .  . . .
+            .  .  g . .
+            h2 .  . . .
+            ",
+        );
+    }
+
+    #[test]
+    fn orc_prioritizes_town() {
+        let outcome = Scenario::new(
+            "
+            @5 . . . .
+            .  . . . .
+            .  . . . .
+            h2 . o2 . .
+            ",
+        )
+        .moves(&[Direction::Right])
+        .run();
+
+        outcome.assert_board(
+            "
+            .  @5 . . .
+            .  .  . . .
+            .  .  . . .
+            h2 o2 . . .
+            ",
+        );
+    }
+
+    #[test]
+    fn goblin_walks_around_water() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . .
+            .  . . . .
+            @5 . . g .
+            ---
+            , , , , ,
+            , , , , ,
+            , , ~ , ,
+            ",
+        )
+        .moves(&[Direction::Up])
+        .run();
+
+        outcome.assert_board(
+            "
+            h2 . . . .
+            @5 . . g .
+            .  . . . .
+            ---
+            , , , , ,
+            , , , , ,
+            , , ~ , ,
+            ",
+        );
+    }
+
+    #[test]
+    fn monsters_attack_adjacent_player_first() {
+        let outcome = Scenario::new(
+            "
+            .  .  @5
+            h2 o2 .
+            ",
+        )
+        .moves(&[Direction::Down])
+        .run();
+
+        outcome.assert_board(
+            "
+            .  .  .
+            h2 o2 @4/5
+            ",
+        );
+    }
+
+    #[test]
+    fn monsters_fight_back_when_attacked() {
+        let outcome = Scenario::new(
+            "
+            h2 o2 @5
+            ",
+        )
+        .moves(&[Direction::Left])
+        .run();
+
+        outcome.assert_board(
+            "
+            h2 o1/2 @4/5
+            ",
+        );
+    }
+
+    #[test]
+    fn telegraphed_attacks_can_be_dodged() {
+        let board = "
+            .  .  .
+            h2 o2 @5
+            ";
+        let mut world = scenario::import(board);
+        world.tick();
+
+        // The orc shows that it is about to attack the player...
+        let orc = world.to_render().into_iter().find(|d| d.hostile).unwrap();
+        assert_eq!(orc.intent, Some(Direction::Right));
+
+        // ...so the player steps away, and the orc only moves into where the player was.
+        let outcome = Scenario::new(board).moves(&[Direction::Up]).run();
+        outcome.assert_board(
+            "
+            .  . @5
+            h2 . o2
+            ",
+        );
+    }
+
+    #[test]
+    fn monsters_attack_diagonally_when_enabled() {
+        let outcome = Scenario::new(
+            "
+            h2 .  .
+            .  .  g2
+            .  @5 .
+            ",
+        )
+        .diagonal()
+        .moves(&[Direction::UpRight])
+        .run();
+
+        outcome.assert_board(
+            "
+            h2 .    .
+            .  .    g1/2
+            .  @4/5 .
+            ",
+        );
+    }
+
+    #[test]
+    fn rats_wander_without_leaving_the_board() {
+        let outcome = Scenario::new(
+            "
+            r  . . . . .
+            .  . . . . .
+            .  . . . . .
+            .  . . . . .
+            h2 . . . . @5
+            ",
+        )
+        .seed(7)
+        .moves(&[
+            Direction::Up,
+            Direction::Down,
+            Direction::Up,
+            Direction::Down,
+            Direction::Up,
+            Direction::Down,
+        ])
+        .run();
+
+        assert_eq!(outcome.board.matches('r').count(), 1);
+        assert_eq!(outcome.board.lines().count(), 5);
+    }
+
+    #[test]
+    fn orcs_remember_their_target() {
+        // Walking around the river takes the orc closer to the other house, but it keeps going.
+        let outcome = Scenario::new(
+            "
+            h2 . o2 . . .
+            .  . .  . . .
+            .  . .  . . h2
+            .  . .  . . .
+            .  . .  . . @5
+            ---
+            , ~ , , , ,
+            , ~ , , , ,
+            , ~ , , , ,
+            , , , , , ,
+            , , , , , ,
+            ",
+        )
+        .moves(&[
+            Direction::Left,
+            Direction::Right,
+            Direction::Left,
+            Direction::Right,
+        ])
+        .run();
+
+        outcome.assert_board(
+            "
+            h2 . . . . .
+            .  . . . . .
+            .  . . . . h2
+            .  o2 . . . .
+            .  . . . . @5
+            ---
+            , ~ , , , ,
+            , ~ , , , ,
+            , ~ , , , ,
+            , , , , , ,
+            , , , , , ,
+            ",
+        );
+    }
+
+    #[test]
+    fn wounded_orcs_retreat() {
+        let outcome = Scenario::new(
+            "
+            .  . .  .
+            h2 . o2 @5
+            .  . .  .
+            ",
+        )
+        .moves(&[Direction::Left, Direction::Down])
+        .run();
+
+        // The orc still hits back as planned, but then runs instead of going for the house.
+        outcome.assert_board(
+            "
+            .  . o1/2 .
+            h2 . .    .
+            .  . .    @4/5
+            ",
+        );
+    }
+
+    #[test]
+    fn orcs_with_lots_of_health_retreat_below_half() {
+        let board = "
+            h2 . .        . @5
+            .  . o130/200 . .
+            .  . .        . .
+            .  . o90/200  . .
+            ";
+        let mut world = scenario::import(board);
+        world.tick();
+
+        let memories = world.ecs.read_storage::<Memory>();
+        let healths = world.ecs.read_storage::<Health>();
+        let retreated: Vec<_> = (&memories, &healths)
+            .join()
+            .map(|(memory, health)| (health.amount(), memory.retreated))
+            .collect();
+        assert_eq!(retreated, vec![(130, false), (90, true)]);
+    }
+
+    #[test]
+    fn goblins_regroup_before_attacking() {
+        let outcome = Scenario::new(
+            "
+            @5 . . . . .
+            .  . . . . .
+            .  . . . . .
+            h2 . g . . g
+            ",
+        )
+        .moves(&[Direction::Right])
+        .run();
+
+        outcome.assert_board(
+            "
+            .  @5 . . . .
+            .  .  . . . .
+            .  .  . . . .
+            h2 .  . g g .
+            ",
+        );
+    }
+
+    #[test]
+    fn goblins_do_not_wait_for_bats() {
+        let outcome = Scenario::new(
+            "
+            @5 . . . . . . .
+            .  . . . . . . .
+            h2 . . . g . . b
+            ",
+        )
+        .moves(&[Direction::Right])
+        .run();
+
+        // Bats cannot join a pack on the ground, so the goblin goes after the player on its own.
+        outcome.assert_board(
+            "
+            .  @5 . . . . . .
+            .  .  . . . . . .
+            h2 .  . g . . b .
+            ",
+        );
+    }
+
+    #[test]
+    fn rats_chase_the_player_once_seen() {
+        for seed in 0..8 {
+            let outcome = Scenario::new(
+                "
+                h2 . . . .
+                @5 . . . r
+                .  . . . .
+                ",
+            )
+            .seed(seed)
+            .moves(&[Direction::Down])
+            .run();
+
+            outcome.assert_board(
+                "
+                h2 . . . .
+                .  . . r .
+                @5 . . . .
+                ",
+            );
+        }
+    }
+
+    #[test]
+    fn rats_do_not_see_through_trees() {
+        let board = "
+            h2 . t . .
+            @5 . t . r
+            .  . t . .
+            ";
+        let mut world = scenario::import(board);
+        world.tick();
+
+        let memories = world.ecs.read_storage::<Memory>();
+        let factions = world.ecs.read_storage::<Faction>();
+        let (memory, _) = (&memories, &factions)
+            .join()
+            .find(|(_, faction)| **faction == Faction::Monsters)
+            .unwrap();
+        assert!(!memory.alerted);
+    }
+
+    #[test]
+    fn militia_hunts_monsters() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . . .
+            @5 . m2 . . o2
+            ",
+        )
+        .moves(&[Direction::Right, Direction::Left])
+        .run();
+
+        outcome.assert_board(
+            "
+            h2 . . .    .    .
+            @5 . . m1/2 o1/2 .
+            ",
+        );
+        assert!(outcome.logs.contains(&LogMessage::Attacked {
+            attacker: Glyph::Militia,
+            target: Glyph::Orc,
+            position: (3, 1),
+            defeated: false,
+        }));
+    }
+
+    #[test]
+    fn guards_hold_their_post() {
+        let outcome = Scenario::new(
+            "
+            h2 . .  . . . .
+            @5 . G3 . . . g
+            ",
+        )
+        .moves(&[
+            Direction::Right,
+            Direction::Left,
+            Direction::Right,
+            Direction::Left,
+        ])
+        .run();
+
+        // It waits at its post for the goblin to come to it, rather than chasing it.
+        assert!(outcome.logs.contains(&LogMessage::Attacked {
+            attacker: Glyph::Guard,
+            target: Glyph::Goblin,
+            position: (2, 1),
+            defeated: true,
+        }));
+    }
+
+    #[test]
+    fn rangers_follow_the_player() {
+        let outcome = Scenario::new(
+            "
+            h2 R1 . . . . . .
+            @5 .  . . . . . .
+            .  .  . . . . . o2
+            ",
+        )
+        .moves(&[
+            Direction::Down,
+            Direction::Right,
+            Direction::Right,
+            Direction::Left,
+        ])
+        .run();
+
+        // It keeps up with the player, and shoots whatever comes close.
+        outcome.assert_board(
+            "
+            h2 . .  .    . . . .
+            .  R .  .    . . . .
+            .  @5 . o1/2 . . . .
+            ",
+        );
+        assert!(outcome.logs.contains(&LogMessage::Shot {
+            attacker: Glyph::Ranger,
+            target: Glyph::Orc,
+            position: (1, 1),
+            defeated: false,
+        }));
+    }
+
+    #[test]
+    fn rangers_shoot_bats() {
+        let outcome = Scenario::new(
+            "
+            h2 R1 . . . .
+            @5 .  . . . b
+            .  .  . . . .
+            ",
+        )
+        .moves(&[Direction::Down, Direction::Up, Direction::Down])
+        .run();
+
+        assert!(outcome.logs.iter().any(|log| matches!(
+            log,
+            LogMessage::Shot {
+                attacker: Glyph::Ranger,
+                target: Glyph::Bat,
+                ..
+            }
+        )));
+    }
+}
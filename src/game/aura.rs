@@ -0,0 +1,204 @@
+//! Auras.
+//!
+//! Entities with an [`Aura`] buff (or debuff) what is within a radius of them, e.g. a shaman
+//! heals the monsters around it and a house shores up the walls next to it. Every turn,
+//! [`AuraSystem`] combines the auras each entity is in into its [`Buffs`], which the rest of the
+//! game reads (and the player can inspect).
+//!
+//! When several auras reach the same entity, they stack by effect:
+//!
+//! - [`AuraEffect::Defense`] adds up, to at most [`MAX_DEFENSE`].
+//! - [`AuraEffect::Regeneration`] does not add up: only the strongest counts.
+//!
+//! An aura never affects the entity giving it.
+
+use specs::prelude::*;
+
+use super::{
+    components::{Affects, Aura, AuraEffect, Buffs, Faction, Health, Position, Renderable},
+    faction::Relations,
+    Glyph, RunState,
+};
+
+/// The most hits every turn that stacked defense can ignore.
+pub const MAX_DEFENSE: u8 = 2;
+
+/// Returns the aura a house shores up the walls next to it with, whether it was placed by the level
+/// generator or spawned between rounds.
+pub fn house() -> Aura {
+    Aura::new(AuraEffect::Defense(1), 1.5, Affects::Glyph(Glyph::Wall))
+}
+
+/// A system that recalculates the [`Buffs`] of everything within an aura.
+///
+/// Must run after [`MapIndexingSystem`](super::map::MapIndexingSystem), once everything has
+/// moved (and been removed), so auras reach whatever is around them now.
+pub struct AuraSystem;
+
+impl<'a> System<'a> for AuraSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Relations>,
+        ReadStorage<'a, Aura>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, Renderable>,
+        WriteStorage<'a, Buffs>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Unpack the system data.
+        let (entities, relations, auras, positions, factions, renderables, mut buffs) = data;
+
+        buffs.clear();
+
+        for (source, aura, center) in (&entities, &auras, &positions).join() {
+            let affected: Vec<Entity> = (&entities, &positions)
+                .join()
+                .filter(|(entity, position)| {
+                    *entity != source && center.distance(position) <= aura.radius()
+                })
+                .filter(|(entity, _)| match aura.affects() {
+                    Affects::Allies => relations.is_allied(&factions, source, *entity),
+                    Affects::Glyph(glyph) => renderables
+                        .get(*entity)
+                        .is_some_and(|r| r.glyph() == *glyph),
+                })
+                .map(|(entity, _)| entity)
+                .collect();
+
+            for entity in affected {
+                let buff = buffs.entry(entity).unwrap().or_insert_with(Buffs::default);
+                match aura.effect() {
+                    AuraEffect::Defense(amount) => {
+                        buff.defense = buff.defense.saturating_add(*amount).min(MAX_DEFENSE);
+                    }
+                    AuraEffect::Regeneration(amount) => {
+                        buff.regeneration = buff.regeneration.max(*amount);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A system that heals everything with [`AuraEffect::Regeneration`] on the monster's turn.
+pub struct RegenerationSystem;
+
+impl<'a> System<'a> for RegenerationSystem {
+    type SystemData = (
+        ReadExpect<'a, RunState>,
+        ReadStorage<'a, Buffs>,
+        WriteStorage<'a, Health>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Unpack the system data.
+        let (state, buffs, mut health) = data;
+
+        // If this is not the monster's turn, do nothing.
+        if *state != RunState::MonsterTurn {
+            return;
+        }
+
+        for (buff, health) in (&buffs, &mut health).join() {
+            health.heal(buff.regeneration);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use specs::prelude::*;
+
+    use crate::game::{
+        components::{Buffs, Position},
+        logger::LogMessage,
+        scenario::{self, Scenario},
+        Direction, Glyph,
+    };
+
+    /// Returns the buffs of whatever stands at a position after the first tick.
+    fn buffs_at(board: &str, x: i32, y: i32) -> Buffs {
+        let mut world = scenario::import(board);
+        world.tick();
+
+        let positions = world.ecs.read_storage::<Position>();
+        let buffs = world.ecs.read_storage::<Buffs>();
+        (&positions, buffs.maybe())
+            .join()
+            .find(|(p, _)| (p.x, p.y) == (x, y))
+            .and_then(|(_, b)| b.cloned())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn auras_stack_by_effect() {
+        // Defense adds up, but only so far.
+        assert_eq!(buffs_at("h2 #3 h2 . @5", 1, 0).defense, 2);
+        assert_eq!(buffs_at("h2 #3 h2\n. h2 @5", 1, 0).defense, 2);
+        assert_eq!(buffs_at("h2 . #3 . @5", 2, 0).defense, 0);
+
+        // Regeneration does not add up, and never heals whoever gives it.
+        assert_eq!(buffs_at("S2 o2 S2 . @5", 1, 0).regeneration, 1);
+        assert_eq!(buffs_at("S2 . . . @5", 0, 0), Buffs::default());
+    }
+
+    #[test]
+    fn houses_shore_up_walls() {
+        // Two orcs attacking a wall next to a house only get one hit in...
+        let outcome = Scenario::new(
+            "
+            h2 #2 o2 . @5
+            .  o2 .  . .
+            .  .  .  . .
+            ",
+        )
+        .moves(&[Direction::Down])
+        .run();
+        assert!(outcome.board.contains("#1/2"));
+        assert_eq!(
+            outcome.logs,
+            vec![
+                LogMessage::Attacked {
+                    attacker: Glyph::Orc,
+                    target: Glyph::Wall,
+                    position: (2, 0),
+                    defeated: false,
+                },
+                LogMessage::Blocked {
+                    attacker: Glyph::Orc,
+                    target: Glyph::Wall,
+                    position: (1, 1),
+                },
+            ]
+        );
+
+        // ...but away from the house, they break through right away.
+        let outcome = Scenario::new(
+            "
+            .  #2 o2 . @5
+            .  o2 .  . .
+            h2 .  .  . .
+            ",
+        )
+        .moves(&[Direction::Down])
+        .run();
+        assert!(!outcome.board.contains('#'));
+    }
+
+    #[test]
+    fn shamans_heal_monsters_around_them() {
+        let outcome = Scenario::new(
+            "
+            h2 . .  .    . . .
+            .  . .  .    . . .
+            .  . S2 o1/4 . . @5
+            ",
+        )
+        .moves(&[Direction::Up])
+        .run();
+
+        assert!(outcome.board.contains("o2/4"));
+    }
+}
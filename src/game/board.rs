@@ -0,0 +1,622 @@
+//! Board export/import.
+//!
+//! A board is a text grid with one line per row and one whitespace-separated token per tile:
+//!
+//! ```text
+//! . . t . g
+//! . # h2 f .
+//! . . @5 . .
+//! ```
+//!
+//! - `.` is an empty tile.
+//! - Any other token starts with the glyph's symbol (the same ones drawn by the UI).
+//! - The symbol may be followed by a health annotation: `h2` is 2/2 health, `h1/2` is 1/2 health.
+//!   Without an annotation, the entity has 1/1 health. Entities without health (crops) ignore it.
+//! - Items lying on the ground (entities that don't block the tile) are each prefixed with `+`,
+//!   after whatever occupies the tile: `@5+f` is the player standing on an item, `+f` is the item
+//!   on its own.
+//! - Fliers are written like occupants, or as items when above something: `#+b` is a flier above a
+//!   wall.
+//!
+//! The entities may be followed by a `---` line and the terrain underneath them, with one symbol
+//! per tile (see [`Terrain::symbol`]). Without it, every tile is grass:
+//!
+//! ```text
+//! . . t . g
+//! . # h2 f .
+//! . . @5 . .
+//! ---
+//! , , , ~ ~
+//! , : : % ,
+//! , , : , ,
+//! ```
+//!
+//! Boards of generated levels may start with a `level` line naming the strategy (see
+//! [`level_strategy`](super::level_strategy)) that generated them. Without it, the default is used:
+//!
+//! ```text
+//! level fortress
+//! # # # # #
+//! # h2 f . #
+//! ```
+//!
+//! Blank lines are ignored.
+//!
+//! Villagers sheltering inside a house (see [`village`](super::village)) have no tile of their own,
+//! so they are not part of the board: exporting it while they hide, and importing it again, loses
+//! them.
+
+use std::fmt;
+
+use specs::prelude::*;
+
+use super::{
+    components::{Blocks, Flying, Health},
+    demo, level_strategy,
+    map::Terrain,
+    DrawEntity, Glyph, MAX_MAP_SIZE, MIN_MAP_SIZE,
+};
+
+/// The symbol used for an empty tile.
+const EMPTY: char = '.';
+
+/// The symbol before each item lying on the ground.
+const ITEM: char = '+';
+
+/// The line separating entities from terrain.
+const TERRAIN_SEPARATOR: &str = "---";
+
+/// The keyword starting the line that names the level strategy.
+const LEVEL: &str = "level";
+
+/// A single occupied tile of a board.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoardTile {
+    pub x: i32,
+    pub y: i32,
+    pub glyph: Glyph,
+
+    /// Current and maximum health.
+    pub hp: (u8, u8),
+
+    /// Whether the entity occupies the tile, rather than lying on the ground.
+    pub blocks: bool,
+}
+
+/// A parsed board.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Board {
+    pub width: usize,
+    pub height: usize,
+    pub tiles: Vec<BoardTile>,
+
+    /// Terrain, as rows.
+    pub terrain: Vec<Vec<Terrain>>,
+
+    /// Name of the level strategy the board was generated with, if recorded.
+    pub level: Option<String>,
+}
+
+/// Why a board could not be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BoardErrorKind {
+    /// The board does not have any rows.
+    Empty,
+
+    /// The symbol does not belong to any glyph.
+    UnknownGlyph(char),
+
+    /// The symbol does not belong to any terrain.
+    UnknownTerrain(String),
+
+    /// The name does not belong to any level strategy.
+    UnknownLevel(String),
+
+    /// The health annotation is not `N` or `N/M` with `0 < N <= M`.
+    InvalidHealth(String),
+
+    /// The row does not have the same number of tiles as the first row.
+    RaggedRow { expected: usize, actual: usize },
+
+    /// The terrain does not have the same number of rows as the entities.
+    TerrainHeight { expected: usize, actual: usize },
+
+    /// The board does not have a player.
+    MissingPlayer,
+
+    /// The board has more than one player.
+    DuplicatePlayer,
+
+    /// The board is narrower or shorter than [`MIN_MAP_SIZE`], or wider or taller than
+    /// [`MAX_MAP_SIZE`].
+    Size { width: usize, height: usize },
+}
+
+/// An error parsing a board.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoardError {
+    /// Where the board goes wrong, as a 1-based line and column.
+    ///
+    /// Errors about the board as a whole ([`BoardErrorKind::Empty`],
+    /// [`BoardErrorKind::MissingPlayer`], and [`BoardErrorKind::Size`]) have no position.
+    pub position: Option<(usize, usize)>,
+    pub kind: BoardErrorKind,
+}
+
+impl BoardError {
+    /// Creates an error at a 1-based line and column.
+    fn at(line: usize, column: usize, kind: BoardErrorKind) -> Self {
+        Self {
+            position: Some((line, column)),
+            kind,
+        }
+    }
+
+    /// Creates an error about the board as a whole.
+    fn whole(kind: BoardErrorKind) -> Self {
+        Self {
+            position: None,
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((line, column)) = self.position {
+            write!(f, "{}:{}: ", line, column)?;
+        }
+        match &self.kind {
+            BoardErrorKind::Empty => write!(f, "board is empty"),
+            BoardErrorKind::UnknownGlyph(c) => write!(f, "unknown glyph {:?}", c),
+            BoardErrorKind::UnknownTerrain(s) => write!(f, "unknown terrain {:?}", s),
+            BoardErrorKind::UnknownLevel(s) => write!(f, "unknown level {:?}", s),
+            BoardErrorKind::InvalidHealth(s) => write!(f, "invalid health {:?}", s),
+            BoardErrorKind::RaggedRow { expected, actual } => {
+                write!(f, "expected {} tiles, found {}", expected, actual)
+            }
+            BoardErrorKind::TerrainHeight { expected, actual } => {
+                write!(f, "expected {} terrain rows, found {}", expected, actual)
+            }
+            BoardErrorKind::MissingPlayer => write!(f, "board has no player"),
+            BoardErrorKind::DuplicatePlayer => write!(f, "board has more than one player"),
+            BoardErrorKind::Size { width, height } => write!(
+                f,
+                "board is {}x{}, expected {} to {} tiles a side",
+                width, height, MIN_MAP_SIZE, MAX_MAP_SIZE
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
+
+/// Writes the given entities and terrain rows as a board, with the level strategy if given.
+///
+/// Entities outside of the terrain are ignored, and the terrain is omitted if it is all grass.
+pub fn export(entities: &[DrawEntity], terrain: &[Vec<Terrain>], level: Option<&str>) -> String {
+    let height = terrain.len();
+    let width = terrain.first().map_or(0, |row| row.len());
+    let mut grid = vec![vec![String::new(); width]; height];
+
+    // Whatever occupies a tile goes first, then fliers, then the items on the ground.
+    let mut entities: Vec<&DrawEntity> = entities.iter().collect();
+    entities.sort_by_key(|e| (!e.blocks, !e.flying));
+
+    for e in entities {
+        if e.x < 0 || e.y < 0 || e.x as usize >= width || e.y as usize >= height {
+            continue;
+        }
+        // Anything after the first occupant (or flier) can only be stacked as an item.
+        let tile = &mut grid[e.y as usize][e.x as usize];
+        if !(e.blocks || e.flying) || !tile.is_empty() {
+            tile.push(ITEM);
+        }
+        tile.push_str(&tile_token(e.glyph, e.hp));
+    }
+    for tile in grid.iter_mut().flatten() {
+        if tile.is_empty() {
+            tile.push(EMPTY);
+        }
+    }
+
+    let mut out = String::new();
+    if let Some(level) = level {
+        out.push_str(&format!("{} {}\n", LEVEL, level));
+    }
+    out.push_str(&write_grid(grid));
+    if terrain.iter().flatten().any(|t| *t != Terrain::Grass) {
+        out.push_str(TERRAIN_SEPARATOR);
+        out.push('\n');
+        out.push_str(&write_grid(
+            terrain
+                .iter()
+                .map(|row| row.iter().map(|t| t.symbol().to_string()).collect())
+                .collect(),
+        ));
+    }
+    out
+}
+
+/// Writes rows of tokens, one line per row.
+fn write_grid(grid: Vec<Vec<String>>) -> String {
+    // Pad every token to the same width so that columns line up.
+    let column = grid.iter().flatten().map(|t| t.len()).max().unwrap_or(1);
+
+    let mut out = String::new();
+    for row in grid {
+        let line: Vec<String> = row.iter().map(|t| format!("{:column$}", t)).collect();
+        out.push_str(line.join(" ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Returns the token for a single tile.
+fn tile_token(glyph: Glyph, hp: (u8, u8)) -> String {
+    match hp {
+        (1, 1) => glyph.symbol().to_string(),
+        (amount, maximum) if amount == maximum => format!("{}{}", glyph.symbol(), amount),
+        (amount, maximum) => format!("{}{}/{}", glyph.symbol(), amount, maximum),
+    }
+}
+
+/// Parses a board.
+pub fn parse(text: &str) -> Result<Board, BoardError> {
+    let mut width = None;
+    let mut height = 0;
+    let mut tiles = Vec::new();
+    let mut has_player = false;
+    let mut terrain: Option<Vec<Vec<Terrain>>> = None;
+    let mut level = None;
+    let mut last_line = 1;
+
+    for (line, row) in text.lines().enumerate() {
+        let line = line + 1;
+        if row.trim().is_empty() {
+            continue;
+        }
+        last_line = line;
+
+        // The level can only be named before the first row.
+        if let Some(name) = row.trim().strip_prefix(LEVEL) {
+            if width.is_none() && level.is_none() && name.starts_with(char::is_whitespace) {
+                // The name starts after any indentation, the keyword, and the space after it.
+                let offset = (row.len() - row.trim_start().len())
+                    + LEVEL.len()
+                    + (name.len() - name.trim_start().len());
+                let name = name.trim();
+                if level_strategy::by_name(name).is_none() {
+                    return Err(BoardError::at(
+                        line,
+                        offset + 1,
+                        BoardErrorKind::UnknownLevel(name.to_string()),
+                    ));
+                }
+                level = Some(name.to_string());
+                continue;
+            }
+        }
+
+        // Everything after the separator is terrain.
+        if terrain.is_none() && row.trim() == TERRAIN_SEPARATOR {
+            terrain = Some(Vec::new());
+            continue;
+        }
+        if let Some(terrain) = &mut terrain {
+            terrain.push(parse_terrain_row(line, row, width.unwrap_or(0))?);
+            continue;
+        }
+
+        let mut x = 0;
+        for (column, token) in tokens(row) {
+            if token == EMPTY.to_string() {
+                x += 1;
+                continue;
+            }
+
+            // The occupant (if any), then every item.
+            let mut offset = 0;
+            for (i, part) in token.split(ITEM).enumerate() {
+                let column = column + offset;
+                offset += part.len() + 1;
+                let error = |kind| BoardError::at(line, column, kind);
+
+                let blocks = i == 0;
+                if blocks && part.is_empty() {
+                    continue;
+                }
+
+                let mut chars = part.chars();
+                let symbol = chars.next().unwrap_or(ITEM);
+                let annotation = chars.as_str();
+
+                let glyph = Glyph::from_symbol(symbol)
+                    .ok_or_else(|| error(BoardErrorKind::UnknownGlyph(symbol)))?;
+                let hp = parse_health(annotation)
+                    .ok_or_else(|| error(BoardErrorKind::InvalidHealth(annotation.to_string())))?;
+
+                if glyph == Glyph::Player {
+                    if has_player {
+                        return Err(error(BoardErrorKind::DuplicatePlayer));
+                    }
+                    has_player = true;
+                }
+
+                tiles.push(BoardTile {
+                    x,
+                    y: height as i32,
+                    glyph,
+                    hp,
+                    blocks,
+                });
+            }
+            x += 1;
+        }
+
+        let x = x as usize;
+        match width {
+            None => width = Some(x),
+            Some(expected) if expected != x => {
+                return Err(BoardError::at(
+                    line,
+                    row.len() + 1,
+                    BoardErrorKind::RaggedRow {
+                        expected,
+                        actual: x,
+                    },
+                ));
+            }
+            Some(_) => {}
+        }
+        height += 1;
+    }
+
+    let width = width.ok_or(BoardError::whole(BoardErrorKind::Empty))?;
+
+    if !has_player {
+        return Err(BoardError::whole(BoardErrorKind::MissingPlayer));
+    }
+
+    let terrain = match terrain {
+        None => vec![vec![Terrain::Grass; width]; height],
+        Some(terrain) if terrain.len() != height => {
+            return Err(BoardError::at(
+                last_line,
+                1,
+                BoardErrorKind::TerrainHeight {
+                    expected: height,
+                    actual: terrain.len(),
+                },
+            ));
+        }
+        Some(terrain) => terrain,
+    };
+
+    Ok(Board {
+        width,
+        height,
+        tiles,
+        terrain,
+        level,
+    })
+}
+
+/// Checks that a parsed board is as large as a generated map can be, but no larger.
+pub fn check_size(board: &Board) -> Result<(), BoardError> {
+    let range = MIN_MAP_SIZE..=MAX_MAP_SIZE;
+    if range.contains(&board.width) && range.contains(&board.height) {
+        return Ok(());
+    }
+    Err(BoardError::whole(BoardErrorKind::Size {
+        width: board.width,
+        height: board.height,
+    }))
+}
+
+/// Parses a single row of terrain that is expected to have `width` tiles.
+fn parse_terrain_row(line: usize, row: &str, width: usize) -> Result<Vec<Terrain>, BoardError> {
+    let mut terrain = Vec::new();
+
+    for (column, token) in tokens(row) {
+        let mut chars = token.chars();
+        let symbol = chars.next().unwrap();
+        match Terrain::from_symbol(symbol) {
+            Some(t) if chars.as_str().is_empty() => terrain.push(t),
+            _ => {
+                return Err(BoardError::at(
+                    line,
+                    column,
+                    BoardErrorKind::UnknownTerrain(token.to_string()),
+                ))
+            }
+        }
+    }
+
+    if terrain.len() != width {
+        return Err(BoardError::at(
+            line,
+            row.len() + 1,
+            BoardErrorKind::RaggedRow {
+                expected: width,
+                actual: terrain.len(),
+            },
+        ));
+    }
+    Ok(terrain)
+}
+
+/// Splits a row into whitespace-separated tokens, with their 1-based columns.
+fn tokens(row: &str) -> impl Iterator<Item = (usize, &str)> {
+    row.split_whitespace()
+        .map(move |t| (t.as_ptr() as usize - row.as_ptr() as usize + 1, t))
+}
+
+/// Parses a health annotation (`""`, `"N"`, or `"N/M"`).
+fn parse_health(annotation: &str) -> Option<(u8, u8)> {
+    let (amount, maximum) = match annotation.split_once('/') {
+        _ if annotation.is_empty() => (1, 1),
+        None => {
+            let amount = annotation.parse().ok()?;
+            (amount, amount)
+        }
+        Some((amount, maximum)) => (amount.parse().ok()?, maximum.parse().ok()?),
+    };
+
+    if amount == 0 || amount > maximum {
+        return None;
+    }
+    Some((amount, maximum))
+}
+
+/// Inserts every tile of a board into the world.
+///
+/// Entities are configured as if just spawned, then take the recorded health (if they have any
+/// health to begin with, unlike crops). Anything stacked after the occupant of a tile lies on the
+/// ground, except fliers, which stay in the air.
+///
+/// Returns the player entity.
+pub fn insert(world: &mut World, board: &Board) -> Option<Entity> {
+    let mut player = None;
+
+    for tile in &board.tiles {
+        let (x, y) = (tile.x, tile.y);
+        let entity = world.create_entity();
+        let entity = demo::configure(tile.glyph, entity, x, y).build();
+        if let Some(health) = world.write_storage::<Health>().get_mut(entity) {
+            *health = Health::with_maximum(tile.hp.0, tile.hp.1);
+        }
+        if !tile.blocks && !world.read_storage::<Flying>().contains(entity) {
+            world.write_storage::<Blocks>().remove(entity);
+        }
+
+        if tile.glyph == Glyph::Player {
+            player = Some(entity);
+        }
+    }
+
+    player
+}
+
+#[cfg(test)]
+mod tests {
+    use specs::prelude::*;
+
+    use super::{parse, BoardError, BoardErrorKind};
+    use crate::game::{
+        components::{Blocks, Flying, Health, Renderable},
+        Glyph, WorldState,
+    };
+
+    /// Imports a board of any size.
+    fn import(text: &str) -> WorldState {
+        WorldState::from_board(parse(text).unwrap())
+    }
+
+    /// Returns the error parsing a board, panicking if it parses.
+    fn error(text: &str) -> BoardError {
+        parse(text).expect_err("board should not parse")
+    }
+
+    /// Returns whether the entity with the given glyph blocks, flies, and has health.
+    fn components(world: &WorldState, glyph: Glyph) -> (bool, bool, bool) {
+        let entities = world.ecs.entities();
+        let renderables = world.ecs.read_storage::<Renderable>();
+        let (entity, _) = (&entities, &renderables)
+            .join()
+            .find(|(_, r)| r.glyph() == glyph)
+            .unwrap();
+        (
+            world.ecs.read_storage::<Blocks>().contains(entity),
+            world.ecs.read_storage::<Flying>().contains(entity),
+            world.ecs.read_storage::<Health>().contains(entity),
+        )
+    }
+
+    #[test]
+    fn every_glyph_round_trips() {
+        for glyph in Glyph::ALL {
+            let token = format!("{}2/3", glyph.symbol());
+            let text = if glyph == Glyph::Player {
+                format!("{} .\n.    .\n", token)
+            } else {
+                format!("@5 {}\n.  .\n", token)
+            };
+
+            // Whatever the glyph is (e.g. an item or a flier), it keeps its position and health,
+            // unless it has no health to keep.
+            let exported = import(&text).export_board();
+            let board = parse(&exported).unwrap();
+            let tile = board.tiles.iter().find(|t| t.glyph == glyph).unwrap();
+            let x = if glyph == Glyph::Player { 0 } else { 1 };
+            let hp = if glyph == Glyph::Crops {
+                (1, 1)
+            } else {
+                (2, 3)
+            };
+            assert_eq!((tile.x, tile.y, tile.hp), (x, 0, hp), "{}", exported);
+
+            // Exporting what was imported gives the same board again.
+            let reimported = import(&exported);
+            assert_eq!(reimported.export_board(), exported);
+        }
+    }
+
+    #[test]
+    fn stacked_entities_round_trip() {
+        // A flier above a wall, with crops (`%`) on the ground.
+        let world = import("@5 #+b+%\n.  .\n");
+        assert_eq!(components(&world, Glyph::Wall), (true, false, true));
+        assert_eq!(components(&world, Glyph::Bat), (false, true, true));
+        assert_eq!(components(&world, Glyph::Crops), (false, false, false));
+
+        let exported = world.export_board();
+        let world = import(&exported);
+        assert_eq!(world.export_board(), exported);
+        assert_eq!(components(&world, Glyph::Wall), (true, false, true));
+        assert_eq!(components(&world, Glyph::Bat), (false, true, true));
+        assert_eq!(components(&world, Glyph::Crops), (false, false, false));
+
+        // Only one entity occupies a tile: anything else is stacked as lying on the ground.
+        let world = import("@5 h2+#\n.  .\n");
+        assert_eq!(components(&world, Glyph::Wall), (false, false, true));
+    }
+
+    #[test]
+    fn malformed_boards_are_rejected_where_they_go_wrong() {
+        assert_eq!(
+            error("@5 . x2"),
+            BoardError::at(1, 6, BoardErrorKind::UnknownGlyph('x'))
+        );
+        assert_eq!(
+            error("@5 . .\n.  .\n"),
+            BoardError::at(
+                2,
+                5,
+                BoardErrorKind::RaggedRow {
+                    expected: 3,
+                    actual: 2,
+                }
+            )
+        );
+        assert_eq!(
+            error("level l\n@5 .\n"),
+            BoardError::at(1, 7, BoardErrorKind::UnknownLevel("l".to_string()))
+        );
+        assert_eq!(
+            error("\n  level   nope\n@5 .\n"),
+            BoardError::at(2, 11, BoardErrorKind::UnknownLevel("nope".to_string()))
+        );
+    }
+
+    #[test]
+    fn malformed_boards_as_a_whole_have_no_position() {
+        assert_eq!(
+            error("h2 .\n.  g\n"),
+            BoardError::whole(BoardErrorKind::MissingPlayer)
+        );
+        assert_eq!(error("\n  \n"), BoardError::whole(BoardErrorKind::Empty));
+        assert_eq!(error("h2 .\n").to_string(), "board has no player");
+        assert_eq!(error("@5 x").to_string(), "1:4: unknown glyph 'x'");
+    }
+}
@@ -0,0 +1,198 @@
+//! Bosses.
+//!
+//! Every few rounds a boss (see [`AI::Boss`](super::components::AI::Boss)) joins the fight. On top
+//! of attacking like any other monster, it brings down telegraphed smashes that hit a whole area,
+//! and summons minions.
+
+use specs::prelude::*;
+
+use super::{
+    components::{Faction, Health, Position, Renderable, Smashing, Summoning},
+    demo,
+    faction::Relations,
+    logger::{LogMessage, Logs},
+    map::{Map, MapIndexingSystem, Neighborhood},
+};
+
+/// How much damage a smash deals to everything it hits.
+const SMASH_DAMAGE: u8 = 2;
+
+/// How many minions a boss summons at once (at most, as they need room).
+const MINIONS: usize = 2;
+
+/// A system that brings down smashes, damaging everything on and around their center.
+///
+/// Must run before [`DefeatSystem`](super::combat::DefeatSystem). Allies of the boss are never
+/// hit.
+pub struct SmashSystem;
+
+impl<'a> System<'a> for SmashSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, Relations>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, Renderable>,
+        WriteStorage<'a, Health>,
+        WriteStorage<'a, Smashing>,
+        WriteExpect<'a, Logs>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Unpack the system data.
+        let (entities, map, relations, factions, renderables, mut health, mut smashing, mut logs) =
+            data;
+
+        for (boss, smash) in (&entities, smashing.drain()).join() {
+            let center = smash.center();
+            let mut area = map.neighbors(center, Neighborhood::Eight);
+            area.push(center.clone());
+
+            let mut hits = 0;
+            for position in area {
+                let Some(target) = map.get_entity(position.x, position.y) else {
+                    continue;
+                };
+                if relations.is_allied(&factions, boss, target) {
+                    continue;
+                }
+                if let Some(health) = health.get_mut(target) {
+                    health.reduce(SMASH_DAMAGE);
+                    hits += 1;
+                }
+            }
+
+            logs.add(LogMessage::Smashed {
+                attacker: renderables.get(boss).unwrap().glyph(),
+                position: (center.x, center.y),
+                hits,
+            });
+        }
+    }
+}
+
+/// Spawns minions (goblins) around every boss that is summoning them.
+///
+/// Minions take the open tiles next to the boss, in the (fixed) order of
+/// [`Neighborhood::offsets`], so summoning is deterministic.
+pub fn summon_minions(ecs: &mut World) {
+    // Make sure the map knows where everything ended up.
+    MapIndexingSystem.run_now(ecs);
+
+    let summoners: Vec<Position> = {
+        let positions = ecs.read_storage::<Position>();
+        let mut summoning = ecs.write_storage::<Summoning>();
+        (summoning.drain(), &positions)
+            .join()
+            .map(|(_, position)| position.clone())
+            .collect()
+    };
+
+    for boss in summoners {
+        let open: Vec<Position> = {
+            let map = ecs.fetch::<Map>();
+            map.neighbors(&boss, Neighborhood::Eight)
+                .into_iter()
+                .filter(|p| map.get_entity(p.x, p.y).is_none() && map.is_passable(p.x, p.y))
+                .take(MINIONS)
+                .collect()
+        };
+
+        for position in &open {
+            demo::configure_goblin(ecs.create_entity(), position.x, position.y).build();
+        }
+        ecs.fetch_mut::<Logs>().add(LogMessage::Summoned {
+            position: (boss.x, boss.y),
+            count: open.len() as u8,
+        });
+
+        // Index the minions, so the next boss does not summon on top of them.
+        MapIndexingSystem.run_now(ecs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bracket_lib::random::RandomNumberGenerator;
+
+    use crate::game::{
+        logger::LogMessage,
+        map::Map,
+        scenario::{self, Scenario},
+        Direction, Glyph,
+    };
+
+    const BOARD: &str = "
+        .  . . .   . h2
+        .  . . .   . .
+        @5 . O12 . . .
+        .  . . .   . .
+        ";
+
+    #[test]
+    fn smashes_can_be_dodged() {
+        // The smash lands two turns after the player came close, where the player was then.
+        let outcome = Scenario::new(BOARD)
+            .moves(&[Direction::Up, Direction::Up])
+            .run();
+        assert_eq!(outcome.stats.health, (5, 5));
+        assert_eq!(
+            outcome.logs,
+            vec![LogMessage::Smashed {
+                attacker: Glyph::Ogre,
+                position: (0, 2),
+                hits: 0,
+            }]
+        );
+
+        let outcome = Scenario::new(BOARD)
+            .moves(&[Direction::Down, Direction::Up])
+            .run();
+        assert_eq!(outcome.stats.health, (3, 5));
+        assert_eq!(outcome.stats.boss, Some((12, 12)));
+    }
+
+    #[test]
+    fn bosses_summon_minions() {
+        let outcome = Scenario::new(
+            "
+            @5 . . . . . .
+            .  . . . . . .
+            .  . . . . . O12
+            h2 . . . . . .
+            ",
+        )
+        .moves(&[Direction::Right])
+        .run();
+
+        assert_eq!(
+            outcome.logs,
+            vec![LogMessage::Summoned {
+                position: (6, 2),
+                count: 2,
+            }]
+        );
+        assert_eq!(outcome.board.matches('g').count(), 2);
+    }
+
+    #[test]
+    fn boss_waves_are_deterministic() {
+        let spawn = |rounds: u8| {
+            let mut world = scenario::import(BOARD.replace("O12", ".").as_str());
+            world.ecs.insert(RandomNumberGenerator::seeded(7));
+            for _ in 1..rounds {
+                world.ecs.fetch_mut::<Map>().next_round();
+            }
+            world.spawn_monsters();
+            (world.export_board(), world.get_logs())
+        };
+
+        let (board, logs) = spawn(5);
+        assert!(board.contains('O'));
+        assert_eq!(logs, vec![LogMessage::BossArrived { boss: Glyph::Ogre }]);
+        assert_eq!(spawn(5), (board, logs));
+
+        let (board, logs) = spawn(4);
+        assert!(!board.contains('O') && logs.is_empty());
+    }
+}
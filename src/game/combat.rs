@@ -2,24 +2,39 @@
 //!
 //! These systems are responsible for handling combat between entities.
 
+use std::collections::HashMap;
+
 use specs::prelude::*;
 
 use super::{
-    components::{Attacking, Defeated, Health, HealthState, Monster, Moving, Position, Renderable},
+    components::{
+        Attacking, Buffs, Defeated, Faction, Flying, Health, HealthState, Moving, Player, Position,
+        Ranged, Renderable, Shooting, AI,
+    },
+    faction::Relations,
+    fov,
     logger::{LogMessage, Logs},
-    map::Map,
+    map::{Layer, Map, Terrain},
+    Glyph, RunState,
 };
 
 /// A system that converts movement into melee attacks.
 ///
-/// If an entity is moving *into* another entity, it will perform a melee attack.
+/// If an entity is moving *into* another entity, it will perform a melee attack. What is in the
+/// way depends on the layer it moves in (see [`Map::get_blocker`]), e.g. fliers pass over walls.
+/// Allies (see [`Relations`]) are not attacked, except by the player, who has to insist (see
+/// [`WorldState::player_move`](super::WorldState::player_move)).
 pub struct ConvertMovementToMeleeAttackSystem;
 
 impl<'a> System<'a> for ConvertMovementToMeleeAttackSystem {
     type SystemData = (
         Entities<'a>,
         ReadExpect<'a, Map>,
-        ReadStorage<'a, Monster>,
+        ReadExpect<'a, Relations>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, AI>,
+        ReadStorage<'a, Flying>,
         ReadStorage<'a, Health>,
         ReadStorage<'a, Position>,
         WriteStorage<'a, Moving>,
@@ -28,15 +43,28 @@ impl<'a> System<'a> for ConvertMovementToMeleeAttackSystem {
 
     fn run(&mut self, data: Self::SystemData) {
         // Unpack the system data.
-        let (entities, map, monsters, health, positions, mut moving, mut attacking) = data;
+        let (
+            entities,
+            map,
+            relations,
+            factions,
+            players,
+            ai,
+            flying,
+            health,
+            positions,
+            mut moving,
+            mut attacking,
+        ) = data;
         let mut stop_movement = Vec::<Entity>::new();
 
         // Iterate over all entities that have a position and are moving.
         for (entity, position, direction) in (&entities, &positions, &mut moving).join() {
             let prospective = position.after(direction);
+            let layer = Layer::of(flying.contains(entity));
 
             // If there would be an overlap with another entity, do not move.
-            if let Some(target) = map.get_entity(prospective.x, prospective.y) {
+            if let Some(target) = map.get_blocker(prospective.x, prospective.y, layer) {
                 // Remove the moving component from the entity.
                 stop_movement.push(entity);
 
@@ -45,8 +73,13 @@ impl<'a> System<'a> for ConvertMovementToMeleeAttackSystem {
                     continue;
                 }
 
-                // If the source is a monster AND the target is a monster, do not attack.
-                if monsters.get(entity).is_some() && monsters.get(target).is_some() {
+                // If the source never fights (e.g. a villager), do not attack.
+                if ai.get(entity).is_some_and(|ai| matches!(ai, AI::Villager)) {
+                    continue;
+                }
+
+                // If the source and the target are allies, do not attack (unless the player insists).
+                if !players.contains(entity) && relations.is_allied(&factions, entity, target) {
                     continue;
                 }
 
@@ -62,7 +95,133 @@ impl<'a> System<'a> for ConvertMovementToMeleeAttackSystem {
     }
 }
 
+/// Whether the tile stops arrows: walls, trees and rocks do.
+pub fn blocks_shot(map: &Map, renderables: &ReadStorage<Renderable>, x: i32, y: i32) -> bool {
+    map.get_terrain(x, y) == Terrain::Rock
+        || map
+            .get_entity(x, y)
+            .and_then(|e| renderables.get(e))
+            .is_some_and(|r| matches!(r.glyph(), Glyph::Wall | Glyph::Tree))
+}
+
+/// Whether `to` is within range of `from`, with nothing in between that stops arrows.
+pub fn in_line_of_fire(
+    map: &Map,
+    renderables: &ReadStorage<Renderable>,
+    from: &Position,
+    to: &Position,
+    range: i32,
+) -> bool {
+    fov::in_sight(map, from, to, range, |x, y| {
+        blocks_shot(map, renderables, x, y)
+    })
+}
+
+/// A system that converts shooting into ranged attacks.
+///
+/// Unlike melee attacks, the target does not need to be adjacent: it must be within range, with
+/// a clear line of fire (see [`in_line_of_fire`]). Otherwise the shot misses, e.g. because the
+/// target stepped behind a tree.
+pub struct RangedAttackSystem;
+
+impl<'a> System<'a> for RangedAttackSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Map>,
+        ReadStorage<'a, Ranged>,
+        ReadStorage<'a, Health>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Renderable>,
+        WriteStorage<'a, Shooting>,
+        WriteStorage<'a, Attacking>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Unpack the system data.
+        let (entities, map, ranged, health, positions, renderables, mut shooting, mut attacking) =
+            data;
+
+        // Iterate over all entities that are shooting.
+        for (entity, shot, ranged, position) in
+            (&entities, shooting.drain(), &ranged, &positions).join()
+        {
+            // If the target is gone, or cannot be hurt, do not attack.
+            let target = shot.target();
+            if health.get(target).is_none() {
+                continue;
+            }
+            let Some(target_position) = positions.get(target) else {
+                continue;
+            };
+
+            // If the target is out of range or out of sight, the shot misses.
+            if !in_line_of_fire(
+                &map,
+                &renderables,
+                position,
+                target_position,
+                ranged.range(),
+            ) {
+                continue;
+            }
+
+            // Insert an attack.
+            attacking.insert(entity, Attacking::ranged(target)).unwrap();
+        }
+    }
+}
+
+/// A system that lets watchtowers (entities that are [`Ranged`] without an [`AI`]) shoot at
+/// fliers.
+///
+/// Each one shoots at the nearest hostile flier in range once per monster turn. Watchtowers
+/// see over walls and trees, so no clear line of fire is needed.
+pub struct InterceptSystem;
+
+impl<'a> System<'a> for InterceptSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, RunState>,
+        ReadExpect<'a, Relations>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, AI>,
+        ReadStorage<'a, Ranged>,
+        ReadStorage<'a, Flying>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, Attacking>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Unpack the system data.
+        let (entities, state, relations, factions, ai, ranged, flying, positions, mut attacking) =
+            data;
+
+        // If this is not the monster's turn, do nothing.
+        if *state != RunState::MonsterTurn {
+            return;
+        }
+
+        for (tower, _, ranged, position) in (&entities, !&ai, &ranged, &positions).join() {
+            let target = (&entities, &flying, &positions)
+                .join()
+                .filter(|(flier, _, _)| relations.is_hostile(&factions, tower, *flier))
+                .map(|(flier, _, p)| (flier, position.distance(p)))
+                .filter(|(_, distance)| *distance <= ranged.range() as f64)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(flier, _)| flier);
+
+            if let Some(target) = target {
+                attacking.insert(tower, Attacking::ranged(target)).unwrap();
+            }
+        }
+    }
+}
+
 /// A system that applies attacks, reducing the health of the target.
+///
+/// Targets with [`Buffs::defense`] take less damage from being attacked several times in a turn:
+/// the first hit always lands, then that many are ignored. A lone attacker still wears them down,
+/// but a mob does not get through any faster.
 pub struct ApplyAttackSystem;
 
 impl<'a> System<'a> for ApplyAttackSystem {
@@ -71,15 +230,31 @@ impl<'a> System<'a> for ApplyAttackSystem {
         WriteStorage<'a, Attacking>,
         ReadStorage<'a, Renderable>,
         ReadStorage<'a, Position>,
+        ReadStorage<'a, Buffs>,
         WriteExpect<'a, Logs>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         // Unpack the system data.
-        let (mut health, mut attacking, renderables, positions, mut logs) = data;
+        let (mut health, mut attacking, renderables, positions, buffs, mut logs) = data;
+        let mut hits = HashMap::<Entity, (bool, u8)>::new();
 
         // Iterate over all entities that are attacking.
         for (attacking, position, render) in (&mut attacking, &positions, &renderables).join() {
+            // Ignore the hits after the first on a defended target, as far as its defense goes.
+            let defense = buffs.get(attacking.target()).map_or(0, |b| b.defense);
+            let (landed, blocked) = hits.entry(attacking.target()).or_insert((false, 0));
+            if *landed && *blocked < defense {
+                *blocked += 1;
+                logs.add(LogMessage::Blocked {
+                    attacker: render.glyph(),
+                    target: renderables.get(attacking.target()).unwrap().glyph(),
+                    position: (position.x, position.y),
+                });
+                continue;
+            }
+            *landed = true;
+
             // Reduce the health of the target.
             let health = health.get_mut(attacking.target()).unwrap();
             let defeated = match health.reduce(1) {
@@ -88,14 +263,23 @@ impl<'a> System<'a> for ApplyAttackSystem {
             };
 
             // Log the attack.
-            logs.add(LogMessage::Attacked {
-                attacker: render.glyph(),
-                target: {
-                    let target = renderables.get(attacking.target()).unwrap();
-                    target.glyph()
-                },
-                position: (position.x, position.y),
-                defeated,
+            let attacker = render.glyph();
+            let target = renderables.get(attacking.target()).unwrap().glyph();
+            let position = (position.x, position.y);
+            logs.add(if attacking.is_ranged() {
+                LogMessage::Shot {
+                    attacker,
+                    target,
+                    position,
+                    defeated,
+                }
+            } else {
+                LogMessage::Attacked {
+                    attacker,
+                    target,
+                    position,
+                    defeated,
+                }
             })
         }
 
@@ -117,20 +301,20 @@ impl<'a> System<'a> for DefeatSystem {
     fn run(&mut self, data: Self::SystemData) {
         // Unpack the system data.
         let (entities, mut health, mut defeated) = data;
-        let mut defeated_entities = Vec::<Entity>::new();
+        let mut defeated_entities = Vec::<(Entity, u8)>::new();
 
         // Iterate over all entities that have health.
         for (entity, health) in (&entities, &health).join() {
             // If the entity has 0 health, remove it.
             if health.amount() == 0 {
-                defeated_entities.push(entity);
+                defeated_entities.push((entity, health.maximum()));
             }
         }
 
         // Remove the health component and add the defeated component to all defeated entities.
-        for entity in defeated_entities {
+        for (entity, maximum) in defeated_entities {
             health.remove(entity);
-            defeated.insert(entity, Defeated).unwrap();
+            defeated.insert(entity, Defeated::new(maximum)).unwrap();
         }
     }
 }
@@ -157,3 +341,220 @@ impl<'a> System<'a> for RemoveDefeatedSystem {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{logger::LogMessage, scenario::Scenario, Direction, Glyph};
+
+    #[test]
+    fn player_defeats_goblin() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . .
+            @5 g . . .
+            .  . . . g
+            ",
+        )
+        .moves(&[Direction::Right])
+        .run();
+
+        outcome.assert_board(
+            "
+            h2 . . . .
+            @5 . . . .
+            .  . . g .
+            ",
+        );
+        assert!(matches!(
+            outcome.logs[..],
+            [LogMessage::Attacked {
+                attacker: Glyph::Player,
+                target: Glyph::Goblin,
+                defeated: true,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn goblin_attacks_adjacent_player() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . .
+            .  . . . .
+            @5 . g . .
+            ",
+        )
+        .moves(&[Direction::Up, Direction::Right])
+        .run();
+
+        outcome.assert_board(
+            "
+            h2 .    . . .
+            .  @4/5 . . .
+            .  g    . . .
+            ",
+        );
+        assert_eq!(outcome.stats.health, (4, 5));
+        assert!(matches!(
+            outcome.logs[..],
+            [LogMessage::Attacked {
+                attacker: Glyph::Goblin,
+                target: Glyph::Player,
+                defeated: false,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn orc_attacks_town() {
+        let outcome = Scenario::new(
+            "
+            @5 . .  . .
+            .  . .  . .
+            .  . .  . .
+            h2 # o2 . .
+            ",
+        )
+        .moves(&[Direction::Right])
+        .run();
+
+        outcome.assert_board(
+            "
+            .  @5 .  . .
+            .  .  .  . .
+            .  .  .  . .
+            h2 .  o2 . .
+            ",
+        );
+        assert!(matches!(
+            outcome.logs[..],
+            [LogMessage::Attacked {
+                attacker: Glyph::Orc,
+                target: Glyph::Wall,
+                defeated: true,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn monsters_do_not_attack_each_other() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . .
+            .  . . . .
+            @5 . . o2 g
+            ",
+        )
+        .moves(&[Direction::Up])
+        .run();
+
+        // The orc heads for the house, and the goblin bumps into the orc instead of attacking it.
+        outcome.assert_board(
+            "
+            h2 . . .  .
+            @5 . . .  .
+            .  . o2 . g
+            ",
+        );
+        assert!(outcome.logs.is_empty());
+    }
+
+    #[test]
+    fn archer_shoots_from_a_distance() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . .
+            .  . . . .
+            @5 . . a .
+            ",
+        )
+        .moves(&[Direction::Right])
+        .run();
+
+        // The archer stays where it is.
+        outcome.assert_board(
+            "
+            h2 .    . . .
+            .  .    . . .
+            .  @4/5 . a .
+            ",
+        );
+        assert_eq!(
+            outcome.logs,
+            vec![LogMessage::Shot {
+                attacker: Glyph::Archer,
+                target: Glyph::Player,
+                position: (3, 2),
+                defeated: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn trees_block_arrows() {
+        let outcome = Scenario::new(
+            "
+            h2 .  . . .
+            .  @5 . . a
+            .  .  t . .
+            ",
+        )
+        .moves(&[Direction::Down])
+        .run();
+
+        // The archer took aim, but the player stepped behind the tree before it could shoot.
+        assert_eq!(outcome.stats.health, (5, 5));
+        assert!(outcome.logs.is_empty());
+    }
+
+    #[test]
+    fn archers_back_away_from_the_player() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . .
+            .  . . . .
+            @5 a . . .
+            .  . . . .
+            ",
+        )
+        .moves(&[Direction::Down])
+        .run();
+
+        outcome.assert_board(
+            "
+            h2 . . . .
+            .  a . . .
+            .  . . . .
+            @5 . . . .
+            ",
+        );
+        assert!(outcome.logs.is_empty());
+    }
+
+    #[test]
+    fn watchtowers_shoot_down_fliers() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . . . b
+            @5 . . w . . .
+            ",
+        )
+        .moves(&[Direction::Right, Direction::Right])
+        .run();
+
+        // The bat is out of range on the first turn, but not the second.
+        assert_eq!(
+            outcome.logs,
+            vec![LogMessage::Shot {
+                attacker: Glyph::Tower,
+                target: Glyph::Bat,
+                position: (3, 1),
+                defeated: true,
+            }]
+        );
+        assert!(!outcome.board.contains('b'));
+    }
+}
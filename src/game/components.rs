@@ -4,7 +4,7 @@ use specs::prelude::*;
 use specs_derive::Component;
 
 /// A component that represents an entity that has a logical (x, y) position in the game world.
-#[derive(Component, Clone, Debug)]
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
@@ -22,12 +22,8 @@ impl Position {
     }
 
     pub fn after(&self, direction: &Moving) -> Position {
-        match direction {
-            Moving::Up => Position::new(self.x, self.y - 1),
-            Moving::Down => Position::new(self.x, self.y + 1),
-            Moving::Left => Position::new(self.x - 1, self.y),
-            Moving::Right => Position::new(self.x + 1, self.y),
-        }
+        let (dx, dy) = direction.delta();
+        Position::new(self.x + dx, self.y + dy)
     }
 }
 
@@ -42,6 +38,97 @@ pub enum Glyph {
     Player,
     Tree,
     Wall,
+    Thief,
+
+    /// Crops dropped by a thief, lying on the ground.
+    Crops,
+    Archer,
+
+    /// The ogre chieftain, a boss.
+    Ogre,
+
+    /// A flying monster.
+    Bat,
+
+    /// A watchtower, which shoots down fliers.
+    Tower,
+
+    /// A monster that splits in two when defeated.
+    Slime,
+
+    /// A goblin shaman, which heals the monsters around it.
+    Shaman,
+
+    /// Allied militia, recruited from a house, which hunts down monsters.
+    Militia,
+
+    /// Allied militia, recruited from a house, which holds its post.
+    Guard,
+
+    /// An allied archer, recruited from a house, which follows the player around.
+    Ranger,
+
+    /// A villager, who works the farms and hides from monsters.
+    Villager,
+}
+
+impl Glyph {
+    /// Every glyph, in declaration order.
+    pub const ALL: [Glyph; 20] = [
+        Glyph::Farm,
+        Glyph::Orc,
+        Glyph::Rat,
+        Glyph::Goblin,
+        Glyph::House,
+        Glyph::Player,
+        Glyph::Tree,
+        Glyph::Wall,
+        Glyph::Thief,
+        Glyph::Crops,
+        Glyph::Archer,
+        Glyph::Ogre,
+        Glyph::Bat,
+        Glyph::Tower,
+        Glyph::Slime,
+        Glyph::Shaman,
+        Glyph::Militia,
+        Glyph::Guard,
+        Glyph::Ranger,
+        Glyph::Villager,
+    ];
+
+    /// Returns the ASCII symbol used to draw the glyph.
+    #[must_use]
+    pub fn symbol(&self) -> char {
+        match self {
+            Glyph::Farm => 'f',
+            Glyph::Orc => 'o',
+            Glyph::Rat => 'r',
+            Glyph::Goblin => 'g',
+            Glyph::House => 'h',
+            Glyph::Player => '@',
+            Glyph::Tree => 't',
+            Glyph::Wall => '#',
+            Glyph::Thief => 'T',
+            Glyph::Crops => '%',
+            Glyph::Archer => 'a',
+            Glyph::Ogre => 'O',
+            Glyph::Bat => 'b',
+            Glyph::Tower => 'w',
+            Glyph::Slime => 's',
+            Glyph::Shaman => 'S',
+            Glyph::Militia => 'm',
+            Glyph::Guard => 'G',
+            Glyph::Ranger => 'R',
+            Glyph::Villager => 'v',
+        }
+    }
+
+    /// Returns the glyph drawn with the given ASCII symbol, if any.
+    #[must_use]
+    pub fn from_symbol(symbol: char) -> Option<Glyph> {
+        Glyph::ALL.into_iter().find(|g| g.symbol() == symbol)
+    }
 }
 
 /// A component that represents an entity that can be drawn to the screen.
@@ -66,13 +153,359 @@ impl Renderable {
 #[derive(Component, Debug)]
 pub struct Player;
 
-/// A component that represents a hostile (monster) entity.
+/// A component that represents which side of the fight an entity is on.
+///
+/// Who attacks whom depends on how factions treat each other (see
+/// [`Relations`](super::faction::Relations)). Entities without a faction (e.g. trees) are
+/// neutral to everything.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Faction {
+    /// The player, and everything they build.
+    Town,
+
+    /// The monsters attacking the town.
+    Monsters,
+
+    /// Monsters charmed out of the fight: they side with the town, but their former allies
+    /// leave them alone.
+    Charmed,
+}
+
+/// A component that represents an entity that flies (see [`Layer::Air`](super::map::Layer::Air)).
+///
+/// Fliers do not occupy a tile on the ground, so they should not also have [`Blocks`].
+#[derive(Component, Debug)]
+pub struct Flying;
+
+/// A component that represents an entity that occupies its tile, stopping others from moving in.
+///
+/// Entities without it (e.g. items on the ground) share their tile with anything else.
+#[derive(Component, Debug)]
+pub struct Blocks;
+
+/// A component that represents an entity that can see its surroundings (see [`fov`](super::fov)).
+#[derive(Component, Debug)]
+pub struct Vision {
+    radius: i32,
+}
+
+impl Vision {
+    /// Create a new vision component, seeing up to `radius` tiles away.
+    #[must_use]
+    pub fn new(radius: i32) -> Self {
+        Self { radius }
+    }
+
+    /// Returns how many tiles away the entity can see.
+    #[must_use]
+    pub fn radius(&self) -> i32 {
+        self.radius
+    }
+}
+
+/// A component that represents a boss monster, whose health is shown in the sidebar.
+#[derive(Component, Debug)]
+pub struct Boss;
+
+/// A component that represents a boss calling in minions this turn.
+#[derive(Component, Debug)]
+pub struct Summoning;
+
+/// A component that represents a boss bringing down a smash this turn.
+#[derive(Component, Debug)]
+pub struct Smashing {
+    center: Position,
+}
+
+impl Smashing {
+    /// Create a new smashing component, hitting everything around `center`.
+    pub fn new(center: Position) -> Self {
+        Self { center }
+    }
+
+    /// Returns the center of the area being smashed.
+    pub fn center(&self) -> &Position {
+        &self.center
+    }
+}
+
+/// A smash a boss is winding up: everything on and around `center` is hit when it lands.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Smash {
+    /// The center of the area that will be hit.
+    pub center: Position,
+
+    /// Monster turns until it lands, counting the one it lands on.
+    pub turns: u8,
+}
+
+/// Something that happens when an entity is defeated (see [`OnDeath`]).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeathEffect {
+    /// Splits into two copies of itself with half the maximum health, on free adjacent tiles.
+    ///
+    /// Copies with less than 1 health are not spawned, so splitting stops eventually.
+    Split,
+
+    /// Drops an item (e.g. [`Glyph::Crops`]) where it stood.
+    Drop(Glyph),
+
+    /// Lets everyone sheltering inside (see [`Occupants`]) out onto the free tiles around it.
+    ///
+    /// Whoever does not fit is lost with it.
+    Evict,
+}
+
+/// A component that represents what happens when an entity is defeated, in order.
+#[derive(Component, Debug, Default)]
+pub struct OnDeath {
+    effects: Vec<DeathEffect>,
+}
+
+impl OnDeath {
+    /// Create a new on-death component with a single effect.
+    pub fn new(effect: DeathEffect) -> Self {
+        Self {
+            effects: vec![effect],
+        }
+    }
+
+    /// Add another effect, after the existing ones.
+    pub fn push(&mut self, effect: DeathEffect) {
+        self.effects.push(effect);
+    }
+
+    /// Returns the effects, in order.
+    pub fn effects(&self) -> &[DeathEffect] {
+        &self.effects
+    }
+}
+
+/// A buff (or debuff) an [`Aura`] gives to what is around it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuraEffect {
+    /// Ignores some of the hits taken every turn, though never the first.
+    Defense(u8),
+
+    /// Heals some health every monster turn, up to the maximum.
+    Regeneration(u8),
+}
+
+/// What an [`Aura`] affects.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Affects {
+    /// Everything allied with whoever gives the aura.
+    Allies,
+
+    /// Everything drawn with the given glyph, e.g. walls.
+    Glyph(Glyph),
+}
+
+/// A component that represents an entity giving an effect to what is within a radius of it
+/// (never to itself).
+#[derive(Component, Debug)]
+pub struct Aura {
+    effect: AuraEffect,
+    radius: f64,
+    affects: Affects,
+}
+
+impl Aura {
+    /// Create a new aura.
+    pub fn new(effect: AuraEffect, radius: f64, affects: Affects) -> Self {
+        Self {
+            effect,
+            radius,
+            affects,
+        }
+    }
+
+    /// Returns the effect the aura gives.
+    pub fn effect(&self) -> &AuraEffect {
+        &self.effect
+    }
+
+    /// Returns how far (in a straight line) the aura reaches.
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    /// Returns what the aura affects.
+    pub fn affects(&self) -> &Affects {
+        &self.affects
+    }
+}
+
+/// A component that represents the combined effects of every aura an entity is in.
+///
+/// Recalculated every turn by [`AuraSystem`](super::aura::AuraSystem); see
+/// [`aura`](super::aura) for how effects stack.
+#[derive(Component, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Buffs {
+    /// How many hits are ignored every turn, after the first.
+    pub defense: u8,
+
+    /// How much health is healed every monster turn.
+    pub regeneration: u8,
+}
+
+impl Buffs {
+    /// Returns the combined effects, e.g. to be inspected.
+    pub fn effects(&self) -> Vec<AuraEffect> {
+        let mut effects = Vec::new();
+        if self.defense > 0 {
+            effects.push(AuraEffect::Defense(self.defense));
+        }
+        if self.regeneration > 0 {
+            effects.push(AuraEffect::Regeneration(self.regeneration));
+        }
+        effects
+    }
+}
+
+/// A component that represents an entity tending a farm this turn.
+#[derive(Component, Debug)]
+pub struct Tending {
+    farm: Entity,
+}
+
+impl Tending {
+    /// Create a new tending component.
+    pub fn new(farm: Entity) -> Self {
+        Self { farm }
+    }
+
+    /// Returns the farm being tended.
+    pub fn farm(&self) -> Entity {
+        self.farm
+    }
+}
+
+/// How far the crops on a farm have grown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CropStage {
+    /// Just sown (or trampled, eaten or harvested): nothing to bring in yet.
+    #[default]
+    Sown,
+
+    /// Halfway there.
+    Sprouting,
+
+    /// Ready to be harvested.
+    Ripe,
+}
+
+/// A component that represents the crops growing on a farm.
+#[derive(Component, Debug, Default)]
+pub struct Growth {
+    stage: CropStage,
+    progress: u8,
+}
+
+impl Growth {
+    /// How many turns of growth it takes to reach the next stage.
+    pub const TURNS_PER_STAGE: u8 = 5;
+
+    /// Returns how far the crops have grown.
+    pub fn stage(&self) -> CropStage {
+        self.stage
+    }
+
+    /// Whether the crops are ready to be harvested.
+    pub fn is_ripe(&self) -> bool {
+        self.stage == CropStage::Ripe
+    }
+
+    /// Grow for a number of turns, moving on to the next stage(s) when enough have passed.
+    pub fn grow(&mut self, turns: u8) {
+        self.progress = self.progress.saturating_add(turns);
+        while self.progress >= Self::TURNS_PER_STAGE && !self.is_ripe() {
+            self.progress -= Self::TURNS_PER_STAGE;
+            self.advance();
+        }
+    }
+
+    /// Move on to the next stage right away.
+    pub fn advance(&mut self) {
+        self.stage = match self.stage {
+            CropStage::Sown => CropStage::Sprouting,
+            CropStage::Sprouting | CropStage::Ripe => CropStage::Ripe,
+        };
+    }
+
+    /// Bring in the crops if they are ripe, starting over. Returns whether there was a harvest.
+    pub fn harvest(&mut self) -> bool {
+        if !self.is_ripe() {
+            return false;
+        }
+        *self = Self::default();
+        true
+    }
+
+    /// Lose whatever has grown (e.g. trampled), starting over. Returns whether anything was lost.
+    pub fn ruin(&mut self) -> bool {
+        if self.stage == CropStage::Sown && self.progress == 0 {
+            return false;
+        }
+        *self = Self::default();
+        true
+    }
+}
+
+/// A component that represents an entity heading indoors this turn.
+#[derive(Component, Debug)]
+pub struct Sheltering;
+
+/// A component that represents the entities sheltering inside a building (e.g. a house).
+///
+/// While inside, they have no [`Position`], so they are neither drawn nor attacked.
+#[derive(Component, Debug)]
+pub struct Occupants {
+    capacity: usize,
+    inside: Vec<Entity>,
+}
+
+impl Occupants {
+    /// Create a new, empty occupants component with room for `capacity` entities.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inside: Vec::new(),
+        }
+    }
+
+    /// Whether another entity fits inside.
+    pub fn has_room(&self) -> bool {
+        self.inside.len() < self.capacity
+    }
+
+    /// Returns the entities inside, in the order they came in.
+    pub fn inside(&self) -> &[Entity] {
+        &self.inside
+    }
+
+    /// Let an entity in, if there is room. Returns whether it fit.
+    pub fn enter(&mut self, entity: Entity) -> bool {
+        if !self.has_room() {
+            return false;
+        }
+        self.inside.push(entity);
+        true
+    }
+
+    /// Let everyone out, returning them in the order they came in.
+    pub fn leave(&mut self) -> Vec<Entity> {
+        std::mem::take(&mut self.inside)
+    }
+}
+
+/// A component that represents an entity carrying crops stolen from a farm.
 #[derive(Component, Debug)]
-pub struct Monster;
+pub struct Stolen;
 
-/// A component that represents an entity that is a non-tree non-player entity.
+/// A component that represents a farm whose crops were stolen this round (so it pays nothing).
 #[derive(Component, Debug)]
-pub struct Town;
+pub struct Raided;
 
 /// A component that represents an entity that is moving in a specified direction.
 #[derive(Component, Clone, Debug, PartialEq, Eq)]
@@ -81,24 +514,263 @@ pub enum Moving {
     Down,
     Left,
     Right,
+
+    /// Diagonals, only allowed with eight-directional movement.
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl Moving {
+    /// Up, down, left, and right.
+    pub const CARDINAL: [Moving; 4] = [Moving::Up, Moving::Down, Moving::Left, Moving::Right];
+
+    /// Every direction, cardinal directions first.
+    pub const ALL: [Moving; 8] = [
+        Moving::Up,
+        Moving::Down,
+        Moving::Left,
+        Moving::Right,
+        Moving::UpLeft,
+        Moving::UpRight,
+        Moving::DownLeft,
+        Moving::DownRight,
+    ];
+
+    /// Returns the (x, y) offset of a single step in this direction.
+    #[must_use]
+    pub fn delta(&self) -> (i32, i32) {
+        match self {
+            Moving::Up => (0, -1),
+            Moving::Down => (0, 1),
+            Moving::Left => (-1, 0),
+            Moving::Right => (1, 0),
+            Moving::UpLeft => (-1, -1),
+            Moving::UpRight => (1, -1),
+            Moving::DownLeft => (-1, 1),
+            Moving::DownRight => (1, 1),
+        }
+    }
+
+    /// Whether this is one of the diagonals.
+    #[must_use]
+    pub fn is_diagonal(&self) -> bool {
+        let (dx, dy) = self.delta();
+        dx != 0 && dy != 0
+    }
+}
+
+/// A component that represents what an AI-controlled entity will do on its next turn.
+///
+/// Intents are decided a turn ahead, so the player can see them coming (and get out of the way).
+#[derive(Component, Debug)]
+pub struct Intent {
+    direction: Option<Moving>,
+    target: Option<Entity>,
+    smash: Option<Smash>,
+    summon: bool,
+    tend: Option<Entity>,
+    shelter: bool,
+}
+
+impl Intent {
+    /// Create a new intent to move (or attack) in a direction, or to wait if there is none.
+    pub fn new(direction: Option<Moving>) -> Self {
+        Self {
+            direction,
+            target: None,
+            smash: None,
+            summon: false,
+            tend: None,
+            shelter: false,
+        }
+    }
+
+    /// Create a new intent to shoot at an entity from where it stands.
+    pub fn shoot(target: Entity) -> Self {
+        Self {
+            target: Some(target),
+            ..Self::new(None)
+        }
+    }
+
+    /// Create a new intent to wind up (or, on its last turn, bring down) a smash.
+    pub fn wind_up(smash: Smash) -> Self {
+        Self {
+            smash: Some(smash),
+            ..Self::new(None)
+        }
+    }
+
+    /// Create a new intent to summon minions.
+    pub fn summon() -> Self {
+        Self {
+            summon: true,
+            ..Self::new(None)
+        }
+    }
+
+    /// Create a new intent to tend a farm from where it stands.
+    pub fn tend(farm: Entity) -> Self {
+        Self {
+            tend: Some(farm),
+            ..Self::new(None)
+        }
+    }
+
+    /// Create a new intent to shelter in its home.
+    pub fn shelter() -> Self {
+        Self {
+            shelter: true,
+            ..Self::new(None)
+        }
+    }
+
+    /// Whether it will only move (or attack, or wait), rather than do anything else.
+    pub fn is_move(&self) -> bool {
+        self.target.is_none()
+            && self.smash.is_none()
+            && !self.summon
+            && self.tend.is_none()
+            && !self.shelter
+    }
+
+    /// Returns the direction the entity will move (or attack) in, or `None` if it will not move.
+    pub fn direction(&self) -> Option<&Moving> {
+        self.direction.as_ref()
+    }
+
+    /// Returns the entity that will be shot at, if any.
+    pub fn target(&self) -> Option<Entity> {
+        self.target
+    }
+
+    /// Returns the smash being wound up, if any.
+    pub fn smash(&self) -> Option<&Smash> {
+        self.smash.as_ref()
+    }
+
+    /// Whether minions will be summoned.
+    pub fn summons(&self) -> bool {
+        self.summon
+    }
+
+    /// Returns the farm that will be tended, if any.
+    pub fn tends(&self) -> Option<Entity> {
+        self.tend
+    }
+
+    /// Whether it will shelter in its home.
+    pub fn shelters(&self) -> bool {
+        self.shelter
+    }
+}
+
+/// A component that represents an entity that can attack from a distance.
+#[derive(Component, Debug)]
+pub struct Ranged {
+    range: i32,
+}
+
+impl Ranged {
+    /// Create a new ranged component, able to hit targets up to `range` tiles away.
+    pub fn new(range: i32) -> Self {
+        Self { range }
+    }
+
+    /// Returns how far (in tiles, as the crow flies) it can shoot.
+    pub fn range(&self) -> i32 {
+        self.range
+    }
+}
+
+/// A component that represents an entity that is shooting at another entity this turn.
+///
+/// Becomes an [`Attacking`] if the target is still in range with a clear line of fire.
+#[derive(Component, Debug)]
+pub struct Shooting {
+    target: Entity,
+}
+
+impl Shooting {
+    /// Create a new shooting component.
+    pub fn new(target: Entity) -> Self {
+        Self { target }
+    }
+
+    /// Returns the entity that is being shot at.
+    pub fn target(&self) -> Entity {
+        self.target
+    }
+}
+
+/// A component that represents what an AI-controlled entity remembers between turns.
+///
+/// What is used depends on the [`AI`].
+#[derive(Component, Debug, Default)]
+pub struct Memory {
+    /// The entity it is going after, until it is destroyed.
+    pub target: Option<Entity>,
+
+    /// Whether it has seen the player (and so stopped patrolling).
+    pub alerted: bool,
+
+    /// Whether it has gathered with its pack (and so attacks).
+    pub gathered: bool,
+
+    /// Turns left to keep retreating for.
+    pub retreating: u8,
+
+    /// Whether it already retreated since it was last at full health.
+    pub retreated: bool,
+
+    /// The smash it is winding up, if any.
+    pub smash: Option<Smash>,
+
+    /// Turns left until it can summon minions again.
+    pub cooldown: u8,
+
+    /// Where it stands guard.
+    pub post: Option<Position>,
+
+    /// The house it lives in (and hides in).
+    pub home: Option<Entity>,
 }
 
 /// A component that represents an entity that is attacking another entity.
 #[derive(Component, Debug)]
 pub struct Attacking {
     target: Entity,
+    ranged: bool,
 }
 
 impl Attacking {
-    /// Create a new attacking component.
+    /// Create a new (melee) attacking component.
     pub fn new(target: Entity) -> Self {
-        Self { target }
+        Self {
+            target,
+            ranged: false,
+        }
+    }
+
+    /// Create a new attacking component for a ranged attack.
+    pub fn ranged(target: Entity) -> Self {
+        Self {
+            target,
+            ranged: true,
+        }
     }
 
     /// Returns the entity that is being attacked.
     pub fn target(&self) -> Entity {
         self.target
     }
+
+    /// Whether the attack is made from a distance.
+    pub fn is_ranged(&self) -> bool {
+        self.ranged
+    }
 }
 
 /// A component that represents an entity that has health.
@@ -124,6 +796,17 @@ impl Health {
         }
     }
 
+    /// Create a new health component that is not at its maximum.
+    ///
+    /// # Panics
+    ///
+    /// If the amount is greater than the maximum.
+    #[must_use]
+    pub fn with_maximum(amount: u8, maximum: u8) -> Self {
+        assert!(amount <= maximum);
+        Self { amount, maximum }
+    }
+
     /// Returns the amount of health.
     #[must_use]
     pub fn amount(&self) -> u8 {
@@ -151,6 +834,11 @@ impl Health {
         self.maximum = self.maximum.saturating_add(amount);
     }
 
+    /// Increase the amount of health, up to the maximum.
+    pub fn heal(&mut self, amount: u8) {
+        self.amount = self.amount.saturating_add(amount).min(self.maximum);
+    }
+
     /// Increase the amount of health to the maximum.
     pub fn reset(&mut self) {
         self.amount = self.maximum;
@@ -159,33 +847,119 @@ impl Health {
 
 /// A component that represents an entity that has been defeated.
 #[derive(Component, Debug)]
-pub struct Defeated;
+pub struct Defeated {
+    maximum: u8,
+}
+
+impl Defeated {
+    /// Create a new defeated component, remembering the maximum health the entity had.
+    pub fn new(maximum: u8) -> Self {
+        Self { maximum }
+    }
+
+    /// Returns the maximum health the entity had before it was defeated.
+    pub fn maximum(&self) -> u8 {
+        self.maximum
+    }
+}
 
 /// A component that represents an entity that is controlled by the AI.
+///
+/// Anything an AI remembers between turns is kept in its [`Memory`].
 #[derive(Component, Debug)]
 pub enum AI {
-    /// The AI will randomly move around the map.
+    /// The AI patrols (randomly moves around the map) until it sees the player, then chases it.
     ///
     /// - If it attempts to move into a friendly, it stops (enforced by combat system).
     /// - If it attempts to move out of bounds, it stops.
     /// - Any other movement is valid (it will attack)
     ///
-    /// **STATELESS**: This AI does not store any state.
+    /// **STATEFUL**: Remembers having seen the player.
     Wander,
 
-    /// The AI will move towards the nearest non-tree/non-monster entity.
+    /// The AI will move towards the nearest town entity, and keep going after it until it is
+    /// destroyed.
     ///
     /// - If the player is adjacent, it will attack the player instead.
     /// - It will attack trees if that's the only option.
+    /// - When wounded to half health or below, it retreats from the player for a few turns.
     ///
-    /// **STATELESS**: This AI does not store any state.
+    /// **STATEFUL**: Remembers its target, and whether it is (or was) retreating.
     PrioritizeTown,
 
-    /// The AI will move towards the player.
+    /// The AI will move towards the player, once it has gathered with its pack.
     ///
-    /// - If a town is adjacent, it will attack the town instead.
+    /// - Until then, it moves towards the nearest other monster.
+    /// - If the player is adjacent, it will attack the player regardless.
     /// - It will attack trees if that's the only option.
     ///
-    /// **STATELESS**: This AI does not store any state.
+    /// **STATEFUL**: Remembers having gathered.
     PrioritizePlayer,
+
+    /// The AI will move towards the nearest farm that has not been raided, and steal its crops
+    /// instead of attacking it.
+    ///
+    /// - Once it is carrying crops, it runs for the nearest edge of the map and escapes.
+    /// - If there is nothing left to steal, it escapes empty-handed.
+    /// - It never attacks anything: bumping into a farm steals from it, anything else stops it.
+    ///
+    /// **STATEFUL**: Remembers its target.
+    Thief,
+
+    /// The AI keeps its distance and shoots (see [`Ranged`]) at the player, or the nearest town
+    /// entity, when it has a clear line of fire.
+    ///
+    /// - If the player is adjacent, it backs away instead.
+    /// - If nothing is in range and in sight, it moves towards the player to find a better spot.
+    ///
+    /// **STATELESS**: This AI does not store any state.
+    Archer,
+
+    /// The AI of a boss: it moves towards the player, winding up a smash once the player is
+    /// close, and summons minions every few turns.
+    ///
+    /// - A smash takes a few turns to land, and hits everything on and around where the player
+    ///   stood when it started winding up (so it can be dodged).
+    /// - It does not summon while winding up a smash.
+    ///
+    /// **STATEFUL**: Remembers the smash it is winding up, and when it last summoned.
+    Boss,
+
+    /// The AI stays close to the nearest other monster, so its [`Aura`] reaches it.
+    ///
+    /// - If the player is adjacent, it backs away instead.
+    /// - If there are no other monsters, it moves towards the player.
+    ///
+    /// **STATELESS**: This AI does not store any state.
+    Support,
+
+    /// The AI holds its post, where it was when it first decided what to do.
+    ///
+    /// - It attacks hostiles right next to it, and shoots at those in range if it can.
+    /// - Otherwise, it walks back to its post.
+    ///
+    /// **STATEFUL**: Remembers its post.
+    Guard,
+
+    /// The AI stays close to the player.
+    ///
+    /// - It attacks hostiles right next to it, and shoots at those in range if it can.
+    ///
+    /// **STATELESS**: This AI does not store any state.
+    Follow,
+
+    /// The AI moves towards the nearest hostile entity, and keeps going after it until it is
+    /// destroyed.
+    ///
+    /// **STATEFUL**: Remembers its target.
+    Hunt,
+
+    /// The AI of a villager: it walks to the nearest farm and tends it, and never fights.
+    ///
+    /// - When something hostile comes close, it runs home and shelters inside (see
+    ///   [`Occupants`]) until the danger has passed.
+    /// - If its home is destroyed, it moves in with the nearest other house.
+    ///
+    /// **STATEFUL**: Remembers its farm and its home.
+    Villager,
 }
@@ -0,0 +1,140 @@
+//! Death effects.
+//!
+//! Entities with [`OnDeath`] do something when they are defeated, e.g. split into copies, drop
+//! what they carried or let out whoever sheltered inside. Effects are triggered after
+//! [`DefeatSystem`](super::combat::DefeatSystem) marks an entity [`Defeated`], while it is still
+//! in the world (and on the map), so new entities can be spawned around it.
+
+use specs::prelude::*;
+
+use super::{
+    components::{DeathEffect, Defeated, Health, OnDeath, Position, Renderable},
+    demo,
+    logger::{LogMessage, Logs},
+    map::{Layer, Map, MapIndexingSystem},
+    village, Glyph,
+};
+
+/// How many copies an entity splits into.
+const SPLIT_INTO: usize = 2;
+
+/// Triggers the death effects of every defeated entity.
+///
+/// Must run after [`DefeatSystem`](super::combat::DefeatSystem) and before
+/// [`RemoveDefeatedSystem`](super::combat::RemoveDefeatedSystem) and [`World::maintain`].
+pub fn trigger_death_effects(ecs: &mut World) {
+    // Make sure the map knows where everything ended up.
+    MapIndexingSystem.run_now(ecs);
+
+    let deaths: Vec<(Entity, Position, Glyph, u8, Vec<DeathEffect>)> = {
+        let entities = ecs.entities();
+        let defeated = ecs.read_storage::<Defeated>();
+        let on_death = ecs.read_storage::<OnDeath>();
+        let positions = ecs.read_storage::<Position>();
+        let renderables = ecs.read_storage::<Renderable>();
+        (&entities, &defeated, &on_death, &positions, &renderables)
+            .join()
+            .map(|(entity, defeated, on_death, position, renderable)| {
+                (
+                    entity,
+                    position.clone(),
+                    renderable.glyph(),
+                    defeated.maximum(),
+                    on_death.effects().to_vec(),
+                )
+            })
+            .collect()
+    };
+
+    for (entity, position, glyph, maximum, effects) in deaths {
+        for effect in effects {
+            match effect {
+                DeathEffect::Split => split(ecs, &position, glyph, maximum / 2),
+                DeathEffect::Drop(item) => {
+                    demo::configure(item, ecs.create_entity(), position.x, position.y).build();
+                }
+                DeathEffect::Evict => {
+                    // Whoever does not fit outside goes down with it.
+                    for stuck in village::release(ecs, entity) {
+                        ecs.delete_entity(stuck).unwrap();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Spawns copies with the given maximum health on the free tiles next to a position.
+fn split(ecs: &mut World, position: &Position, glyph: Glyph, maximum: u8) {
+    if maximum == 0 {
+        return;
+    }
+
+    let free: Vec<Position> = {
+        let map = ecs.fetch::<Map>();
+        map.neighbors(position, map.movement())
+            .into_iter()
+            .filter(|p| map.get_blocker(p.x, p.y, Layer::Ground).is_none())
+            .filter(|p| map.is_passable(p.x, p.y))
+            .take(SPLIT_INTO)
+            .collect()
+    };
+
+    for p in &free {
+        demo::configure(glyph, ecs.create_entity(), p.x, p.y)
+            .with(Health::new(maximum))
+            .build();
+    }
+    ecs.fetch_mut::<Logs>().add(LogMessage::Split {
+        glyph,
+        position: (position.x, position.y),
+        count: free.len() as u8,
+    });
+
+    // Index the copies, so nothing else spawns on top of them.
+    MapIndexingSystem.run_now(ecs);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{logger::LogMessage, scenario::Scenario, Direction, Glyph};
+
+    #[test]
+    fn slimes_split_when_defeated() {
+        let outcome = Scenario::new(
+            "
+            h2 . . .  .
+            .  . . .  .
+            .  . . s1/4 @5
+            ",
+        )
+        .moves(&[Direction::Left])
+        .run();
+
+        // Two copies with half the health fit on the free tiles around it.
+        assert!(outcome.logs.contains(&LogMessage::Split {
+            glyph: Glyph::Slime,
+            position: (3, 2),
+            count: 2,
+        }));
+        assert_eq!(outcome.board.matches("s2").count(), 2);
+    }
+
+    #[test]
+    fn the_smallest_slimes_do_not_split() {
+        let outcome = Scenario::new(
+            "
+            h2 . . .  .
+            .  . . s1 @5
+            ",
+        )
+        .moves(&[Direction::Left])
+        .run();
+
+        assert!(!outcome
+            .logs
+            .iter()
+            .any(|log| matches!(log, LogMessage::Split { .. })));
+        assert!(!outcome.board.contains('s'));
+    }
+}
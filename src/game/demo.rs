@@ -1,4 +1,5 @@
 //! Demo system.
+use super::aura;
 use super::components::*;
 use specs::prelude::*;
 
@@ -52,62 +53,231 @@ pub(super) fn spawn_demo(ecs: &mut World) -> Entity {
     player_entity.expect("No player entity found in demo level")
 }
 
-fn configure_player(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
+pub fn configure_player(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
     entity
         .with(Position::new(x, y))
+        .with(Blocks)
         .with(Renderable::new(Glyph::Player))
         .with(Health::new(32))
         .with(Health::new(32))
+        .with(Vision::new(6))
         .with(Player)
+        .with(Faction::Town)
 }
 
 pub fn configure_rat(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
     entity
         .with(Position::new(x, y))
+        .with(Blocks)
         .with(Renderable::new(Glyph::Rat))
         .with(Health::new(1))
         .with(AI::Wander)
-        .with(Monster)
+        .with(Faction::Monsters)
 }
 
 pub fn configure_goblin(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
     entity
         .with(Position::new(x, y))
+        .with(Blocks)
         .with(Renderable::new(Glyph::Goblin))
         .with(Health::new(1))
         .with(AI::PrioritizePlayer)
-        .with(Monster)
+        .with(Faction::Monsters)
 }
 
 pub fn configure_orc(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
     entity
         .with(Position::new(x, y))
+        .with(Blocks)
         .with(Renderable::new(Glyph::Orc))
         .with(Health::new(2))
         .with(AI::PrioritizeTown)
-        .with(Monster)
+        .with(Faction::Monsters)
+}
+
+pub fn configure_archer(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
+    entity
+        .with(Position::new(x, y))
+        .with(Blocks)
+        .with(Renderable::new(Glyph::Archer))
+        .with(Health::new(1))
+        .with(Ranged::new(4))
+        .with(AI::Archer)
+        .with(Faction::Monsters)
+}
+
+pub fn configure_ogre(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
+    entity
+        .with(Position::new(x, y))
+        .with(Blocks)
+        .with(Renderable::new(Glyph::Ogre))
+        .with(Health::new(12))
+        .with(AI::Boss)
+        .with(Boss)
+        .with(Faction::Monsters)
+}
+
+pub fn configure_bat(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
+    entity
+        .with(Position::new(x, y))
+        .with(Flying)
+        .with(Renderable::new(Glyph::Bat))
+        .with(Health::new(1))
+        .with(AI::PrioritizeTown)
+        .with(Faction::Monsters)
+}
+
+pub fn configure_tower(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
+    entity
+        .with(Position::new(x, y))
+        .with(Blocks)
+        .with(Renderable::new(Glyph::Tower))
+        .with(Health::new(3))
+        .with(Ranged::new(3))
+        .with(Faction::Town)
+}
+
+pub fn configure_slime(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
+    entity
+        .with(Position::new(x, y))
+        .with(Blocks)
+        .with(Renderable::new(Glyph::Slime))
+        .with(Health::new(4))
+        .with(AI::PrioritizeTown)
+        .with(OnDeath::new(DeathEffect::Split))
+        .with(Faction::Monsters)
+}
+
+pub fn configure_shaman(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
+    entity
+        .with(Position::new(x, y))
+        .with(Blocks)
+        .with(Renderable::new(Glyph::Shaman))
+        .with(Health::new(2))
+        .with(AI::Support)
+        .with(Aura::new(AuraEffect::Regeneration(1), 2.0, Affects::Allies))
+        .with(Faction::Monsters)
+}
+
+pub fn configure_militia(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
+    entity
+        .with(Position::new(x, y))
+        .with(Blocks)
+        .with(Renderable::new(Glyph::Militia))
+        .with(Health::new(2))
+        .with(AI::Hunt)
+        .with(Faction::Town)
+}
+
+pub fn configure_guard(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
+    entity
+        .with(Position::new(x, y))
+        .with(Blocks)
+        .with(Renderable::new(Glyph::Guard))
+        .with(Health::new(3))
+        .with(AI::Guard)
+        .with(Faction::Town)
+}
+
+pub fn configure_ranger(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
+    entity
+        .with(Position::new(x, y))
+        .with(Blocks)
+        .with(Renderable::new(Glyph::Ranger))
+        .with(Health::new(1))
+        .with(AI::Follow)
+        .with(Ranged::new(4))
+        .with(Faction::Town)
+}
+
+pub fn configure_villager(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
+    entity
+        .with(Position::new(x, y))
+        .with(Blocks)
+        .with(Renderable::new(Glyph::Villager))
+        .with(Health::new(1))
+        .with(AI::Villager)
+        .with(Faction::Town)
+}
+
+pub fn configure_thief(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
+    entity
+        .with(Position::new(x, y))
+        .with(Blocks)
+        .with(Renderable::new(Glyph::Thief))
+        .with(Health::new(1))
+        .with(AI::Thief)
+        .with(Faction::Monsters)
 }
 
 pub fn configure_farm(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
     entity
         .with(Position::new(x, y))
+        .with(Blocks)
         .with(Renderable::new(Glyph::Farm))
         .with(Health::new(1))
-        .with(Town)
+        .with(Growth::default())
+        .with(Faction::Town)
 }
 
 pub fn configure_wall(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
     entity
         .with(Position::new(x, y))
+        .with(Blocks)
         .with(Renderable::new(Glyph::Wall))
         .with(Health::new(3))
-        .with(Town)
+        .with(Faction::Town)
 }
 
 pub fn configure_house(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
     entity
         .with(Position::new(x, y))
+        .with(Blocks)
         .with(Renderable::new(Glyph::House))
         .with(Health::new(2))
-        .with(Town)
+        .with(Vision::new(3))
+        .with(aura::house())
+        .with(Occupants::new(2))
+        .with(OnDeath::new(DeathEffect::Evict))
+        .with(Faction::Town)
+}
+
+pub fn configure_tree(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
+    entity
+        .with(Position::new(x, y))
+        .with(Blocks)
+        .with(Renderable::new(Glyph::Tree))
+        .with(Health::new(1))
+}
+
+pub fn configure_crops(entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
+    entity
+        .with(Position::new(x, y))
+        .with(Renderable::new(Glyph::Crops))
+}
+
+/// Configures an entity drawn with the given glyph, as if it was just spawned.
+pub fn configure(glyph: Glyph, entity: EntityBuilder, x: i32, y: i32) -> EntityBuilder {
+    match glyph {
+        Glyph::Farm => configure_farm(entity, x, y),
+        Glyph::Orc => configure_orc(entity, x, y),
+        Glyph::Archer => configure_archer(entity, x, y),
+        Glyph::Ogre => configure_ogre(entity, x, y),
+        Glyph::Bat => configure_bat(entity, x, y),
+        Glyph::Tower => configure_tower(entity, x, y),
+        Glyph::Slime => configure_slime(entity, x, y),
+        Glyph::Shaman => configure_shaman(entity, x, y),
+        Glyph::Militia => configure_militia(entity, x, y),
+        Glyph::Guard => configure_guard(entity, x, y),
+        Glyph::Ranger => configure_ranger(entity, x, y),
+        Glyph::Villager => configure_villager(entity, x, y),
+        Glyph::Rat => configure_rat(entity, x, y),
+        Glyph::Goblin => configure_goblin(entity, x, y),
+        Glyph::House => configure_house(entity, x, y),
+        Glyph::Player => configure_player(entity, x, y),
+        Glyph::Tree => configure_tree(entity, x, y),
+        Glyph::Wall => configure_wall(entity, x, y),
+        Glyph::Thief => configure_thief(entity, x, y),
+        Glyph::Crops => configure_crops(entity, x, y),
+    }
 }
@@ -0,0 +1,233 @@
+//! Factions.
+//!
+//! Everything that takes part in a fight belongs to a [`Faction`], and how factions treat each
+//! other is looked up in the [`Relations`] table:
+//!
+//! - Hostile entities attack each other on sight, and are what the AI goes after.
+//! - Neutral entities are left alone, but are still attacked when bumped into (e.g. trees, which
+//!   belong to no faction, or wildlife once it has a faction of its own).
+//! - Allied entities never attack each other.
+//!
+//! Changing an entity's faction (e.g. charming a monster) or the table (e.g. setting monsters
+//! against each other) changes who fights whom, without touching any system.
+
+use std::collections::HashMap;
+
+use specs::prelude::*;
+
+use super::components::Faction;
+
+/// How one faction treats another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relation {
+    Hostile,
+    Neutral,
+    Allied,
+}
+
+/// A singleton that stores how every faction treats every other.
+///
+/// Relations are symmetric. A faction is allied with itself, and neutral towards anything else,
+/// unless set otherwise.
+pub struct Relations {
+    table: HashMap<(Faction, Faction), Relation>,
+}
+
+impl Relations {
+    /// Create the default table: the town and the monsters are at war, and charmed monsters side
+    /// with the town.
+    pub fn new() -> Self {
+        let mut relations = Self {
+            table: HashMap::new(),
+        };
+        relations.set(Faction::Town, Faction::Monsters, Relation::Hostile);
+        relations.set(Faction::Charmed, Faction::Town, Relation::Allied);
+        relations
+    }
+
+    /// Set how two factions treat each other (both ways).
+    pub fn set(&mut self, a: Faction, b: Faction, relation: Relation) {
+        self.table.insert((a, b), relation);
+        self.table.insert((b, a), relation);
+    }
+
+    /// Returns how two factions treat each other.
+    #[must_use]
+    pub fn get(&self, a: Faction, b: Faction) -> Relation {
+        match self.table.get(&(a, b)) {
+            Some(relation) => *relation,
+            None if a == b => Relation::Allied,
+            None => Relation::Neutral,
+        }
+    }
+
+    /// Returns how two entities treat each other; entities without a faction are neutral.
+    #[must_use]
+    pub fn between(&self, factions: &ReadStorage<Faction>, a: Entity, b: Entity) -> Relation {
+        match (factions.get(a), factions.get(b)) {
+            (Some(a), Some(b)) => self.get(*a, *b),
+            _ => Relation::Neutral,
+        }
+    }
+
+    /// Whether two entities are hostile to each other.
+    #[must_use]
+    pub fn is_hostile(&self, factions: &ReadStorage<Faction>, a: Entity, b: Entity) -> bool {
+        self.between(factions, a, b) == Relation::Hostile
+    }
+
+    /// Whether two entities are allied with each other.
+    #[must_use]
+    pub fn is_allied(&self, factions: &ReadStorage<Faction>, a: Entity, b: Entity) -> bool {
+        self.between(factions, a, b) == Relation::Allied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        components::{Memory, Renderable},
+        logger::LogMessage,
+        scenario::{self, Scenario},
+        Direction, Glyph, MovementDenied, WorldState,
+    };
+
+    /// Sets the faction of every entity with the given glyph.
+    fn set_faction(world: &mut WorldState, glyph: Glyph, to: Faction) {
+        let renderables = world.ecs.read_storage::<Renderable>();
+        let mut factions = world.ecs.write_storage::<Faction>();
+        for (renderable, faction) in (&renderables, &mut factions).join() {
+            if renderable.glyph() == glyph {
+                *faction = to;
+            }
+        }
+    }
+
+    /// Returns what the AI with the given glyph is going after, if anything.
+    fn target_of(world: &WorldState, glyph: Glyph) -> Option<Glyph> {
+        let renderables = world.ecs.read_storage::<Renderable>();
+        let memories = world.ecs.read_storage::<Memory>();
+        let (_, memory) = (&renderables, &memories)
+            .join()
+            .find(|(renderable, _)| renderable.glyph() == glyph)?;
+        renderables.get(memory.target?).map(Renderable::glyph)
+    }
+
+    #[test]
+    fn relations_are_symmetric_and_can_change() {
+        let mut relations = Relations::new();
+        assert_eq!(
+            relations.get(Faction::Monsters, Faction::Town),
+            Relation::Hostile
+        );
+        assert_eq!(
+            relations.get(Faction::Town, Faction::Town),
+            Relation::Allied
+        );
+
+        // A truce, then infighting: monsters turn on each other.
+        relations.set(Faction::Town, Faction::Monsters, Relation::Neutral);
+        assert_eq!(
+            relations.get(Faction::Monsters, Faction::Town),
+            Relation::Neutral
+        );
+        relations.set(Faction::Monsters, Faction::Monsters, Relation::Hostile);
+        assert_eq!(
+            relations.get(Faction::Monsters, Faction::Monsters),
+            Relation::Hostile
+        );
+    }
+
+    #[test]
+    fn charmed_monsters_are_allies() {
+        let mut world = scenario::import(
+            "
+            @5 g . .
+            h2 . . .
+            ",
+        );
+        world.tick();
+
+        // Charm the goblin: the player no longer attacks it right away.
+        set_faction(&mut world, Glyph::Goblin, Faction::Charmed);
+        assert!(matches!(
+            world.player_move(Direction::Right),
+            Err(MovementDenied::Friendly)
+        ));
+    }
+
+    #[test]
+    fn charmed_monsters_are_left_alone_by_their_former_allies() {
+        let board = "
+            h2 . . . .
+            .  . . . .
+            @5 . g o2 .
+            ";
+
+        // Charmed, the goblin is no target for the orc next to it.
+        let mut world = scenario::import(board);
+        set_faction(&mut world, Glyph::Goblin, Faction::Charmed);
+        world.tick();
+        assert_eq!(target_of(&world, Glyph::Orc), Some(Glyph::House));
+
+        // Had it simply joined the town, it would be.
+        let mut world = scenario::import(board);
+        set_faction(&mut world, Glyph::Goblin, Faction::Town);
+        world.tick();
+        assert_eq!(target_of(&world, Glyph::Orc), Some(Glyph::Goblin));
+    }
+
+    #[test]
+    fn neutral_entities_are_not_targeted() {
+        let board = "
+            h2 . . . .
+            .  . . . .
+            @5 . . o2 t
+            ";
+
+        // The tree right next to the orc belongs to no faction, so the house is the target.
+        let mut world = scenario::import(board);
+        world.tick();
+        assert_eq!(target_of(&world, Glyph::Orc), Some(Glyph::House));
+
+        // During a truce, the town is no target either.
+        let mut world = scenario::import(board);
+        world
+            .ecs
+            .fetch_mut::<Relations>()
+            .set(Faction::Town, Faction::Monsters, Relation::Neutral);
+        world.tick();
+        assert_eq!(target_of(&world, Glyph::Orc), None);
+    }
+
+    #[test]
+    fn monsters_set_against_each_other_fight() {
+        let board = "
+            h2 . . . .
+            .  . . . .
+            @5 . . o2 g
+            ";
+        let attacked = |logs: &[LogMessage]| {
+            logs.iter().any(|log| {
+                matches!(
+                    log,
+                    LogMessage::Attacked {
+                        attacker: Glyph::Goblin | Glyph::Orc,
+                        target: Glyph::Goblin | Glyph::Orc,
+                        ..
+                    }
+                )
+            })
+        };
+
+        let outcome = Scenario::new(board).moves(&[Direction::Up]).run();
+        assert!(!attacked(&outcome.logs));
+
+        let outcome = Scenario::new(board)
+            .relation(Faction::Monsters, Faction::Monsters, Relation::Hostile)
+            .moves(&[Direction::Up])
+            .run();
+        assert!(attacked(&outcome.logs));
+    }
+}
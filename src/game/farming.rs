@@ -0,0 +1,229 @@
+//! Farming.
+//!
+//! Farms grow crops through the stages of [`CropStage`], a stage every
+//! [`Growth::TURNS_PER_STAGE`] monster turns (faster when tended by villagers) and another one
+//! between rounds. Ripe crops are worth [`HARVEST_FOOD`] when harvested, either by the player
+//! walking into the farm or at the end of the round. Monsters that attack a farm trample (or eat)
+//! whatever has grown, and thieves steal it.
+
+use specs::prelude::*;
+
+use super::{
+    components::{Attacking, Faction, Growth, Player, Position, Renderable},
+    faction::Relations,
+    logger::{LogMessage, Logs},
+    map::Map,
+    RunState, HARVEST_FOOD,
+};
+
+/// A system that grows the crops on every farm on the monster's turn.
+pub struct GrowthSystem;
+
+impl<'a> System<'a> for GrowthSystem {
+    type SystemData = (ReadExpect<'a, RunState>, WriteStorage<'a, Growth>);
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Unpack the system data.
+        let (state, mut growth) = data;
+
+        // If this is not the monster's turn, do nothing.
+        if *state != RunState::MonsterTurn {
+            return;
+        }
+
+        for growth in (&mut growth).join() {
+            growth.grow(1);
+        }
+    }
+}
+
+/// A system that turns the player walking into ripe crops into a harvest, and monsters attacking
+/// a farm into ruined crops.
+///
+/// Must run before [`ApplyAttackSystem`](super::combat::ApplyAttackSystem), so harvesting never
+/// damages the farm.
+pub struct HarvestSystem;
+
+impl<'a> System<'a> for HarvestSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, Map>,
+        ReadExpect<'a, Relations>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Renderable>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, Attacking>,
+        WriteStorage<'a, Growth>,
+        WriteExpect<'a, Logs>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Unpack the system data.
+        let (
+            entities,
+            mut map,
+            relations,
+            factions,
+            players,
+            renderables,
+            positions,
+            mut attacking,
+            mut growth,
+            mut logs,
+        ) = data;
+
+        // Find everything bumping into crops.
+        let bumps: Vec<(Entity, Entity)> = (&entities, &attacking)
+            .join()
+            .filter(|(_, attack)| growth.contains(attack.target()))
+            .map(|(attacker, attack)| (attacker, attack.target()))
+            .collect();
+
+        for (attacker, farm) in bumps {
+            let Some(position) = positions.get(farm) else {
+                continue;
+            };
+            let crops = growth.get_mut(farm).unwrap();
+
+            if players.contains(attacker) {
+                // Harvesting takes the place of the attack; unripe crops are attacked as usual.
+                if crops.harvest() {
+                    attacking.remove(attacker);
+                    map.resources.food = map.resources.food.saturating_add(HARVEST_FOOD);
+                    logs.add(LogMessage::Harvested {
+                        position: (position.x, position.y),
+                        food: HARVEST_FOOD,
+                    });
+                }
+            } else if relations.is_hostile(&factions, attacker, farm) && crops.ruin() {
+                if let Some(renderable) = renderables.get(attacker) {
+                    logs.add(LogMessage::Trampled {
+                        attacker: renderable.glyph(),
+                        position: (position.x, position.y),
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use specs::prelude::*;
+
+    use crate::game::{
+        components::{CropStage, Growth},
+        logger::{LogMessage, Logs},
+        scenario::{self, Scenario},
+        Direction, Glyph, Resources, WorldState,
+    };
+
+    /// Ripens the crops on every farm.
+    fn ripen(world: &mut WorldState) {
+        for growth in (&mut world.ecs.write_storage::<Growth>()).join() {
+            growth.grow(Growth::TURNS_PER_STAGE * 2);
+        }
+    }
+
+    #[test]
+    fn crops_grow_in_stages() {
+        let mut growth = Growth::default();
+        growth.grow(Growth::TURNS_PER_STAGE - 1);
+        assert_eq!(growth.stage(), CropStage::Sown);
+        growth.grow(1);
+        assert_eq!(growth.stage(), CropStage::Sprouting);
+        growth.advance();
+        assert!(growth.is_ripe());
+        assert!(growth.harvest());
+        assert_eq!(growth.stage(), CropStage::Sown);
+        assert!(!growth.harvest());
+    }
+
+    #[test]
+    fn the_player_harvests_ripe_crops() {
+        let mut world = scenario::import(
+            "
+            h2 . . . . . g
+            f  @5 . . . . .
+            ",
+        );
+        world.tick();
+        ripen(&mut world);
+
+        // Walking into ripe crops harvests them right away, without harming the farm.
+        world.player_move(Direction::Left).unwrap();
+        world.tick();
+        let logs = world.ecs.fetch_mut::<Logs>().flush();
+        assert!(logs.contains(&LogMessage::Harvested {
+            position: (0, 1),
+            food: 2,
+        }));
+        assert!(world.export_board().contains('f'));
+    }
+
+    #[test]
+    fn monsters_trample_crops() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . . .
+            .  . . . . .
+            f3 o2 . . . @5
+            ",
+        )
+        .moves(&[Direction::Up, Direction::Down, Direction::Up])
+        .run();
+
+        assert!(outcome.logs.contains(&LogMessage::Trampled {
+            attacker: Glyph::Orc,
+            position: (0, 2),
+        }));
+    }
+
+    #[test]
+    fn only_ripe_crops_are_brought_in_at_the_end_of_the_round() {
+        let board = "
+            h2 . . .
+            f  . . .
+            @5 g . .
+            ";
+
+        // Freshly sown crops only sprout between rounds: the house goes hungry, and pays nothing.
+        let outcome = Scenario::new(board).moves(&[Direction::Right]).run();
+        assert_eq!(outcome.stats.resources, Resources::default());
+
+        // Crops that already sprouted ripen between rounds, and are brought in to feed the house.
+        let mut world = scenario::import(board);
+        world.tick();
+        for growth in (&mut world.ecs.write_storage::<Growth>()).join() {
+            growth.advance();
+        }
+        world.player_move(Direction::Right).unwrap();
+        world.tick();
+        world.tick();
+        assert_eq!(world.get_stats().resources, Resources::new(1, 0, 1));
+    }
+
+    #[test]
+    fn huge_harvests_saturate() {
+        // 300 farms, more than can be counted in a byte.
+        let mut board = format!("h2 {}\n", ". ".repeat(19));
+        for _ in 0..15 {
+            board.push_str(&format!("{}\n", "f ".repeat(20)));
+        }
+        board.push_str(&format!("@5 g {}\n", ". ".repeat(18)));
+
+        let mut world = scenario::import(&board);
+        world.tick();
+        for growth in (&mut world.ecs.write_storage::<Growth>()).join() {
+            growth.advance();
+        }
+        world.player_move(Direction::Right).unwrap();
+        world.tick();
+        world.tick();
+        assert_eq!(
+            world.get_stats().resources,
+            Resources::new(1, 0, u8::MAX - 1)
+        );
+    }
+}
@@ -0,0 +1,377 @@
+//! Field of view and fog of war.
+//!
+//! Everything with [`Vision`] (the player, houses) sees a circle around itself, computed with
+//! recursive shadowcasting over the [`Map`]. Trees and rocks block sight. With fog of war
+//! enabled, only tiles currently in sight are visible, tiles seen before are remembered, and
+//! everything else is unexplored.
+
+use specs::prelude::*;
+
+use super::{
+    components::{Position, Renderable, Vision},
+    map::{Map, Terrain},
+    Glyph,
+};
+
+/// Octant transforms, as (xx, xy, yx, yy) multipliers from octant to map coordinates.
+const OCTANTS: [(i32, i32, i32, i32); 8] = [
+    (1, 0, 0, 1),
+    (0, 1, 1, 0),
+    (0, -1, 1, 0),
+    (-1, 0, 0, 1),
+    (-1, 0, 0, -1),
+    (0, -1, -1, 0),
+    (0, 1, -1, 0),
+    (1, 0, 0, -1),
+];
+
+/// How much of a tile the player can see.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileVisibility {
+    /// Never seen; nothing about it is known.
+    Unexplored,
+
+    /// Seen before but not right now; buildings and terrain are remembered, monsters are not.
+    Remembered,
+
+    /// Currently in sight.
+    Visible,
+}
+
+/// Which tiles are visible and which have been seen before.
+///
+/// When disabled (the default), every tile is always visible.
+pub struct FogOfWar {
+    enabled: bool,
+    width: usize,
+    visible: Vec<bool>,
+    revealed: Vec<bool>,
+}
+
+impl FogOfWar {
+    /// Create a disabled fog of war for a map of the given dimensions.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            enabled: false,
+            width,
+            visible: vec![false; width * height],
+            revealed: vec![false; width * height],
+        }
+    }
+
+    /// Turn fog of war on or off.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Whether fog of war is on.
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns how much of a tile can be seen.
+    ///
+    /// # Panics
+    ///
+    /// If the position is out of bounds.
+    #[must_use]
+    pub fn get(&self, x: i32, y: i32) -> TileVisibility {
+        let index = self.index(x, y);
+        if !self.enabled || self.visible[index] {
+            TileVisibility::Visible
+        } else if self.revealed[index] {
+            TileVisibility::Remembered
+        } else {
+            TileVisibility::Unexplored
+        }
+    }
+
+    /// Returns the visibility of every tile as rows.
+    pub fn rows(&self) -> Vec<Vec<TileVisibility>> {
+        (0..self.visible.len() / self.width)
+            .map(|y| {
+                (0..self.width)
+                    .map(|x| self.get(x as i32, y as i32))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Forget what is currently visible (but not what was seen before).
+    fn clear_visible(&mut self) {
+        self.visible.fill(false);
+    }
+
+    /// Mark a tile as visible, and so seen.
+    fn reveal(&mut self, x: i32, y: i32) {
+        let index = self.index(x, y);
+        self.visible[index] = true;
+        self.revealed[index] = true;
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        assert!(x >= 0 && (x as usize) < self.width && y >= 0);
+        y as usize * self.width + x as usize
+    }
+}
+
+/// Returns every tile within `radius` of `origin` that is not hidden behind an opaque tile.
+///
+/// Opaque tiles themselves are visible (e.g. the tree blocking the view), as is the origin.
+pub fn field_of_view(
+    map: &Map,
+    origin: &Position,
+    radius: i32,
+    opaque: impl Fn(i32, i32) -> bool,
+) -> Vec<Position> {
+    let mut seen = vec![false; map.width() * map.height()];
+    let mut visible = Vec::new();
+    let mut mark = |x: i32, y: i32| {
+        let index = y as usize * map.width() + x as usize;
+        if !seen[index] {
+            seen[index] = true;
+            visible.push(Position::new(x, y));
+        }
+    };
+
+    if !map.in_bounds(origin.x, origin.y) {
+        return visible;
+    }
+    mark(origin.x, origin.y);
+
+    let opaque = |x: i32, y: i32| !map.in_bounds(x, y) || opaque(x, y);
+    for octant in OCTANTS {
+        cast_light(map, origin, radius, 1, 1.0, 0.0, octant, &opaque, &mut mark);
+    }
+
+    visible
+}
+
+/// Whether `to` is within `radius` of `from`, with nothing opaque in between.
+pub fn in_sight(
+    map: &Map,
+    from: &Position,
+    to: &Position,
+    radius: i32,
+    opaque: impl Fn(i32, i32) -> bool,
+) -> bool {
+    if from.distance(to) > radius as f64 {
+        return false;
+    }
+    let line = map.line(from, to);
+    let between = line.len().saturating_sub(2);
+    line.iter().skip(1).take(between).all(|p| !opaque(p.x, p.y))
+}
+
+/// Whether the tile blocks sight: trees and rocks do.
+pub fn blocks_sight(map: &Map, renderables: &ReadStorage<Renderable>, x: i32, y: i32) -> bool {
+    map.get_terrain(x, y) == Terrain::Rock
+        || map
+            .get_entity(x, y)
+            .and_then(|e| renderables.get(e))
+            .is_some_and(|r| r.glyph() == Glyph::Tree)
+}
+
+/// Scans one octant row by row, recursing past every opaque tile to keep track of shadows.
+///
+/// Slopes go from `start` (1.0, the diagonal) down to `end` (0.0, straight ahead).
+#[allow(clippy::too_many_arguments)]
+fn cast_light(
+    map: &Map,
+    origin: &Position,
+    radius: i32,
+    row: i32,
+    mut start: f64,
+    end: f64,
+    (xx, xy, yx, yy): (i32, i32, i32, i32),
+    opaque: &impl Fn(i32, i32) -> bool,
+    mark: &mut impl FnMut(i32, i32),
+) {
+    if start < end {
+        return;
+    }
+
+    let mut new_start = 0.0;
+    for distance in row..=radius {
+        let dy = -distance;
+        let mut blocked = false;
+
+        for dx in -distance..=0 {
+            let x = origin.x + dx * xx + dy * xy;
+            let y = origin.y + dx * yx + dy * yy;
+            let left_slope = (dx as f64 - 0.5) / (dy as f64 + 0.5);
+            let right_slope = (dx as f64 + 0.5) / (dy as f64 - 0.5);
+
+            if start < right_slope {
+                continue;
+            } else if end > left_slope {
+                break;
+            }
+
+            if dx * dx + dy * dy <= radius * radius && map.in_bounds(x, y) {
+                mark(x, y);
+            }
+
+            if blocked {
+                if opaque(x, y) {
+                    // Still in the shadow of the previous opaque tile.
+                    new_start = right_slope;
+                } else {
+                    blocked = false;
+                    start = new_start;
+                }
+            } else if opaque(x, y) && distance < radius {
+                // The start of a shadow: scan what is still visible beside it.
+                blocked = true;
+                let octant = (xx, xy, yx, yy);
+                cast_light(
+                    map,
+                    origin,
+                    radius,
+                    distance + 1,
+                    start,
+                    left_slope,
+                    octant,
+                    opaque,
+                    mark,
+                );
+                new_start = right_slope;
+            }
+        }
+
+        if blocked {
+            break;
+        }
+    }
+}
+
+/// A system that recalculates what is visible from everything with [`Vision`].
+///
+/// Must run after [`MapIndexingSystem`](super::map::MapIndexingSystem), as trees are looked up on
+/// the map.
+pub struct FieldOfViewSystem;
+
+impl<'a> System<'a> for FieldOfViewSystem {
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        WriteExpect<'a, FogOfWar>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Vision>,
+        ReadStorage<'a, Renderable>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Unpack the system data.
+        let (map, mut fog, positions, visions, renderables) = data;
+
+        if !fog.is_enabled() {
+            return;
+        }
+
+        let opaque = |x, y| blocks_sight(&map, &renderables, x, y);
+
+        fog.clear_visible();
+        for (position, vision) in (&positions, &visions).join() {
+            for seen in field_of_view(&map, position, vision.radius(), opaque) {
+                fog.reveal(seen.x, seen.y);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::scenario;
+
+    /// Asserts which tiles are visible, drawn as `#` for visible and `.` for hidden.
+    ///
+    /// Leading indentation and blank lines are ignored.
+    #[track_caller]
+    fn assert_visible(map: &Map, visible: &[Position], expected: &str) {
+        let mut rows = vec![vec!['.'; map.width()]; map.height()];
+        for p in visible {
+            rows[p.y as usize][p.x as usize] = '#';
+        }
+        let actual: Vec<String> = rows.into_iter().map(String::from_iter).collect();
+        let expected: Vec<&str> = expected
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn open_fields_are_seen_in_a_circle() {
+        let map = Map::new(7, 7);
+        let visible = field_of_view(&map, &Position::new(3, 3), 2, |_, _| false);
+
+        assert_visible(
+            &map,
+            &visible,
+            "
+            .......
+            ...#...
+            ..###..
+            .#####.
+            ..###..
+            ...#...
+            .......
+            ",
+        );
+    }
+
+    #[test]
+    fn opaque_tiles_cast_shadows() {
+        let map = Map::new(7, 3);
+        let visible = field_of_view(&map, &Position::new(0, 1), 10, |x, y| (x, y) == (2, 1));
+
+        // The tree itself is seen, but not what is behind it, and the shadow widens with distance.
+        assert_visible(
+            &map,
+            &visible,
+            "
+            ######.
+            ###....
+            ######.
+            ",
+        );
+    }
+
+    #[test]
+    fn everything_is_visible_without_fog() {
+        let mut fog = FogOfWar::new(3, 2);
+        assert_eq!(fog.get(2, 1), TileVisibility::Visible);
+
+        fog.set_enabled(true);
+        assert_eq!(fog.get(2, 1), TileVisibility::Unexplored);
+
+        fog.reveal(2, 1);
+        assert_eq!(fog.get(2, 1), TileVisibility::Visible);
+
+        fog.clear_visible();
+        assert_eq!(fog.get(2, 1), TileVisibility::Remembered);
+        assert_eq!(fog.rows().len(), 2);
+    }
+
+    #[test]
+    fn monsters_behind_trees_are_hidden() {
+        let board = "
+            @5 . t g .
+            h2 . t . .
+            . . . . .
+            ";
+        let mut world = scenario::import(board).with_fog_of_war();
+        world.tick();
+
+        let rendered = world.to_render();
+        assert!(rendered.iter().all(|d| !d.hostile));
+        assert!(rendered.iter().any(|d| d.glyph == Glyph::Tree));
+
+        // What is behind the trees is unexplored, but still exported.
+        assert_eq!(world.visibility()[0][3], TileVisibility::Unexplored);
+        assert!(world.export_board().contains('g'));
+    }
+}
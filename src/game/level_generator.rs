@@ -1,9 +1,96 @@
+use std::fmt;
 use std::num::NonZeroU8;
 
+use bracket_lib::noise::{FastNoise, NoiseType};
 use bracket_lib::random::RandomNumberGenerator;
 use specs::{Builder, Entity, World, WorldExt};
 
-use super::{components::*, Glyph};
+use super::{
+    aura,
+    components::*,
+    level_strategy::{LevelStrategy, Village},
+    map::{Map, Neighborhood, Terrain},
+    pathfinding::DistanceField,
+    Glyph,
+};
+
+/// Cost of walking through water or rock when carving a path to the village.
+const CARVE_COST: u32 = 16;
+
+/// How many times to try generating a playable level before giving up.
+const MAX_ATTEMPTS: u32 = 16;
+
+/// A generated level.
+#[derive(Debug)]
+pub struct Level {
+    /// Entities to insert into the world.
+    pub items: Vec<LevelInsert>,
+
+    /// Terrain rows underneath the entities.
+    pub terrain: Vec<Vec<Terrain>>,
+
+    /// How well the level meets the constraints it was generated with.
+    pub report: LevelReport,
+}
+
+/// The result of validating a generated level.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelReport {
+    /// Houses that were requested.
+    pub houses_expected: usize,
+
+    /// Houses that were placed.
+    pub houses_placed: usize,
+
+    /// Houses the player can walk to.
+    pub houses_reachable: usize,
+
+    /// Edges of the map (out of 4) that monsters can walk to the village from.
+    pub edges_connected: usize,
+
+    /// Open tiles next to the player (0 if there is no player).
+    pub player_moves: usize,
+
+    /// Fraction of the map that was requested to be occupied.
+    pub density_expected: f32,
+
+    /// Fraction of the map that is occupied.
+    pub density: f32,
+}
+
+impl LevelReport {
+    /// Returns whether the level meets every constraint.
+    #[must_use]
+    pub fn is_playable(&self) -> bool {
+        self.houses_placed == self.houses_expected
+            && self.houses_reachable == self.houses_placed
+            && self.edges_connected == 4
+            && self.player_moves > 0
+            && self.density >= self.density_expected
+    }
+}
+
+/// An error when no playable level could be generated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GenerationError {
+    /// How many levels were generated.
+    pub attempts: u32,
+
+    /// The report of the last level that was generated.
+    pub report: LevelReport,
+}
+
+impl fmt::Display for GenerationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "no playable level after {} attempts (last: {:?})",
+            self.attempts, self.report
+        )
+    }
+}
+
+impl std::error::Error for GenerationError {}
 
 #[derive(Debug)]
 pub struct LevelInsert {
@@ -20,9 +107,46 @@ pub enum LevelItem {
     Wall,
 }
 
+/// Parameters for the terrain around the village.
+#[derive(Clone, Debug)]
+pub struct TerrainParameters {
+    /// Number of rivers crossing the map.
+    pub rivers: u8,
+
+    /// Number of shallow fords along each river.
+    pub fords_per_river: u8,
+
+    /// Fraction (0.0 to 1.0) of open land covered by rocky outcrops.
+    pub rock_density: f32,
+
+    /// Frequency of the noise that clusters forests and outcrops; lower means larger clusters.
+    pub cluster_frequency: f32,
+
+    /// How far (in tiles) around each house is kept clear of rivers, rocks, and (if possible) trees.
+    pub clearing_radius: usize,
+
+    /// Whether the first river flows through the village, instead of anywhere on the map.
+    pub river_through_village: bool,
+}
+
+impl Default for TerrainParameters {
+    fn default() -> Self {
+        Self {
+            rivers: 1,
+            fords_per_river: 2,
+            rock_density: 0.04,
+            cluster_frequency: 0.2,
+            clearing_radius: 2,
+            river_through_village: false,
+        }
+    }
+}
+
 pub struct LevelGenerator {
     width: usize,
     height: usize,
+    parameters: TerrainParameters,
+    strategy: Box<dyn LevelStrategy>,
 }
 
 impl LevelGenerator {
@@ -34,11 +158,44 @@ impl LevelGenerator {
     pub fn new(width: usize, height: usize) -> Self {
         assert!(width > 0);
         assert!(height > 0);
-        Self { width, height }
+        Self {
+            width,
+            height,
+            parameters: TerrainParameters::default(),
+            strategy: Box::new(Village),
+        }
+    }
+
+    /// Uses the given strategy instead of the default ([`Village`]).
+    #[must_use]
+    pub fn with_strategy(mut self, strategy: Box<dyn LevelStrategy>) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Returns the strategy levels are generated with.
+    #[must_use]
+    pub fn strategy(&self) -> &dyn LevelStrategy {
+        self.strategy.as_ref()
+    }
+
+    /// Uses the given terrain parameters instead of the defaults.
+    ///
+    /// # Panics
+    ///
+    /// - If rock density is not between 0.0 and 1.0.
+    #[must_use]
+    pub fn with_parameters(mut self, parameters: TerrainParameters) -> Self {
+        assert!((0.0..=1.0).contains(&parameters.rock_density));
+        self.parameters = parameters;
+        self
     }
 
     /// Generates a new level with the given width, height, houses, and tree density (0.0 to 1.0).
     ///
+    /// Levels that are not playable (see [`LevelReport::is_playable`]) are thrown away and
+    /// generated again from a new sub-seed, up to a limited number of attempts.
+    ///
     /// # Panics
     ///
     /// - If houses is 0.
@@ -48,119 +205,533 @@ impl LevelGenerator {
         rng: &mut RandomNumberGenerator,
         houses: u8,
         density: f32,
-    ) -> Vec<LevelInsert> {
+    ) -> Result<Level, GenerationError> {
         assert!(houses > 0);
         assert!((0.0..=1.0).contains(&density));
 
+        let mut attempts = 0;
+        loop {
+            // Each attempt gets its own seed, so a failed attempt can never repeat itself.
+            let mut attempt_rng = RandomNumberGenerator::seeded(rng.next_u64());
+            let level = self.generate_once(&mut attempt_rng, houses, density);
+            attempts += 1;
+
+            if level.report.is_playable() {
+                return Ok(level);
+            }
+            if attempts == MAX_ATTEMPTS {
+                return Err(GenerationError {
+                    attempts,
+                    report: level.report,
+                });
+            }
+        }
+    }
+
+    /// Generates a single level, without checking whether it is playable.
+    fn generate_once(&self, rng: &mut RandomNumberGenerator, houses: u8, density: f32) -> Level {
+        // Let the strategy adjust the terrain and density.
+        let parameters = self.strategy.terrain(self.parameters.clone());
+        let density = self.strategy.density(density);
+
         // First, create a grid of empty (None) tiles we'll use as a baseline.
         let mut grid = vec![vec![None; self.width]; self.height];
 
-        // First, add houses. Houses should be within 3 tiles of another house, but not within 1.
+        // Next, let the strategy lay out the village (houses, farms, and walls).
+        self.strategy.place_village(self, rng, houses, &mut grid);
+
+        // Lay the ground underneath the village: rivers, outcrops, and roads.
+        let terrain = self.generate_terrain(rng, &grid, &parameters);
+
+        // Next, grow forests until we hit the expected density.
+        self.plant_forests(rng, density, &parameters, &terrain, &mut grid);
+
+        // Next add the player to the closest point in the center that is open.
         {
-            let mut houses_added = 0;
+            // Impassable terrain is as good as occupied.
+            let mut open = grid.clone();
+            for (y, row) in terrain.iter().enumerate() {
+                for (x, t) in row.iter().enumerate() {
+                    if !t.is_passable() {
+                        open[y][x] = Some(LevelItem::Tree);
+                    }
+                }
+            }
 
-            // Add the first house at a random position in the center-ish of the map.
-            // Meaning, given a 10x10 map, the first house will be placed between (3,3) and (6,6).
+            // Find the closest open position to the center, and place the player.
+            if let Some((x, y)) =
+                self.find_somewhat_adjacent_position(rng, 1, 3, &LevelItem::House, &open)
             {
-                let x_third = self.width / 3;
-                let y_third = self.height / 3;
+                grid[y][x] = Some(LevelItem::Player {
+                    health: NonZeroU8::new(5).unwrap(),
+                });
+            }
+        }
+
+        // Goblins will be added by the spawn system.
+
+        // Check what we ended up with.
+        let report = self.validate(houses, density, &terrain, &grid);
+
+        // Finally, convert the grid into a vector of inserts.
+        Level {
+            items: self.convert_to_level_inserts(grid),
+            terrain,
+            report,
+        }
+    }
 
-                let x = rng.range(x_third, x_third * 2);
-                let y = rng.range(y_third, y_third * 2);
+    /// Checks a generated grid of items and terrain against the constraints it was generated with.
+    fn validate(
+        &self,
+        houses: u8,
+        density: f32,
+        terrain: &[Vec<Terrain>],
+        grid: &[Vec<Option<LevelItem>>],
+    ) -> LevelReport {
+        let map = Map::with_terrain(terrain.to_vec());
+        let mut house_positions = Vec::new();
+        let mut player = None;
+        let mut occupied = 0;
 
-                grid[y][x] = Some(LevelItem::House);
-                houses_added += 1;
+        for (y, row) in grid.iter().enumerate() {
+            for (x, item) in row.iter().enumerate() {
+                let position = Position::new(x as i32, y as i32);
+                match item {
+                    Some(LevelItem::House) => house_positions.push(position),
+                    Some(LevelItem::Player { .. }) => {
+                        player = Some(position);
+                        continue;
+                    }
+                    Some(_) => {}
+                    None => continue,
+                }
+                occupied += 1;
             }
+        }
 
-            // Add the remaining houses.
-            while houses_added < houses {
-                // Find a random position within 3 tiles of an existing house.
-                let (x, y) =
-                    self.find_somewhat_adjacent_position(rng, 2, 4, &LevelItem::House, &grid);
+        // Monsters spawn along the edges and walk to the houses.
+        let (w, h) = (self.width as i32, self.height as i32);
+        let to_houses = DistanceField::new(&map, &house_positions);
+        let edges_connected = [
+            (0..w).any(|x| to_houses.get(x, 0).is_some()),
+            (0..w).any(|x| to_houses.get(x, h - 1).is_some()),
+            (0..h).any(|y| to_houses.get(0, y).is_some()),
+            (0..h).any(|y| to_houses.get(w - 1, y).is_some()),
+        ]
+        .into_iter()
+        .filter(|connected| *connected)
+        .count();
 
-                // Place the house.
-                grid[y][x] = Some(LevelItem::House);
-                houses_added += 1;
+        // The player must be able to move, and to walk to every house.
+        let (houses_reachable, player_moves) = match &player {
+            Some(player) => {
+                let to_player = DistanceField::new(&map, std::slice::from_ref(player));
+                let houses_reachable = house_positions
+                    .iter()
+                    .filter(|p| to_player.get(p.x, p.y).is_some())
+                    .count();
+                let player_moves = map
+                    .neighbors(player, Neighborhood::Four)
+                    .into_iter()
+                    .filter(|p| grid[p.y as usize][p.x as usize].is_none())
+                    .filter(|p| map.is_passable(p.x, p.y))
+                    .count();
+                (houses_reachable, player_moves)
             }
+            None => (0, 0),
+        };
+
+        LevelReport {
+            houses_expected: houses as usize,
+            houses_placed: house_positions.len(),
+            houses_reachable,
+            edges_connected,
+            player_moves,
+            density_expected: density,
+            density: occupied as f32 / (self.width * self.height) as f32,
         }
+    }
 
-        // Add a farm as close as possible to each house.
+    /// Places houses, each within 2 to 4 tiles of another, starting near the center of the map.
+    ///
+    /// Stops early if the grid is full.
+    pub fn place_houses(
+        &self,
+        rng: &mut RandomNumberGenerator,
+        houses: u8,
+        grid: &mut [Vec<Option<LevelItem>>],
+    ) {
+        let mut houses_added = 0;
+
+        // Add the first house at a random position in the center-ish of the map.
+        // Meaning, given a 10x10 map, the first house will be placed between (3,3) and (6,6).
         {
-            let mut farms_added = 0;
+            let x_third = self.width / 3;
+            let y_third = self.height / 3;
+
+            let x = rng.range(x_third, (x_third * 2).max(x_third + 1));
+            let y = rng.range(y_third, (y_third * 2).max(y_third + 1));
+
+            grid[y][x] = Some(LevelItem::House);
+            houses_added += 1;
+        }
 
-            while farms_added < houses {
-                // Find a random position within 1 tile of an existing house.
-                let (x, y) =
-                    self.find_somewhat_adjacent_position(rng, 1, 2, &LevelItem::House, &grid);
+        // Add the remaining houses.
+        while houses_added < houses {
+            // Find a random position within 3 tiles of an existing house.
+            let Some((x, y)) =
+                self.find_somewhat_adjacent_position(rng, 2, 4, &LevelItem::House, grid)
+            else {
+                break;
+            };
+
+            // Place the house.
+            grid[y][x] = Some(LevelItem::House);
+            houses_added += 1;
+        }
+    }
 
-                // Place the farm.
-                grid[y][x] = Some(LevelItem::Farm);
-                farms_added += 1;
+    /// Places farms, each as close as possible to a house.
+    ///
+    /// Stops early if the grid is full.
+    pub fn place_farms(
+        &self,
+        rng: &mut RandomNumberGenerator,
+        farms: u8,
+        grid: &mut [Vec<Option<LevelItem>>],
+    ) {
+        for _ in 0..farms {
+            // Find a random position within 1 tile of an existing house.
+            let Some((x, y)) =
+                self.find_somewhat_adjacent_position(rng, 1, 2, &LevelItem::House, grid)
+            else {
+                break;
+            };
+
+            // Place the farm.
+            grid[y][x] = Some(LevelItem::Farm);
+        }
+    }
+
+    /// Places walls. Each wall has a 50% chance of "defending" a house or farm.
+    ///
+    /// Walls always try to face "outwards" towards the edge of the board. Stops early if the grid
+    /// is full.
+    pub fn place_walls(
+        &self,
+        rng: &mut RandomNumberGenerator,
+        walls: u8,
+        grid: &mut [Vec<Option<LevelItem>>],
+    ) {
+        for _ in 0..walls {
+            // Find a random position adjacent to an existing house or farm.
+            let item_to_protect = if rng.range(0, 2) == 0 {
+                LevelItem::House
+            } else {
+                LevelItem::Farm
+            };
+            let Some((x, y)) =
+                self.find_adjacent_outwards_facing_position(rng, &item_to_protect, grid)
+            else {
+                break;
+            };
+
+            // Place the wall.
+            grid[y][x] = Some(LevelItem::Wall);
+        }
+    }
+
+    /// Generates terrain underneath a grid of items.
+    ///
+    /// - Farms (and the tiles around them) are on soil.
+    /// - Houses (and the tiles next to them) are on dirt.
+    /// - A road leads from a house to the closest edge of the map.
+    /// - Rivers meander across the map outside of the village clearing, with a few fords.
+    /// - Rocky outcrops cluster on open grass outside of the village clearing.
+    ///
+    /// Every edge of the map is guaranteed to have a path to the village.
+    fn generate_terrain(
+        &self,
+        rng: &mut RandomNumberGenerator,
+        grid: &[Vec<Option<LevelItem>>],
+        parameters: &TerrainParameters,
+    ) -> Vec<Vec<Terrain>> {
+        let mut terrain = vec![vec![Terrain::Grass; self.width]; self.height];
+        let houses = self.all_items_of_type_shuffled(rng, &LevelItem::House, grid);
+        let farms = self.all_items_of_type_shuffled(rng, &LevelItem::Farm, grid);
+
+        // Soil around farms.
+        for (x, y) in &farms {
+            for (nx, ny) in self.neighbors(*x, *y, Neighborhood::Eight) {
+                if matches!(grid[ny][nx], None | Some(LevelItem::Farm)) {
+                    terrain[ny][nx] = Terrain::Soil;
+                }
             }
+            terrain[*y][*x] = Terrain::Soil;
         }
 
-        // Add 2x walls per house. Each wall has a 50% chance of "defending" a house or farm.
-        // Walls always try to face "outwards" towards the edge of the board.
-        {
-            let mut walls_added = 0;
+        // Dirt around houses.
+        for (x, y) in &houses {
+            for (nx, ny) in self.neighbors(*x, *y, Neighborhood::Four) {
+                if terrain[ny][nx] == Terrain::Grass {
+                    terrain[ny][nx] = Terrain::Dirt;
+                }
+            }
+            terrain[*y][*x] = Terrain::Dirt;
+        }
 
-            while walls_added < houses * 2 {
-                // Find a random position adjacent to an existing house or farm.
-                let item_to_protect = if rng.range(0, 2) == 0 {
-                    LevelItem::House
-                } else {
-                    LevelItem::Farm
-                };
-                let (x, y) =
-                    self.find_adjacent_outwards_facing_position(rng, &item_to_protect, &grid);
+        // A road from one of the houses straight to the closest edge.
+        if let Some((x, y)) = houses.first() {
+            let (x, y) = (*x as i32, *y as i32);
+            let (w, h) = (self.width as i32, self.height as i32);
+            let (dx, dy) = [
+                (x, (-1, 0)),
+                (w - 1 - x, (1, 0)),
+                (y, (0, -1)),
+                (h - 1 - y, (0, 1)),
+            ]
+            .into_iter()
+            .min_by_key(|(distance, _)| *distance)
+            .unwrap()
+            .1;
+
+            let (mut rx, mut ry) = (x + dx, y + dy);
+            while rx >= 0 && rx < w && ry >= 0 && ry < h {
+                if terrain[ry as usize][rx as usize] != Terrain::Soil {
+                    terrain[ry as usize][rx as usize] = Terrain::Road;
+                }
+                rx += dx;
+                ry += dy;
+            }
+        }
+
+        // Rivers only flow through untouched grass outside of the clearing (roads become bridges).
+        let radius = parameters.clearing_radius;
+        let wild = |x: usize, y: usize, terrain: &[Vec<Terrain>]| {
+            grid[y][x].is_none()
+                && terrain[y][x] == Terrain::Grass
+                && !in_clearing(x, y, &houses, radius)
+        };
 
-                // Place the wall.
-                grid[y][x] = Some(LevelItem::Wall);
-                walls_added += 1;
+        for river in 0..parameters.rivers {
+            let through = match houses.first() {
+                Some(house) if river == 0 && parameters.river_through_village => Some(*house),
+                _ => None,
+            };
+            let river = self.trace_river(rng, parameters, through);
+            let mut flooded = Vec::new();
+            for (x, y) in river {
+                if wild(x, y, &terrain) {
+                    terrain[y][x] = Terrain::Water;
+                    flooded.push((x, y));
+                }
+            }
+
+            // Fords.
+            self.shuffle(rng, &mut flooded);
+            for (x, y) in flooded
+                .into_iter()
+                .take(parameters.fords_per_river as usize)
+            {
+                terrain[y][x] = Terrain::Ford;
             }
         }
 
-        // Next, check density so far and add trees until we hit the expected density.
+        // Rocky outcrops on the open grass where the noise peaks.
         {
-            // Amount of occupied tiles so far.
-            let total_tiles = (self.width * self.height) as f32;
-            let total_houses_farms_and_walls = houses as f32 * 4.0;
+            let mut noise = FastNoise::seeded(rng.next_u64());
+            noise.set_noise_type(NoiseType::Simplex);
+            noise.set_frequency(parameters.cluster_frequency);
 
-            let mut total_trees = 0.0;
-            let mut total_density = total_houses_farms_and_walls / total_tiles;
+            let mut open = Vec::new();
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    if wild(x, y, &terrain) {
+                        open.push((noise.get_noise(x as f32, y as f32), x, y));
+                    }
+                }
+            }
+            open.sort_by(|a, b| b.0.total_cmp(&a.0));
 
-            while total_density < density {
-                // Find a random position within 1 tile of an existing house or farm.
-                let (x, y) = self.find_any_open_position(rng, &grid);
+            let rocks = (open.len() as f32 * parameters.rock_density).round() as usize;
+            for (_, x, y) in open.into_iter().take(rocks) {
+                terrain[y][x] = Terrain::Rock;
+            }
+        }
+
+        // Finally, make sure monsters can reach the village from every edge.
+        self.connect_edges(&mut terrain, &houses);
 
-                // Place the tree.
-                grid[y][x] = Some(LevelItem::Tree);
+        terrain
+    }
 
-                // Recalculate density.
-                total_trees += 1.0;
-                total_density = (total_houses_farms_and_walls + total_trees) / total_tiles;
+    /// Returns the tiles of a river that meanders from one edge of the map to the opposite one,
+    /// optionally passing through the given position.
+    ///
+    /// Consecutive tiles are always orthogonally adjacent, so the river has no diagonal gaps.
+    fn trace_river(
+        &self,
+        rng: &mut RandomNumberGenerator,
+        parameters: &TerrainParameters,
+        through: Option<(usize, usize)>,
+    ) -> Vec<(usize, usize)> {
+        let mut noise = FastNoise::seeded(rng.next_u64());
+        noise.set_noise_type(NoiseType::Perlin);
+        noise.set_frequency(parameters.cluster_frequency / 3.0);
+
+        // Flow along the longer side (randomly, for square maps), drifting across the other one.
+        let vertical = match self.height.cmp(&self.width) {
+            std::cmp::Ordering::Greater => true,
+            std::cmp::Ordering::Less => false,
+            std::cmp::Ordering::Equal => rng.range(0, 2) == 0,
+        };
+        let (length, breadth) = if vertical {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        };
+
+        let start = match through {
+            Some((x, y)) => {
+                let (along, across) = if vertical { (y, x) } else { (x, y) };
+                across as f32 - noise.get_noise(along as f32, 0.0) * breadth as f32
+            }
+            None => rng.range(0, breadth) as f32,
+        };
+        let mut river = Vec::new();
+        let mut previous = None;
+        for along in 0..length {
+            let drift = noise.get_noise(along as f32, 0.0) * breadth as f32;
+            let across = (start + drift).round().clamp(0.0, breadth as f32 - 1.0) as usize;
+
+            // Fill in any sideways steps.
+            let from = previous.unwrap_or(across);
+            for across in from.min(across)..=from.max(across) {
+                river.push(if vertical {
+                    (across, along)
+                } else {
+                    (along, across)
+                });
             }
+            previous = Some(across);
         }
+        river
+    }
 
-        // Next add the player to the closest point in the center that is open.
-        {
-            // Find the closest open position to the center.
-            let (x, y) = self.find_somewhat_adjacent_position(rng, 1, 3, &LevelItem::House, &grid);
+    /// Plants trees until the expected density is reached, clustering them into forests.
+    ///
+    /// Trees are never planted on roads, soil, or impassable terrain, and only in the clearing
+    /// around houses if there is no room left outside of it.
+    fn plant_forests(
+        &self,
+        rng: &mut RandomNumberGenerator,
+        density: f32,
+        parameters: &TerrainParameters,
+        terrain: &[Vec<Terrain>],
+        grid: &mut [Vec<Option<LevelItem>>],
+    ) {
+        // Amount of occupied tiles so far.
+        let total_tiles = (self.width * self.height) as f32;
+        let occupied = grid.iter().flatten().filter(|item| item.is_some()).count() as f32;
+        let wanted = ((density * total_tiles) - occupied).ceil();
+        if wanted <= 0.0 {
+            return;
+        }
 
-            // Place the player.
-            grid[y][x] = Some(LevelItem::Player {
-                health: NonZeroU8::new(5).unwrap(),
-            });
+        let village = self.all_items_of_type_shuffled(rng, &LevelItem::House, grid);
+        let mut noise = FastNoise::seeded(rng.next_u64());
+        noise.set_noise_type(NoiseType::SimplexFractal);
+        noise.set_frequency(parameters.cluster_frequency);
+
+        // Rank open tiles: outside of the clearing first, then by forest noise (with some jitter).
+        let mut candidates = Vec::new();
+        for (y, row) in grid.iter().enumerate() {
+            for (x, item) in row.iter().enumerate() {
+                let t = terrain[y][x];
+                if item.is_some() || !t.can_build(Glyph::Tree) || t == Terrain::Road {
+                    continue;
+                }
+                let jitter = rng.range(0, 100) as f32 / 500.0;
+                let score = noise.get_noise(x as f32, y as f32) + jitter;
+                let clearing = in_clearing(x, y, &village, parameters.clearing_radius);
+                candidates.push((clearing, score, x, y));
+            }
         }
+        candidates.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.total_cmp(&a.1)));
 
-        // Goblins will be added by the spawn system.
+        for (_, _, x, y) in candidates.into_iter().take(wanted as usize) {
+            grid[y][x] = Some(LevelItem::Tree);
+        }
+    }
 
-        // Finally, convert the grid into a vector of inserts.
-        self.convert_to_level_inserts(grid)
+    /// Carves fords and paths through rivers and rocks until every edge can reach the village.
+    fn connect_edges(&self, terrain: &mut [Vec<Terrain>], houses: &[(usize, usize)]) {
+        let goals: Vec<Position> = houses
+            .iter()
+            .map(|(x, y)| Position::new(*x as i32, *y as i32))
+            .collect();
+        let (w, h) = (self.width as i32, self.height as i32);
+        let edges: [Vec<(i32, i32)>; 4] = [
+            (0..w).map(|x| (x, 0)).collect(),
+            (0..w).map(|x| (x, h - 1)).collect(),
+            (0..h).map(|y| (0, y)).collect(),
+            (0..h).map(|y| (w - 1, y)).collect(),
+        ];
+
+        for edge in &edges {
+            let map = Map::with_terrain(terrain.to_vec());
+            let field = DistanceField::new(&map, &goals);
+            if edge.iter().any(|(x, y)| field.get(*x, *y).is_some()) {
+                continue;
+            }
+
+            // Walk the cheapest path if water and rock were merely expensive, clearing the way.
+            let field = DistanceField::with_costs(&map, &goals, |t| {
+                Some(t.movement_cost().unwrap_or(CARVE_COST))
+            });
+            let (x, y) = match edge
+                .iter()
+                .filter(|(x, y)| field.get(*x, *y).is_some())
+                .min_by_key(|(x, y)| field.get(*x, *y))
+            {
+                Some(&(x, y)) => (x, y),
+                None => continue,
+            };
+            let mut position = Position::new(x, y);
+            loop {
+                let tile = &mut terrain[position.y as usize][position.x as usize];
+                match tile {
+                    Terrain::Water => *tile = Terrain::Ford,
+                    Terrain::Rock => *tile = Terrain::Dirt,
+                    _ => {}
+                }
+                if field.get(position.x, position.y) == Some(0) {
+                    break;
+                }
+                match field.step(&position, &Moving::Up) {
+                    Some(direction) => position = position.after(&direction),
+                    None => break,
+                }
+            }
+        }
+    }
+
+    /// Returns the in-bounds tiles next to a position.
+    fn neighbors(&self, x: usize, y: usize, neighborhood: Neighborhood) -> Vec<(usize, usize)> {
+        neighborhood
+            .offsets()
+            .iter()
+            .map(|(dx, dy)| (x as i32 + dx, y as i32 + dy))
+            .filter(|(nx, ny)| {
+                *nx >= 0 && *nx < self.width as i32 && *ny >= 0 && *ny < self.height as i32
+            })
+            .map(|(nx, ny)| (nx as usize, ny as usize))
+            .collect()
     }
 
     /// Given a vector of items, shuffles them in place.
-    pub fn shuffle<T>(&mut self, rng: &mut RandomNumberGenerator, items: &mut Vec<T>) {
+    pub fn shuffle<T>(&self, rng: &mut RandomNumberGenerator, items: &mut Vec<T>) {
         // This is not a great shuffle impl, but self.rng is not a great RNG impl.
         // In practice we should not need something significantly better.
         let starting_len = items.len();
@@ -173,7 +744,7 @@ impl LevelGenerator {
 
     /// Returns all positions of a given item type in a grid, shuffled.
     fn all_items_of_type_shuffled(
-        &mut self,
+        &self,
         rng: &mut RandomNumberGenerator,
         of: &LevelItem,
         grid: &[Vec<Option<LevelItem>>],
@@ -208,30 +779,26 @@ impl LevelGenerator {
 
         let mut spots = Vec::new();
 
-        fn add_spot_if_in_bounds(
-            spots: &mut Vec<Spot>,
-            x: i32,
-            y: i32,
-            width: usize,
-            height: usize,
-        ) {
+        let (width, height) = (self.width, self.height);
+        let mut add_spot_if_in_bounds = |x: i32, y: i32| {
             if x >= 0 && x < width as i32 && y >= 0 && y < height as i32 {
+                let (x, y) = (x as usize, y as usize);
                 spots.push(Spot {
-                    x: x as usize,
-                    y: y as usize,
-                    distance_to_nearest_edge: 0,
+                    x,
+                    y,
+                    distance_to_nearest_edge: x.min(width - 1 - x).min(y).min(height - 1 - y),
                 });
             }
-        }
+        };
 
         // Left.
-        add_spot_if_in_bounds(&mut spots, x as i32 - 1, y as i32, self.width, self.height);
+        add_spot_if_in_bounds(x as i32 - 1, y as i32);
         // Right.
-        add_spot_if_in_bounds(&mut spots, x as i32 + 1, y as i32, self.width, self.height);
+        add_spot_if_in_bounds(x as i32 + 1, y as i32);
         // Up.
-        add_spot_if_in_bounds(&mut spots, x as i32, y as i32 - 1, self.width, self.height);
+        add_spot_if_in_bounds(x as i32, y as i32 - 1);
         // Down.
-        add_spot_if_in_bounds(&mut spots, x as i32, y as i32 + 1, self.width, self.height);
+        add_spot_if_in_bounds(x as i32, y as i32 + 1);
 
         // Sort by distance to nearest edge.
         spots.sort_by_key(|s| s.distance_to_nearest_edge);
@@ -247,15 +814,15 @@ impl LevelGenerator {
     /// - If no open position is found, tries the next closest edge.
     /// - If still no open position is found, tries the next item of the provided item type.
     ///
-    /// As a fallback, picks a completely random open position.
+    /// As a fallback, picks a completely random open position, or `None` if the grid is full.
     fn find_adjacent_outwards_facing_position(
-        &mut self,
+        &self,
         rng: &mut RandomNumberGenerator,
-        _of: &LevelItem,
+        of: &LevelItem,
         grid: &[Vec<Option<LevelItem>>],
-    ) -> (usize, usize) {
+    ) -> Option<(usize, usize)> {
         // Make a list of the positions of all items of the provided type.
-        let mut positions = self.all_items_of_type_shuffled(rng, &LevelItem::House, grid);
+        let mut positions = self.all_items_of_type_shuffled(rng, of, grid);
 
         while !positions.is_empty() {
             let next = positions.pop().unwrap();
@@ -264,7 +831,7 @@ impl LevelGenerator {
             let adjacent_positions = self.closest_board_edges(next.0, next.1);
             for (x, y) in adjacent_positions {
                 if grid[y][x].is_none() {
-                    return (x, y);
+                    return Some((x, y));
                 }
             }
         }
@@ -274,14 +841,16 @@ impl LevelGenerator {
     }
 
     /// Finds a random position within the grid that is somewhat adjacent to the given position.
+    ///
+    /// As a fallback, picks a completely random open position, or `None` if the grid is full.
     pub fn find_somewhat_adjacent_position(
-        &mut self,
+        &self,
         rng: &mut RandomNumberGenerator,
         outside: usize,
         within: usize,
         of: &LevelItem,
         grid: &[Vec<Option<LevelItem>>],
-    ) -> (usize, usize) {
+    ) -> Option<(usize, usize)> {
         // Make a list of the positions of all items of the provided type.
         let positions = self.all_items_of_type_shuffled(rng, of, grid);
 
@@ -296,13 +865,13 @@ impl LevelGenerator {
                     let y = *y as isize + dy * try_distance as isize;
 
                     // Check if the position is in bounds.
-                    if x < 0 || x >= self.width as isize || y <= 0 || y >= self.height as isize {
+                    if x < 0 || x >= self.width as isize || y < 0 || y >= self.height as isize {
                         continue;
                     }
 
                     // Check if the position is open.
                     if grid[y as usize][x as usize].is_none() {
-                        return (x as usize, y as usize);
+                        return Some((x as usize, y as usize));
                     }
                 }
             }
@@ -314,12 +883,12 @@ impl LevelGenerator {
         self.find_any_open_position(rng, grid)
     }
 
-    /// Finds a random position within the grid that is empty.
-    fn find_any_open_position(
-        &mut self,
+    /// Finds a random position within the grid that is empty, or `None` if the grid is full.
+    pub fn find_any_open_position(
+        &self,
         rng: &mut RandomNumberGenerator,
         grid: &[Vec<Option<LevelItem>>],
-    ) -> (usize, usize) {
+    ) -> Option<(usize, usize)> {
         let mut positions = Vec::new();
 
         for (y, row) in grid.iter().enumerate() {
@@ -330,7 +899,10 @@ impl LevelGenerator {
             }
         }
 
-        positions[rng.range(0, positions.len())]
+        if positions.is_empty() {
+            return None;
+        }
+        Some(positions[rng.range(0, positions.len())])
     }
 
     fn convert_to_level_inserts(&self, grid: Vec<Vec<Option<LevelItem>>>) -> Vec<LevelInsert> {
@@ -363,7 +935,7 @@ impl LevelGenerator {
                 Position::new(x as i32, y as i32)
             };
 
-            let entity = world.create_entity().with(position);
+            let entity = world.create_entity().with(position).with(Blocks);
 
             match insert.item {
                 LevelItem::Player { health } => {
@@ -371,7 +943,9 @@ impl LevelGenerator {
                         entity
                             .with(Renderable::new(Glyph::Player))
                             .with(Health::new(health.get()))
+                            .with(Vision::new(6))
                             .with(Player)
+                            .with(Faction::Town)
                             .build(),
                     );
                 }
@@ -379,21 +953,26 @@ impl LevelGenerator {
                     entity
                         .with(Renderable::new(Glyph::Farm))
                         .with(Health::new(1))
-                        .with(Town)
+                        .with(Growth::default())
+                        .with(Faction::Town)
                         .build();
                 }
                 LevelItem::House => {
                     entity
                         .with(Renderable::new(Glyph::House))
                         .with(Health::new(2))
-                        .with(Town)
+                        .with(Vision::new(3))
+                        .with(aura::house())
+                        .with(Occupants::new(2))
+                        .with(OnDeath::new(DeathEffect::Evict))
+                        .with(Faction::Town)
                         .build();
                 }
                 LevelItem::Wall => {
                     entity
                         .with(Renderable::new(Glyph::Wall))
                         .with(Health::new(3))
-                        .with(Town)
+                        .with(Faction::Town)
                         .build();
                 }
                 LevelItem::Tree => {
@@ -407,3 +986,174 @@ impl LevelGenerator {
         player
     }
 }
+
+/// Returns whether a position is within `radius` tiles of any of the given houses.
+fn in_clearing(x: usize, y: usize, houses: &[(usize, usize)], radius: usize) -> bool {
+    houses
+        .iter()
+        .any(|(hx, hy)| hx.abs_diff(x).max(hy.abs_diff(y)) <= radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Map sizes the property tests run against.
+    const SIZES: [(usize, usize); 5] = [(6, 6), (12, 12), (20, 8), (8, 20), (40, 30)];
+
+    /// Generates a level for every size and seed, returning the map and the houses.
+    fn generate_all(parameters: TerrainParameters, seeds: u64) -> Vec<(Map, Vec<Position>, Level)> {
+        let mut levels = Vec::new();
+        for (width, height) in SIZES {
+            for seed in 0..seeds {
+                let mut rng = RandomNumberGenerator::seeded(seed);
+                let mut generator =
+                    LevelGenerator::new(width, height).with_parameters(parameters.clone());
+                let level = match generator.generate(&mut rng, 2, 0.15) {
+                    Ok(level) => level,
+                    Err(e) => panic!("{}x{} seed {}: {}", width, height, seed, e),
+                };
+                let houses = level
+                    .items
+                    .iter()
+                    .filter(|i| i.item == LevelItem::House)
+                    .map(|i| Position::new(i.position.0 as i32, i.position.1 as i32))
+                    .collect();
+                levels.push((Map::with_terrain(level.terrain.clone()), houses, level));
+            }
+        }
+        levels
+    }
+
+    #[test]
+    fn generated_levels_are_playable() {
+        for (_, _, level) in generate_all(TerrainParameters::default(), 1000) {
+            assert!(level.report.is_playable(), "{:?}", level.report);
+            let players = level
+                .items
+                .iter()
+                .filter(|i| matches!(i.item, LevelItem::Player { .. }))
+                .count();
+            assert_eq!(players, 1);
+        }
+    }
+
+    #[test]
+    fn every_edge_reaches_the_village() {
+        let parameters = TerrainParameters {
+            rivers: 3,
+            fords_per_river: 0,
+            rock_density: 0.3,
+            ..TerrainParameters::default()
+        };
+        for (map, houses, _) in generate_all(parameters, 25) {
+            let field = DistanceField::new(&map, &houses);
+            let (w, h) = (map.width() as i32, map.height() as i32);
+            assert!((0..w).any(|x| field.get(x, 0).is_some()));
+            assert!((0..w).any(|x| field.get(x, h - 1).is_some()));
+            assert!((0..h).any(|y| field.get(0, y).is_some()));
+            assert!((0..h).any(|y| field.get(w - 1, y).is_some()));
+        }
+    }
+
+    #[test]
+    fn items_stand_on_terrain_they_can_use() {
+        for (map, _, level) in generate_all(TerrainParameters::default(), 25) {
+            for insert in &level.items {
+                let (x, y) = (insert.position.0 as i32, insert.position.1 as i32);
+                let terrain = map.get_terrain(x, y);
+                match insert.item {
+                    LevelItem::Farm => assert_eq!(terrain, Terrain::Soil),
+                    LevelItem::Tree => assert!(terrain.can_build(Glyph::Tree)),
+                    _ => assert!(terrain.is_passable()),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn impossible_constraints_are_reported() {
+        let mut rng = RandomNumberGenerator::seeded(0);
+        let mut generator = LevelGenerator::new(6, 6);
+
+        // A fully occupied map leaves the player nowhere to go.
+        let error = generator.generate(&mut rng, 2, 1.0).unwrap_err();
+        assert_eq!(error.attempts, MAX_ATTEMPTS);
+        assert_eq!(error.report.density_expected, 1.0);
+        assert!(!error.report.is_playable());
+    }
+
+    #[test]
+    fn positions_on_the_top_row_are_adjacent() {
+        let mut rng = RandomNumberGenerator::seeded(0);
+        let generator = LevelGenerator::new(3, 2);
+        let grid = vec![
+            vec![None, None, None],
+            vec![
+                Some(LevelItem::Tree),
+                Some(LevelItem::House),
+                Some(LevelItem::Tree),
+            ],
+        ];
+
+        let position =
+            generator.find_somewhat_adjacent_position(&mut rng, 1, 2, &LevelItem::House, &grid);
+        assert_eq!(position, Some((1, 0)));
+    }
+
+    #[test]
+    fn walls_protect_the_requested_item() {
+        let mut rng = RandomNumberGenerator::seeded(0);
+        let generator = LevelGenerator::new(5, 3);
+        let grid = vec![
+            vec![
+                None,
+                Some(LevelItem::Tree),
+                None,
+                Some(LevelItem::Tree),
+                None,
+            ],
+            vec![
+                None,
+                Some(LevelItem::House),
+                None,
+                Some(LevelItem::Farm),
+                None,
+            ],
+            vec![
+                None,
+                Some(LevelItem::Tree),
+                None,
+                Some(LevelItem::Tree),
+                None,
+            ],
+        ];
+
+        // The farm is closest to the right edge, the tile between them is not outward-facing.
+        let position =
+            generator.find_adjacent_outwards_facing_position(&mut rng, &LevelItem::Farm, &grid);
+        assert_eq!(position, Some((4, 1)));
+    }
+
+    #[test]
+    fn closest_board_edges_are_sorted() {
+        let generator = LevelGenerator::new(10, 10);
+        assert_eq!(
+            generator.closest_board_edges(7, 2),
+            vec![(8, 2), (7, 1), (6, 2), (7, 3)]
+        );
+    }
+
+    #[test]
+    fn full_grids_have_no_open_positions() {
+        let mut rng = RandomNumberGenerator::seeded(0);
+        let generator = LevelGenerator::new(2, 2);
+        let grid = vec![vec![Some(LevelItem::Tree); 2]; 2];
+
+        assert_eq!(generator.find_any_open_position(&mut rng, &grid), None);
+        assert_eq!(
+            generator.find_somewhat_adjacent_position(&mut rng, 1, 2, &LevelItem::Tree, &grid),
+            None
+        );
+    }
+}
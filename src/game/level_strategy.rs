@@ -0,0 +1,294 @@
+//! Level strategies.
+//!
+//! A [`LevelStrategy`] decides how the village is laid out and how wild the land around it is,
+//! while the [`LevelGenerator`] does everything else (terrain, forests, the player, validation).
+
+use bracket_lib::random::RandomNumberGenerator;
+
+use super::level_generator::{LevelGenerator, LevelItem, TerrainParameters};
+
+/// How many random positions are considered for each scattered house.
+const HAMLET_CANDIDATES: usize = 16;
+
+/// A way of laying out a level.
+pub trait LevelStrategy: Send + Sync {
+    /// Returns the name used to choose the strategy (e.g. on the command line) and record it.
+    fn name(&self) -> &'static str;
+
+    /// Places the village (houses, farms, and walls) on an empty grid.
+    fn place_village(
+        &self,
+        generator: &LevelGenerator,
+        rng: &mut RandomNumberGenerator,
+        houses: u8,
+        grid: &mut [Vec<Option<LevelItem>>],
+    );
+
+    /// Adjusts the terrain parameters; by default they are used as-is.
+    fn terrain(&self, parameters: TerrainParameters) -> TerrainParameters {
+        parameters
+    }
+
+    /// Adjusts the tree density (0.0 to 1.0); by default it is used as-is.
+    fn density(&self, density: f32) -> f32 {
+        density
+    }
+}
+
+/// Returns every strategy, starting with the default.
+pub fn all() -> Vec<Box<dyn LevelStrategy>> {
+    vec![
+        Box::new(Village),
+        Box::new(Fortress),
+        Box::new(Hamlets),
+        Box::new(RiverCrossing),
+        Box::new(OpenField),
+    ]
+}
+
+/// Returns the strategy with the given name, if any.
+pub fn by_name(name: &str) -> Option<Box<dyn LevelStrategy>> {
+    all().into_iter().find(|s| s.name() == name)
+}
+
+/// The default: houses close together, each with a farm and two walls facing outwards.
+pub struct Village;
+
+impl LevelStrategy for Village {
+    fn name(&self) -> &'static str {
+        "village"
+    }
+
+    fn place_village(
+        &self,
+        generator: &LevelGenerator,
+        rng: &mut RandomNumberGenerator,
+        houses: u8,
+        grid: &mut [Vec<Option<LevelItem>>],
+    ) {
+        generator.place_houses(rng, houses, grid);
+        generator.place_farms(rng, houses, grid);
+        generator.place_walls(rng, houses * 2, grid);
+    }
+}
+
+/// A village surrounded by a ring of walls, with a single gap as a gate.
+pub struct Fortress;
+
+impl LevelStrategy for Fortress {
+    fn name(&self) -> &'static str {
+        "fortress"
+    }
+
+    fn place_village(
+        &self,
+        generator: &LevelGenerator,
+        rng: &mut RandomNumberGenerator,
+        houses: u8,
+        grid: &mut [Vec<Option<LevelItem>>],
+    ) {
+        generator.place_houses(rng, houses, grid);
+        generator.place_farms(rng, houses, grid);
+
+        // Find the bounds of the village, one tile out (clamped to the map).
+        let (mut left, mut top, mut right, mut bottom) = (usize::MAX, usize::MAX, 0, 0);
+        for (y, row) in grid.iter().enumerate() {
+            for (x, item) in row.iter().enumerate() {
+                if item.is_some() {
+                    left = left.min(x.saturating_sub(1));
+                    top = top.min(y.saturating_sub(1));
+                    right = right.max((x + 1).min(row.len() - 1));
+                    bottom = bottom.max((y + 1).min(grid.len() - 1));
+                }
+            }
+        }
+
+        // Wall in the open tiles along the bounds, except for a random gate.
+        let mut ring = Vec::new();
+        for (y, row) in grid.iter().enumerate().take(bottom + 1).skip(top) {
+            for (x, item) in row.iter().enumerate().take(right + 1).skip(left) {
+                let on_edge = x == left || x == right || y == top || y == bottom;
+                if on_edge && item.is_none() {
+                    ring.push((x, y));
+                }
+            }
+        }
+        if ring.is_empty() {
+            return;
+        }
+        let gate = rng.range(0, ring.len());
+        for (i, (x, y)) in ring.into_iter().enumerate() {
+            if i != gate {
+                grid[y][x] = Some(LevelItem::Wall);
+            }
+        }
+    }
+}
+
+/// Houses spread out across the map, each with a farm and a single wall.
+pub struct Hamlets;
+
+impl LevelStrategy for Hamlets {
+    fn name(&self) -> &'static str {
+        "hamlets"
+    }
+
+    fn place_village(
+        &self,
+        generator: &LevelGenerator,
+        rng: &mut RandomNumberGenerator,
+        houses: u8,
+        grid: &mut [Vec<Option<LevelItem>>],
+    ) {
+        // Start like any other village, then put every other house as far away as we can find.
+        generator.place_houses(rng, 1, grid);
+
+        for _ in 1..houses {
+            let mut placed = Vec::new();
+            for (y, row) in grid.iter().enumerate() {
+                for (x, item) in row.iter().enumerate() {
+                    if *item == Some(LevelItem::House) {
+                        placed.push((x, y));
+                    }
+                }
+            }
+
+            let best = (0..HAMLET_CANDIDATES)
+                .filter_map(|_| generator.find_any_open_position(rng, grid))
+                .max_by_key(|(x, y)| {
+                    placed
+                        .iter()
+                        .map(|(hx, hy)| hx.abs_diff(*x).max(hy.abs_diff(*y)))
+                        .min()
+                        .unwrap_or(0)
+                });
+            match best {
+                Some((x, y)) => grid[y][x] = Some(LevelItem::House),
+                None => break,
+            }
+        }
+
+        generator.place_farms(rng, houses, grid);
+        generator.place_walls(rng, houses, grid);
+    }
+
+    fn terrain(&self, parameters: TerrainParameters) -> TerrainParameters {
+        // Each hamlet only clears the land right next to it.
+        TerrainParameters {
+            clearing_radius: 1,
+            ..parameters
+        }
+    }
+}
+
+/// A village split by a river, with a single ford.
+pub struct RiverCrossing;
+
+impl LevelStrategy for RiverCrossing {
+    fn name(&self) -> &'static str {
+        "river-crossing"
+    }
+
+    fn place_village(
+        &self,
+        generator: &LevelGenerator,
+        rng: &mut RandomNumberGenerator,
+        houses: u8,
+        grid: &mut [Vec<Option<LevelItem>>],
+    ) {
+        Village.place_village(generator, rng, houses, grid);
+    }
+
+    fn terrain(&self, parameters: TerrainParameters) -> TerrainParameters {
+        TerrainParameters {
+            rivers: parameters.rivers.max(1),
+            fords_per_river: 1,
+            clearing_radius: 0,
+            river_through_village: true,
+            ..parameters
+        }
+    }
+}
+
+/// A village on open grassland, with no rivers or rocks and only a few trees.
+pub struct OpenField;
+
+impl LevelStrategy for OpenField {
+    fn name(&self) -> &'static str {
+        "open-field"
+    }
+
+    fn place_village(
+        &self,
+        generator: &LevelGenerator,
+        rng: &mut RandomNumberGenerator,
+        houses: u8,
+        grid: &mut [Vec<Option<LevelItem>>],
+    ) {
+        Village.place_village(generator, rng, houses, grid);
+    }
+
+    fn terrain(&self, parameters: TerrainParameters) -> TerrainParameters {
+        TerrainParameters {
+            rivers: 0,
+            rock_density: 0.0,
+            ..parameters
+        }
+    }
+
+    fn density(&self, density: f32) -> f32 {
+        density / 4.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strategies_are_found_by_name() {
+        for strategy in all() {
+            assert_eq!(by_name(strategy.name()).unwrap().name(), strategy.name());
+        }
+        assert!(by_name("castle").is_none());
+    }
+
+    #[test]
+    fn every_strategy_generates_playable_levels() {
+        for strategy in all() {
+            for (width, height) in [(6, 6), (12, 12), (20, 8), (8, 20), (40, 30)] {
+                for seed in 0..100 {
+                    let mut rng = RandomNumberGenerator::seeded(seed);
+                    let mut generator = LevelGenerator::new(width, height)
+                        .with_strategy(by_name(strategy.name()).unwrap());
+                    if let Err(e) = generator.generate(&mut rng, 2, 0.15) {
+                        panic!(
+                            "{} {}x{} seed {}: {}",
+                            strategy.name(),
+                            width,
+                            height,
+                            seed,
+                            e
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fortresses_are_walled_in() {
+        let mut rng = RandomNumberGenerator::seeded(0);
+        let mut generator = LevelGenerator::new(20, 20).with_strategy(Box::new(Fortress));
+        let level = generator.generate(&mut rng, 2, 0.0).unwrap();
+        let walls = level
+            .items
+            .iter()
+            .filter(|i| i.item == LevelItem::Wall)
+            .count();
+
+        // The smallest possible ring around two houses and two farms is much larger than the
+        // two walls per house of a plain village.
+        assert!(walls > 8, "only {} walls", walls);
+    }
+}
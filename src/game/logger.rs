@@ -3,7 +3,7 @@
 use super::Glyph;
 
 /// A message to be displayed in the log.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum LogMessage {
     /// Something was attacked.
     Attacked {
@@ -19,6 +19,120 @@ pub enum LogMessage {
         /// Whether the target was defeated by the attack.
         defeated: bool,
     },
+
+    /// Something was shot at from a distance.
+    Shot {
+        /// Who shot.
+        attacker: Glyph,
+
+        /// What was hit.
+        target: Glyph,
+
+        /// Where the shot was fired from.
+        position: (i32, i32),
+
+        /// Whether the target was defeated by the shot.
+        defeated: bool,
+    },
+
+    /// A boss joined the fight.
+    BossArrived {
+        /// Which boss it is.
+        boss: Glyph,
+    },
+
+    /// A boss brought down a smash.
+    Smashed {
+        /// Who smashed.
+        attacker: Glyph,
+
+        /// The center of the area that was hit.
+        position: (i32, i32),
+
+        /// How many things were hit.
+        hits: u8,
+    },
+
+    /// A boss summoned minions.
+    Summoned {
+        /// Where the boss is.
+        position: (i32, i32),
+
+        /// How many minions came.
+        count: u8,
+    },
+
+    /// A thief stole the crops of a farm.
+    Stole {
+        /// Where the farm is.
+        position: (i32, i32),
+    },
+
+    /// A thief left the map.
+    Escaped {
+        /// Where it left from.
+        position: (i32, i32),
+
+        /// Whether it got away with stolen crops.
+        stolen: bool,
+    },
+
+    /// The player picked up crops a thief dropped, earning their worth.
+    PickedUp {
+        /// Where the crops were.
+        position: (i32, i32),
+
+        /// How much food they were worth.
+        food: u8,
+    },
+
+    /// An attack was ignored thanks to a defense aura.
+    Blocked {
+        /// Who attacked.
+        attacker: Glyph,
+
+        /// What was attacked.
+        target: Glyph,
+
+        /// Where the attack came from.
+        position: (i32, i32),
+    },
+
+    /// Something split into copies of itself when defeated.
+    Split {
+        /// What split.
+        glyph: Glyph,
+
+        /// Where it was defeated.
+        position: (i32, i32),
+
+        /// How many copies fit around it.
+        count: u8,
+    },
+
+    /// The player harvested ripe crops.
+    Harvested {
+        /// Where the farm is.
+        position: (i32, i32),
+
+        /// How much food they were worth.
+        food: u8,
+    },
+
+    /// A monster trampled (or ate) the crops of a farm.
+    Trampled {
+        /// Who trampled them.
+        attacker: Glyph,
+
+        /// Where the farm is.
+        position: (i32, i32),
+    },
+
+    /// A villager ran into its home to hide.
+    Sheltered {
+        /// Where the house is.
+        position: (i32, i32),
+    },
 }
 
 /// A singleton that stores logs of events.
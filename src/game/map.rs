@@ -4,17 +4,179 @@ use std::num::NonZeroU8;
 
 use specs::prelude::*;
 
-use super::components::{Moving, Position, Renderable};
+use super::components::{Blocks, Flying, Moving, Position, Renderable};
+use super::resources::Resources;
+use super::Glyph;
+
+/// Which tiles count as next to each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Neighborhood {
+    /// Up, down, left, and right.
+    Four,
+
+    /// Up, down, left, right, and the diagonals.
+    Eight,
+}
+
+impl Neighborhood {
+    /// Returns the (x, y) offsets of every neighbor.
+    #[must_use]
+    pub fn offsets(&self) -> &'static [(i32, i32)] {
+        match self {
+            Neighborhood::Four => &[(0, -1), (0, 1), (-1, 0), (1, 0)],
+            Neighborhood::Eight => &[
+                (0, -1),
+                (0, 1),
+                (-1, 0),
+                (1, 0),
+                (-1, -1),
+                (1, -1),
+                (-1, 1),
+                (1, 1),
+            ],
+        }
+    }
+
+    /// Returns every direction that can be moved in.
+    #[must_use]
+    pub fn directions(&self) -> &'static [Moving] {
+        match self {
+            Neighborhood::Four => &Moving::CARDINAL,
+            Neighborhood::Eight => &Moving::ALL,
+        }
+    }
+
+    /// Whether two positions are next to each other (i.e. a single step apart).
+    #[must_use]
+    pub fn is_adjacent(&self, a: &Position, b: &Position) -> bool {
+        let (dx, dy) = ((a.x - b.x).abs(), (a.y - b.y).abs());
+        match self {
+            Neighborhood::Four => dx + dy == 1,
+            Neighborhood::Eight => dx.max(dy) == 1,
+        }
+    }
+}
+
+/// Which layer an entity moves in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Layer {
+    /// Walking: stopped by impassable terrain and anything occupying a tile.
+    Ground,
+
+    /// Flying (see [`Flying`]): over any terrain, and over walls and trees.
+    Air,
+}
+
+impl Layer {
+    /// Returns the layer of something that flies, or not.
+    #[must_use]
+    pub fn of(flies: bool) -> Layer {
+        if flies {
+            Layer::Air
+        } else {
+            Layer::Ground
+        }
+    }
+}
+
+/// The ground underneath entities.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Terrain {
+    Grass,
+    Dirt,
+    Water,
+    Rock,
+    Road,
+
+    /// Fertile soil, the only terrain farms can be built on.
+    Soil,
+
+    /// Shallow water that can be waded through, but not built on.
+    Ford,
+}
+
+impl Terrain {
+    /// Every terrain, in declaration order.
+    pub const ALL: [Terrain; 7] = [
+        Terrain::Grass,
+        Terrain::Dirt,
+        Terrain::Water,
+        Terrain::Rock,
+        Terrain::Road,
+        Terrain::Soil,
+        Terrain::Ford,
+    ];
+
+    /// Returns the cost of walking onto this terrain, or `None` if it cannot be walked on.
+    ///
+    /// Costs are relative, i.e. roads are preferred by pathfinding and soil is avoided.
+    #[must_use]
+    pub fn movement_cost(&self) -> Option<u32> {
+        match self {
+            Terrain::Road => Some(1),
+            Terrain::Grass | Terrain::Dirt => Some(2),
+            Terrain::Soil | Terrain::Ford => Some(3),
+            Terrain::Water | Terrain::Rock => None,
+        }
+    }
+
+    /// Whether this terrain can be walked on.
+    #[must_use]
+    pub fn is_passable(&self) -> bool {
+        self.movement_cost().is_some()
+    }
+
+    /// Whether the given structure can be built on this terrain.
+    #[must_use]
+    pub fn can_build(&self, what: Glyph) -> bool {
+        match what {
+            Glyph::Farm => *self == Terrain::Soil,
+            _ => self.is_passable() && *self != Terrain::Ford,
+        }
+    }
+
+    /// Returns the ASCII symbol used to write the terrain in a board.
+    #[must_use]
+    pub fn symbol(&self) -> char {
+        match self {
+            Terrain::Grass => ',',
+            Terrain::Dirt => ':',
+            Terrain::Water => '~',
+            Terrain::Rock => '^',
+            Terrain::Road => '=',
+            Terrain::Soil => '%',
+            Terrain::Ford => '_',
+        }
+    }
+
+    /// Returns the terrain written with the given ASCII symbol, if any.
+    #[must_use]
+    pub fn from_symbol(symbol: char) -> Option<Terrain> {
+        Terrain::ALL.into_iter().find(|t| t.symbol() == symbol)
+    }
+}
 
 pub struct Map {
-    /// A 2D vector of entities with positions on the map.
+    /// A 2D vector of the entity blocking each tile, if any.
     entities: Vec<Option<Entity>>,
 
+    /// A 2D vector of the non-blocking entities on each tile (e.g. items on the ground).
+    items: Vec<Vec<Entity>>,
+
+    /// A 2D vector of the flying entity above each tile, if any.
+    fliers: Vec<Option<Entity>>,
+
+    /// A 2D vector of whether each tile is occupied by something low enough to fly over.
+    low: Vec<bool>,
+
+    /// A 2D vector of the terrain underneath entities.
+    terrain: Vec<Terrain>,
+
     /// Round number, starting at 1.
     round: NonZeroU8,
 
-    /// Amount of $ for the player.
-    pub money: u8,
+    /// Resources in store for the player.
+    pub resources: Resources,
 
     /// The width of the map.
     width: usize,
@@ -27,6 +189,9 @@ pub struct Map {
 
     /// The game system will block the player from attacking a friendly, once per move.
     previous_blocked_move: Option<Moving>,
+
+    /// Which directions can be moved in (four by default, or eight with diagonals).
+    movement: Neighborhood,
 }
 
 impl Map {
@@ -40,15 +205,34 @@ impl Map {
 
         Self {
             entities: vec![None; width * height],
+            items: vec![Vec::new(); width * height],
+            fliers: vec![None; width * height],
+            low: vec![false; width * height],
+            terrain: vec![Terrain::Grass; width * height],
             round: NonZeroU8::new(1).unwrap(),
-            money: 0,
+            resources: Resources::default(),
             width,
             farms: 0,
             houses: 0,
             previous_blocked_move: None,
+            movement: Neighborhood::Four,
         }
     }
 
+    /// Create a new empty map with the given terrain rows.
+    ///
+    /// # Panics
+    ///
+    /// If there are no rows, or the rows are empty or not all the same length.
+    pub fn with_terrain(terrain: Vec<Vec<Terrain>>) -> Self {
+        let width = terrain.first().map_or(0, |row| row.len());
+        assert!(terrain.iter().all(|row| row.len() == width));
+
+        let mut map = Self::new(width, terrain.len());
+        map.terrain = terrain.into_iter().flatten().collect();
+        map
+    }
+
     /// Check if the player can move in the given direction.
     pub fn allow_move_into_friendly(&mut self, moving: Moving) -> bool {
         if let Some(previous) = &self.previous_blocked_move {
@@ -66,6 +250,11 @@ impl Map {
         for entity in self.entities.iter_mut() {
             *entity = None;
         }
+        for items in self.items.iter_mut() {
+            items.clear();
+        }
+        self.fliers.fill(None);
+        self.low.fill(false);
     }
 
     /// Check if a coordinate is within the bounds of the map.
@@ -74,14 +263,60 @@ impl Map {
         x >= 0 && x < self.width() as i32 && y >= 0 && y < self.height() as i32
     }
 
-    /// Set the entity at the given coordinate.
-    pub fn set_entity(&mut self, x: i32, y: i32, entity: Entity) {
+    /// Index an entity at the given coordinate.
+    ///
+    /// A blocking entity occupies the tile, unless it is already occupied: then it is stacked with
+    /// the non-blocking entities instead, so that nothing is lost.
+    ///
+    /// # Panics
+    ///
+    /// If the coordinate is out of bounds.
+    pub fn set_entity(&mut self, x: i32, y: i32, entity: Entity, blocks: bool) {
         // If out of bounds, panic.
         assert!(self.in_bounds(x, y), "x: {}, y: {}", x, y);
-        self.entities[(y as usize * self.width) + x as usize] = Some(entity);
+        let index = (y as usize * self.width) + x as usize;
+        if blocks && self.entities[index].is_none() {
+            self.entities[index] = Some(entity);
+        } else {
+            self.items[index].push(entity);
+        }
+    }
+
+    /// Index a flying entity at the given coordinate, above whatever occupies the tile.
+    ///
+    /// # Panics
+    ///
+    /// If the coordinate is out of bounds.
+    pub fn set_flier(&mut self, x: i32, y: i32, entity: Entity) {
+        assert!(self.in_bounds(x, y), "x: {}, y: {}", x, y);
+        let index = (y as usize * self.width) + x as usize;
+        if self.fliers[index].is_none() {
+            self.fliers[index] = Some(entity);
+        } else {
+            self.items[index].push(entity);
+        }
+    }
+
+    /// Mark whatever occupies the given coordinate as low enough to fly over (walls and trees).
+    ///
+    /// # Panics
+    ///
+    /// If the coordinate is out of bounds.
+    pub fn set_low(&mut self, x: i32, y: i32) {
+        assert!(self.in_bounds(x, y), "x: {}, y: {}", x, y);
+        self.low[(y as usize * self.width) + x as usize] = true;
     }
 
-    /// Get the entity at the given coordinate.
+    /// Get the non-blocking entities at the given coordinate.
+    #[must_use]
+    pub fn get_items(&self, x: i32, y: i32) -> &[Entity] {
+        if !self.in_bounds(x, y) {
+            return &[];
+        }
+        &self.items[(y as usize * self.width) + x as usize]
+    }
+
+    /// Get the entity blocking the given coordinate.
     #[must_use]
     pub fn get_entity(&self, x: i32, y: i32) -> Option<Entity> {
         // If out of bounds, return None.
@@ -91,6 +326,102 @@ impl Map {
         self.entities[(y as usize * self.width) + x as usize]
     }
 
+    /// Get the entity blocking the given coordinate, then the flier above it, if any.
+    fn occupants(&self, x: i32, y: i32) -> impl Iterator<Item = Entity> + '_ {
+        let index = self
+            .in_bounds(x, y)
+            .then(|| (y as usize * self.width) + x as usize);
+        index
+            .into_iter()
+            .flat_map(|index| self.entities[index].into_iter().chain(self.fliers[index]))
+    }
+
+    /// Get the entity that stops something moving in `layer` from entering the given coordinate
+    /// (and so what it would attack instead).
+    ///
+    /// Fliers are in the way of both layers, as they can be swatted from the ground. Whatever
+    /// occupies the tile is in the way of fliers too, unless it is low enough to fly over.
+    #[must_use]
+    pub fn get_blocker(&self, x: i32, y: i32, layer: Layer) -> Option<Entity> {
+        if !self.in_bounds(x, y) {
+            return None;
+        }
+        let index = (y as usize * self.width) + x as usize;
+        match layer {
+            Layer::Ground => self.fliers[index].or(self.entities[index]),
+            Layer::Air if self.low[index] => self.fliers[index],
+            Layer::Air => self.fliers[index].or(self.entities[index]),
+        }
+    }
+
+    /// Get the terrain at the given coordinate.
+    ///
+    /// Out of bounds coordinates are treated as impassable rock.
+    #[must_use]
+    pub fn get_terrain(&self, x: i32, y: i32) -> Terrain {
+        if !self.in_bounds(x, y) {
+            return Terrain::Rock;
+        }
+        self.terrain[(y as usize * self.width) + x as usize]
+    }
+
+    /// Whether the given coordinate is in bounds and can be walked on.
+    #[must_use]
+    pub fn is_passable(&self, x: i32, y: i32) -> bool {
+        self.get_terrain(x, y).is_passable()
+    }
+
+    /// Whether a single step in a direction can be taken, ignoring entities.
+    ///
+    /// Diagonal steps are only allowed with eight-directional movement, and cannot squeeze
+    /// between two impassable tiles.
+    #[must_use]
+    pub fn can_step(&self, from: &Position, direction: &Moving) -> bool {
+        if !self.movement.directions().contains(direction) {
+            return false;
+        }
+        let (dx, dy) = direction.delta();
+        if !self.is_passable(from.x + dx, from.y + dy) {
+            return false;
+        }
+        !direction.is_diagonal()
+            || self.is_passable(from.x + dx, from.y)
+            || self.is_passable(from.x, from.y + dy)
+    }
+
+    /// Whether a single step in a direction can be taken in a layer, ignoring entities.
+    ///
+    /// Fliers only need to stay within the map (see [`Map::can_step`] for walking).
+    #[must_use]
+    pub fn can_step_in(&self, from: &Position, direction: &Moving, layer: Layer) -> bool {
+        match layer {
+            Layer::Ground => self.can_step(from, direction),
+            Layer::Air => {
+                let to = from.after(direction);
+                self.movement.directions().contains(direction) && self.in_bounds(to.x, to.y)
+            }
+        }
+    }
+
+    /// Returns which directions can be moved in.
+    #[must_use]
+    pub fn movement(&self) -> Neighborhood {
+        self.movement
+    }
+
+    /// Sets which directions can be moved in.
+    pub fn set_movement(&mut self, movement: Neighborhood) {
+        self.movement = movement;
+    }
+
+    /// Returns the terrain as rows, e.g. to be drawn.
+    pub fn terrain_rows(&self) -> Vec<Vec<Terrain>> {
+        self.terrain
+            .chunks(self.width)
+            .map(|r| r.to_vec())
+            .collect()
+    }
+
     /// Return the width of the map.
     pub fn width(&self) -> usize {
         self.width
@@ -101,6 +432,140 @@ impl Map {
         self.entities.len() / self.width
     }
 
+    /// Returns the in-bounds positions next to the given one.
+    #[must_use]
+    pub fn neighbors(&self, of: &Position, neighborhood: Neighborhood) -> Vec<Position> {
+        neighborhood
+            .offsets()
+            .iter()
+            .map(|(dx, dy)| Position::new(of.x + dx, of.y + dy))
+            .filter(|p| self.in_bounds(p.x, p.y))
+            .collect()
+    }
+
+    /// Returns the in-bounds positions exactly `distance` steps (including diagonals) away from
+    /// the given one, i.e. a square ring around it.
+    ///
+    /// A distance of 0 is the position itself.
+    #[must_use]
+    pub fn ring(&self, center: &Position, distance: usize) -> Vec<Position> {
+        let d = distance as i32;
+        self.rectangle(center.x - d, center.y - d, center.x + d, center.y + d)
+            .into_iter()
+            .filter(|p| self.in_bounds(p.x, p.y))
+            .collect()
+    }
+
+    /// Returns the positions exactly `inset` tiles away from the edge of the map, i.e. a ring
+    /// that shrinks towards the center.
+    ///
+    /// Returns nothing once the ring would be smaller than a single tile.
+    #[must_use]
+    pub fn ring_from_edge(&self, inset: usize) -> Vec<Position> {
+        if inset * 2 >= self.width() || inset * 2 >= self.height() {
+            return Vec::new();
+        }
+        let (left, top) = (inset as i32, inset as i32);
+        let right = (self.width() - inset - 1) as i32;
+        let bottom = (self.height() - inset - 1) as i32;
+        self.rectangle(left, top, right, bottom)
+    }
+
+    /// Returns the outline of a rectangle (inclusive), each position once: the top and bottom
+    /// rows, then the left and right columns.
+    fn rectangle(&self, left: i32, top: i32, right: i32, bottom: i32) -> Vec<Position> {
+        let mut outline = Vec::new();
+        for x in left..=right {
+            outline.push(Position::new(x, top));
+        }
+        if bottom != top {
+            for x in left..=right {
+                outline.push(Position::new(x, bottom));
+            }
+        }
+        for y in (top + 1)..bottom {
+            outline.push(Position::new(left, y));
+            if right != left {
+                outline.push(Position::new(right, y));
+            }
+        }
+        outline
+    }
+
+    /// Returns the indexed blocking entities and fliers within `radius` (straight-line distance)
+    /// of a position, closest rings first.
+    #[must_use]
+    pub fn entities_within(&self, center: &Position, radius: f64) -> Vec<(Entity, Position)> {
+        let mut found = Vec::new();
+        for distance in 0..=(radius.max(0.0).ceil() as usize) {
+            for position in self.ring(center, distance) {
+                if center.distance(&position) > radius {
+                    continue;
+                }
+                for entity in self.occupants(position.x, position.y) {
+                    found.push((entity, position.clone()));
+                }
+            }
+        }
+        found
+    }
+
+    /// Returns the indexed blocking entity or flier closest (in a straight line) to a position that
+    /// matches the predicate, if any.
+    ///
+    /// Ties are broken by the order of [`Map::ring`], then blocking entities first.
+    pub fn nearest_entity(
+        &self,
+        center: &Position,
+        predicate: impl Fn(Entity) -> bool,
+    ) -> Option<(Entity, Position)> {
+        let mut nearest: Option<(f64, Entity, Position)> = None;
+        let farthest = self.width().max(self.height());
+
+        for distance in 0..farthest {
+            // Anything in this ring (or further out) is at least `distance` away.
+            if matches!(nearest, Some((d, _, _)) if d < distance as f64) {
+                break;
+            }
+            for position in self.ring(center, distance) {
+                let d = center.distance(&position);
+                for entity in self.occupants(position.x, position.y) {
+                    if !matches!(nearest, Some((n, _, _)) if n <= d) && predicate(entity) {
+                        nearest = Some((d, entity, position.clone()));
+                    }
+                }
+            }
+        }
+
+        nearest.map(|(_, entity, position)| (entity, position))
+    }
+
+    /// Returns the positions on a straight line between two positions (inclusive), from `from`
+    /// to `to`.
+    #[must_use]
+    pub fn line(&self, from: &Position, to: &Position) -> Vec<Position> {
+        // Bresenham's line algorithm.
+        let (dx, dy) = ((to.x - from.x).abs(), -(to.y - from.y).abs());
+        let (sx, sy) = ((to.x - from.x).signum(), (to.y - from.y).signum());
+        let mut error = dx + dy;
+        let (mut x, mut y) = (from.x, from.y);
+
+        let mut line = vec![Position::new(x, y)];
+        while (x, y) != (to.x, to.y) {
+            let e2 = 2 * error;
+            if e2 >= dy {
+                error += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                error += dx;
+                y += sy;
+            }
+            line.push(Position::new(x, y));
+        }
+        line
+    }
+
     /// Returns the round number.
     #[must_use]
     pub fn round(&self) -> NonZeroU8 {
@@ -122,29 +587,237 @@ impl<'a> System<'a> for MapIndexingSystem {
         Entities<'a>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, Renderable>,
+        ReadStorage<'a, Blocks>,
+        ReadStorage<'a, Flying>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut map, entities, positions, render) = data;
+        let (mut map, entities, positions, render, blocks, flying) = data;
 
         // Clear the map.
         map.clear();
 
-        let mut total_farms = 0;
-        let mut total_houses = 0;
+        let mut total_farms = 0_usize;
+        let mut total_houses = 0_usize;
 
         // Iterate over all entities with positions and index them on the map.
-        for (entity, position, render) in (&entities, &positions, &render).join() {
-            map.set_entity(position.x, position.y, entity);
+        for (entity, position, render, blocks, flying) in (
+            &entities,
+            &positions,
+            &render,
+            blocks.maybe(),
+            flying.maybe(),
+        )
+            .join()
+        {
+            if flying.is_some() {
+                map.set_flier(position.x, position.y, entity);
+            } else {
+                map.set_entity(position.x, position.y, entity, blocks.is_some());
+            }
 
             match render.glyph() {
                 super::Glyph::Farm => total_farms += 1,
                 super::Glyph::House => total_houses += 1,
+                super::Glyph::Wall | super::Glyph::Tree
+                    if map.get_entity(position.x, position.y) == Some(entity) =>
+                {
+                    map.set_low(position.x, position.y)
+                }
                 _ => (),
             };
         }
 
-        map.farms = total_farms;
-        map.houses = total_houses;
+        map.farms = u8::try_from(total_farms).unwrap_or(u8::MAX);
+        map.houses = u8::try_from(total_houses).unwrap_or(u8::MAX);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use specs::prelude::*;
+
+    use super::{Layer, Map, Neighborhood};
+    use crate::game::components::Position;
+
+    /// Returns a map with an entity indexed at each of the given positions.
+    fn map_with(width: usize, height: usize, at: &[(i32, i32)]) -> (Map, Vec<Entity>) {
+        let mut world = World::new();
+        let mut map = Map::new(width, height);
+        let mut entities = Vec::new();
+        for (x, y) in at {
+            let entity = world.create_entity().build();
+            map.set_entity(*x, *y, entity, true);
+            entities.push(entity);
+        }
+        (map, entities)
+    }
+
+    #[test]
+    fn layers_are_blocked_by_different_things() {
+        let (mut map, entities) = map_with(3, 1, &[(0, 0), (1, 0)]);
+        let mut world = World::new();
+        let bat = world.create_entity().build();
+        map.set_low(1, 0);
+        map.set_flier(2, 0, bat);
+
+        // Fliers pass over low entities (walls and trees), but nothing passes through a flier.
+        assert_eq!(map.get_blocker(0, 0, Layer::Air), Some(entities[0]));
+        assert_eq!(map.get_blocker(1, 0, Layer::Air), None);
+        assert_eq!(map.get_blocker(1, 0, Layer::Ground), Some(entities[1]));
+        assert_eq!(map.get_blocker(2, 0, Layer::Ground), Some(bat));
+        assert_eq!(map.get_entity(2, 0), None);
+    }
+
+    fn positions(list: &[(i32, i32)]) -> Vec<Position> {
+        list.iter().map(|(x, y)| Position::new(*x, *y)).collect()
+    }
+
+    #[test]
+    fn neighbors_stay_in_bounds() {
+        let map = Map::new(3, 3);
+        let corner = Position::new(0, 0);
+
+        assert_eq!(
+            map.neighbors(&corner, Neighborhood::Four),
+            positions(&[(0, 1), (1, 0)])
+        );
+        assert_eq!(
+            map.neighbors(&corner, Neighborhood::Eight),
+            positions(&[(0, 1), (1, 0), (1, 1)])
+        );
+        assert_eq!(
+            map.neighbors(&Position::new(1, 1), Neighborhood::Eight)
+                .len(),
+            8
+        );
+    }
+
+    #[test]
+    fn adjacency_depends_on_the_neighborhood() {
+        let (a, b) = (Position::new(2, 2), Position::new(3, 3));
+        assert!(!Neighborhood::Four.is_adjacent(&a, &b));
+        assert!(Neighborhood::Eight.is_adjacent(&a, &b));
+        assert!(Neighborhood::Four.is_adjacent(&a, &Position::new(2, 1)));
+        assert!(!Neighborhood::Eight.is_adjacent(&a, &a));
+        assert!(!Neighborhood::Eight.is_adjacent(&a, &Position::new(4, 2)));
+    }
+
+    #[test]
+    fn rings_are_square_and_clipped() {
+        let map = Map::new(5, 5);
+
+        assert_eq!(map.ring(&Position::new(2, 2), 0), positions(&[(2, 2)]));
+        assert_eq!(map.ring(&Position::new(2, 2), 1).len(), 8);
+        assert_eq!(map.ring(&Position::new(2, 2), 2).len(), 16);
+        assert_eq!(
+            map.ring(&Position::new(0, 0), 1),
+            positions(&[(0, 1), (1, 1), (1, 0)])
+        );
+        assert!(map.ring(&Position::new(0, 0), 5).is_empty());
+    }
+
+    #[test]
+    fn rings_from_edge_shrink_to_the_center() {
+        let map = Map::new(4, 3);
+
+        assert_eq!(map.ring_from_edge(0).len(), 10);
+        assert_eq!(map.ring_from_edge(1), positions(&[(1, 1), (2, 1)]));
+        assert!(map.ring_from_edge(2).is_empty());
+    }
+
+    #[test]
+    fn entities_within_a_radius() {
+        let (map, entities) = map_with(5, 5, &[(2, 2), (3, 3), (4, 2), (0, 0)]);
+        let found: Vec<Entity> = map
+            .entities_within(&Position::new(2, 2), 2.0)
+            .into_iter()
+            .map(|(e, _)| e)
+            .collect();
+
+        // (3, 3) is ~1.41 away, (4, 2) is exactly 2, and (0, 0) is ~2.83.
+        assert_eq!(found, vec![entities[0], entities[1], entities[2]]);
+    }
+
+    #[test]
+    fn nearest_entity_matching_a_predicate() {
+        let (map, entities) = map_with(6, 6, &[(0, 0), (4, 2), (3, 5)]);
+        let center = Position::new(3, 3);
+
+        let nearest = map.nearest_entity(&center, |_| true);
+        assert_eq!(nearest, Some((entities[1], Position::new(4, 2))));
+
+        let nearest = map.nearest_entity(&center, |e| e != entities[1]);
+        assert_eq!(nearest, Some((entities[2], Position::new(3, 5))));
+
+        assert_eq!(map.nearest_entity(&center, |_| false), None);
+    }
+
+    #[test]
+    fn fliers_are_found_too() {
+        let mut world = World::new();
+        let mut map = Map::new(5, 5);
+        let entities: Vec<Entity> = (0..2).map(|_| world.create_entity().build()).collect();
+        let bat = world.create_entity().build();
+        map.set_entity(2, 2, entities[0], true);
+        map.set_entity(4, 4, entities[1], true);
+        map.set_flier(2, 2, bat);
+
+        let found: Vec<Entity> = map
+            .entities_within(&Position::new(2, 2), 1.0)
+            .into_iter()
+            .map(|(e, _)| e)
+            .collect();
+        assert_eq!(found, vec![entities[0], bat]);
+
+        let nearest = map.nearest_entity(&Position::new(3, 3), |e| e != entities[1]);
+        assert_eq!(nearest, Some((entities[0], Position::new(2, 2))));
+        let nearest = map.nearest_entity(&Position::new(3, 3), |e| e == bat);
+        assert_eq!(nearest, Some((bat, Position::new(2, 2))));
+    }
+
+    #[test]
+    fn nearest_entity_is_not_just_the_nearest_ring() {
+        // (3, 3) is in an earlier ring, but (4, 0) is closer in a straight line.
+        let (map, entities) = map_with(6, 6, &[(3, 3), (4, 0)]);
+        let nearest = map.nearest_entity(&Position::new(0, 0), |_| true);
+        assert_eq!(nearest, Some((entities[1], Position::new(4, 0))));
+    }
+
+    #[test]
+    fn lines_include_both_ends() {
+        let map = Map::new(5, 5);
+
+        assert_eq!(
+            map.line(&Position::new(0, 0), &Position::new(3, 1)),
+            positions(&[(0, 0), (1, 0), (2, 1), (3, 1)])
+        );
+        assert_eq!(
+            map.line(&Position::new(2, 4), &Position::new(2, 2)),
+            positions(&[(2, 4), (2, 3), (2, 2)])
+        );
+        assert_eq!(
+            map.line(&Position::new(1, 1), &Position::new(1, 1)),
+            positions(&[(1, 1)])
+        );
+    }
+
+    #[test]
+    fn tiles_hold_one_blocker_and_any_items() {
+        let mut world = World::new();
+        let mut map = Map::new(2, 1);
+        let [item, blocker, extra] = [(); 3].map(|_| world.create_entity().build());
+
+        map.set_entity(0, 0, item, false);
+        map.set_entity(0, 0, blocker, true);
+        map.set_entity(0, 0, extra, true);
+
+        // The second blocking entity is stacked rather than overwriting the first.
+        assert_eq!(map.get_entity(0, 0), Some(blocker));
+        assert_eq!(map.get_items(0, 0), &[item, extra]);
+
+        map.clear();
+        assert_eq!(map.get_entity(0, 0), None);
+        assert!(map.get_items(0, 0).is_empty());
     }
 }
@@ -4,33 +4,38 @@
 
 use specs::prelude::*;
 
-use super::components::{Moving, Position};
-use super::map::Map;
+use super::components::{Flying, Moving, Position};
+use super::map::{Layer, Map};
 
 pub struct MovementSystem;
 
 impl<'a> System<'a> for MovementSystem {
     type SystemData = (
         ReadExpect<'a, Map>,
+        ReadStorage<'a, Flying>,
         WriteStorage<'a, Position>,
         WriteStorage<'a, Moving>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         // Unpack the system data.
-        let (map, mut positions, mut moving) = data;
+        let (map, flying, mut positions, mut moving) = data;
 
         // Iterate over all entities that have a position and are moving.
-        for (position, direction) in (&mut positions, &mut moving).join() {
+        for (position, direction, flying) in (&mut positions, &mut moving, flying.maybe()).join() {
             let prospective = position.after(direction);
+            let layer = Layer::of(flying.is_some());
 
             // If there would be an overlap with another entity, do not move.
-            if map.get_entity(prospective.x, prospective.y).is_some() {
+            if map
+                .get_blocker(prospective.x, prospective.y, layer)
+                .is_some()
+            {
                 continue;
             }
 
-            // If the entity is within the bounds of the map, update its position.
-            if map.in_bounds(prospective.x, prospective.y) {
+            // If the step stays within the map (and not e.g. into water), update its position.
+            if map.can_step_in(position, direction, layer) {
                 *position = prospective;
             }
         }
@@ -39,3 +44,229 @@ impl<'a> System<'a> for MovementSystem {
         moving.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{
+        scenario::{InputResult, Scenario},
+        Direction, MovementDenied,
+    };
+
+    #[test]
+    fn player_moves_into_empty_tile() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . .
+            .  . . . .
+            @5 . . . g
+            ",
+        )
+        .moves(&[Direction::Up])
+        .run();
+
+        outcome.assert_board(
+            "
+            h2 . . . .
+            @5 . . . .
+            .  . . g .
+            ",
+        );
+    }
+
+    #[test]
+    fn player_walks_over_items() {
+        let outcome = Scenario::new(
+            "
+            h2 .    . . . .
+            @5 +t+t . . . g
+            ",
+        )
+        .moves(&[Direction::Right])
+        .run();
+
+        outcome.assert_board(
+            "
+            h2 .       . . . .
+            .  @5+t+t  . . g .
+            ",
+        );
+    }
+
+    #[test]
+    fn player_cannot_move_out_of_bounds() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . .
+            .  . . . .
+            @5 . . . g
+            ",
+        )
+        .moves(&[Direction::Left])
+        .run();
+
+        assert!(matches!(
+            outcome.results[..],
+            [InputResult::Moved(Err(MovementDenied::Impassable))]
+        ));
+        outcome.assert_board(
+            "
+            h2 . . . .
+            .  . . . .
+            @5 . . . g
+            ",
+        );
+    }
+
+    #[test]
+    fn player_cannot_walk_on_water() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . .
+            @5 . . . g
+            ---
+            , , , , ,
+            ~ , , , ,
+            ",
+        )
+        .moves(&[Direction::Down])
+        .run();
+
+        assert!(matches!(
+            outcome.results[..],
+            [InputResult::Moved(Err(MovementDenied::Impassable))]
+        ));
+    }
+
+    #[test]
+    fn moving_into_friendly_requires_a_second_input() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . .
+            #3 . . . .
+            @5 . . . g
+            ",
+        )
+        .moves(&[Direction::Up, Direction::Up])
+        .run();
+
+        assert!(matches!(
+            outcome.results[..],
+            [
+                InputResult::Moved(Err(MovementDenied::Friendly)),
+                InputResult::Moved(Ok(())),
+            ]
+        ));
+        outcome.assert_board(
+            "
+            h2    . . . .
+            #2/3  . . . .
+            @5    . . g .
+            ",
+        );
+    }
+
+    #[test]
+    fn blocked_entities_do_not_move_into_vacated_tiles() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . .
+            .  . . . .
+            @5 . g g .
+            ",
+        )
+        .moves(&[Direction::Up])
+        .run();
+
+        // The second goblin is blocked by where the first goblin was at the start of the turn.
+        outcome.assert_board(
+            "
+            h2 . . . .
+            @5 . . . .
+            .  g . g .
+            ",
+        );
+    }
+
+    #[test]
+    fn player_moves_diagonally_when_enabled() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . .
+            .  . . . .
+            @5 . . . g
+            ",
+        )
+        .diagonal()
+        .moves(&[Direction::UpRight])
+        .run();
+
+        outcome.assert_board(
+            "
+            h2 .  . . .
+            .  @5 . . .
+            .  .  . g .
+            ",
+        );
+    }
+
+    #[test]
+    fn player_cannot_move_diagonally_by_default() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . .
+            @5 . . . g
+            ",
+        )
+        .moves(&[Direction::UpRight])
+        .run();
+
+        assert!(matches!(
+            outcome.results[..],
+            [InputResult::Moved(Err(MovementDenied::Diagonal))]
+        ));
+    }
+
+    #[test]
+    fn diagonal_moves_cannot_squeeze_between_water() {
+        let outcome = Scenario::new(
+            "
+            .  . . . h2
+            @5 . . . g
+            ---
+            ~ , , , ,
+            , ~ , , ,
+            ",
+        )
+        .diagonal()
+        .moves(&[Direction::UpRight])
+        .run();
+
+        assert!(matches!(
+            outcome.results[..],
+            [InputResult::Moved(Err(MovementDenied::Impassable))]
+        ));
+    }
+
+    #[test]
+    fn bats_fly_over_walls() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . .
+            @5 . . . .
+            .  . # b .
+            ",
+        )
+        .moves(&[Direction::Right])
+        .run();
+
+        // The bat heads for the house, ignoring the wall (and not attacking it).
+        outcome.assert_board(
+            "
+            h2 .  .   . .
+            .  @5 .   . .
+            .  .  #+b . .
+            ",
+        );
+        assert!(outcome.logs.is_empty());
+    }
+}
@@ -0,0 +1,139 @@
+//! Pathfinding.
+//!
+//! Entities only ever move one tile at a time, so instead of finding complete paths we compute a
+//! [`DistanceField`] (the cost of walking from every tile to the nearest goal) and step downhill.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use super::{
+    components::{Moving, Position},
+    map::{Layer, Map, Terrain},
+};
+
+/// The cost of walking from every tile of a map to the nearest of a set of goals.
+///
+/// Entities are ignored (they can be attacked out of the way), only the terrain matters. Steps
+/// follow the map's movement (see [`Map::movement`]); diagonal steps cost the same as any other.
+pub struct DistanceField {
+    width: usize,
+    costs: Vec<Option<u32>>,
+
+    /// Directions considered when stepping, in order of preference when costs are equal.
+    directions: &'static [Moving],
+}
+
+impl DistanceField {
+    /// Computes the distance field towards the given goals.
+    pub fn new(map: &Map, goals: &[Position]) -> Self {
+        Self::with_costs(map, goals, |t| t.movement_cost())
+    }
+
+    /// Computes the distance field towards the given goals for something moving in a layer.
+    ///
+    /// Fliers ignore the terrain, so every step costs the same.
+    pub fn for_layer(map: &Map, goals: &[Position], layer: Layer) -> Self {
+        match layer {
+            Layer::Ground => Self::new(map, goals),
+            Layer::Air => Self::with_costs(map, goals, |_| Some(1)),
+        }
+    }
+
+    /// Computes the distance field towards the given goals, with custom terrain costs.
+    ///
+    /// Terrain with a cost of `None` cannot be walked on.
+    pub fn with_costs(
+        map: &Map,
+        goals: &[Position],
+        terrain_cost: impl Fn(Terrain) -> Option<u32>,
+    ) -> Self {
+        let width = map.width();
+        let directions = map.movement().directions();
+        let walkable =
+            |x: i32, y: i32| map.in_bounds(x, y) && terrain_cost(map.get_terrain(x, y)).is_some();
+        let mut costs = vec![None; width * map.height()];
+        let mut frontier = BinaryHeap::new();
+
+        for goal in goals {
+            if map.in_bounds(goal.x, goal.y) {
+                costs[goal.y as usize * width + goal.x as usize] = Some(0);
+                frontier.push(Reverse((0, goal.x, goal.y)));
+            }
+        }
+
+        // Dijkstra, walking backwards from the goals: the cost of a step is the cost of the tile
+        // that is walked onto, which is the tile we are expanding from.
+        while let Some(Reverse((cost, x, y))) = frontier.pop() {
+            if costs[y as usize * width + x as usize] < Some(cost) {
+                continue;
+            }
+            let step = terrain_cost(map.get_terrain(x, y)).unwrap_or(0);
+
+            for direction in directions {
+                let (dx, dy) = direction.delta();
+                let (nx, ny) = (x + dx, y + dy);
+                if !walkable(nx, ny) {
+                    continue;
+                }
+
+                // Diagonal steps cannot squeeze between two tiles that cannot be walked on.
+                if direction.is_diagonal() && !walkable(nx, y) && !walkable(x, ny) {
+                    continue;
+                }
+
+                let index = ny as usize * width + nx as usize;
+                let next = cost + step;
+                if matches!(costs[index], Some(c) if c <= next) {
+                    continue;
+                }
+                costs[index] = Some(next);
+                frontier.push(Reverse((next, nx, ny)));
+            }
+        }
+
+        Self {
+            width,
+            costs,
+            directions,
+        }
+    }
+
+    /// Returns the cost of walking from the given coordinate to the nearest goal.
+    ///
+    /// Returns `None` if out of bounds or no goal can be reached.
+    #[must_use]
+    pub fn get(&self, x: i32, y: i32) -> Option<u32> {
+        if x < 0 || y < 0 || x as usize >= self.width {
+            return None;
+        }
+        self.costs
+            .get(y as usize * self.width + x as usize)
+            .copied()
+            .flatten()
+    }
+
+    /// Returns the direction that gets closest to the nearest goal, preferring `preferred` on ties.
+    ///
+    /// Returns `None` if no goal can be reached.
+    #[must_use]
+    pub fn step(&self, from: &Position, preferred: &Moving) -> Option<Moving> {
+        self.directions
+            .iter()
+            .filter_map(|direction| {
+                let next = from.after(direction);
+                let cost = self.get(next.x, next.y)?;
+
+                // Reachable tiles next to a reachable tile are reachable, so this is enough to
+                // stop diagonal steps from squeezing between tiles that cannot be walked on.
+                if direction.is_diagonal()
+                    && self.get(next.x, from.y).is_none()
+                    && self.get(from.x, next.y).is_none()
+                {
+                    return None;
+                }
+                Some((cost, direction != preferred, direction.clone()))
+            })
+            .min_by_key(|(cost, not_preferred, _)| (*cost, *not_preferred))
+            .map(|(_, _, direction)| direction)
+    }
+}
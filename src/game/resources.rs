@@ -0,0 +1,152 @@
+//! Resources.
+//!
+//! The town runs on [`Resources`]: gold, wood and food.
+//!
+//! - Trees yield wood, a piece for every blow the player lands on one (see [`WoodcuttingSystem`]).
+//! - Farms yield food when their crops are harvested.
+//! - Houses eat food at the end of every round, and pay gold if they could.
+//!
+//! Everything that can be built or recruited costs a mixture of them (see [`cost`]).
+
+use std::fmt;
+
+use specs::prelude::*;
+
+use super::{
+    components::{Attacking, Player, Renderable},
+    map::Map,
+    Glyph,
+};
+
+/// An amount of every resource, e.g. what is in store or what something costs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Resources {
+    pub gold: u8,
+    pub wood: u8,
+    pub food: u8,
+}
+
+impl Resources {
+    /// Create a new amount of resources.
+    pub const fn new(gold: u8, wood: u8, food: u8) -> Self {
+        Self { gold, wood, food }
+    }
+
+    /// Whether there is at least as much of every resource as something costs.
+    #[must_use]
+    pub fn can_afford(&self, cost: &Resources) -> bool {
+        self.gold >= cost.gold && self.wood >= cost.wood && self.food >= cost.food
+    }
+
+    /// Pay for something if there is enough of every resource. Returns whether it was paid.
+    pub fn spend(&mut self, cost: &Resources) -> bool {
+        if !self.can_afford(cost) {
+            return false;
+        }
+        self.gold -= cost.gold;
+        self.wood -= cost.wood;
+        self.food -= cost.food;
+        true
+    }
+}
+
+impl fmt::Display for Resources {
+    /// E.g. `2g 1f`, leaving out whatever there is none of.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = [(self.gold, 'g'), (self.wood, 'w'), (self.food, 'f')]
+            .into_iter()
+            .filter(|(amount, _)| *amount > 0)
+            .map(|(amount, unit)| format!("{}{}", amount, unit))
+            .collect();
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// Returns what building or recruiting something costs, or `None` if it cannot be bought.
+#[must_use]
+pub fn cost(glyph: Glyph) -> Option<Resources> {
+    match glyph {
+        Glyph::Wall => Some(Resources::new(0, 1, 0)),
+        Glyph::Farm => Some(Resources::new(1, 1, 0)),
+        Glyph::Tower => Some(Resources::new(2, 1, 0)),
+        Glyph::Militia | Glyph::Guard => Some(Resources::new(1, 0, 1)),
+        Glyph::Ranger => Some(Resources::new(1, 1, 1)),
+        _ => None,
+    }
+}
+
+/// Whether anything at all can be bought with the given resources.
+#[must_use]
+pub fn can_afford_anything(resources: &Resources) -> bool {
+    Glyph::ALL
+        .iter()
+        .filter_map(|glyph| cost(*glyph))
+        .any(|cost| resources.can_afford(&cost))
+}
+
+/// A system that gives the player wood for every blow it lands on a tree.
+///
+/// Must run before [`ApplyAttackSystem`](super::combat::ApplyAttackSystem), which clears attacks.
+pub struct WoodcuttingSystem;
+
+impl<'a> System<'a> for WoodcuttingSystem {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Renderable>,
+        ReadStorage<'a, Attacking>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Unpack the system data.
+        let (mut map, players, renderables, attacking) = data;
+
+        for (_, attack) in (&players, &attacking).join() {
+            let is_tree = renderables
+                .get(attack.target())
+                .is_some_and(|r| r.glyph() == Glyph::Tree);
+            if is_tree {
+                map.resources.wood = map.resources.wood.saturating_add(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{scenario::Scenario, Direction};
+
+    #[test]
+    fn costs_are_paid_in_full_or_not_at_all() {
+        let mut store = Resources::new(1, 2, 0);
+        assert!(!store.spend(&cost(Glyph::Ranger).unwrap()));
+        assert_eq!(store, Resources::new(1, 2, 0));
+        assert!(store.spend(&cost(Glyph::Farm).unwrap()));
+        assert_eq!(store, Resources::new(0, 1, 0));
+        assert!(can_afford_anything(&store));
+        assert!(store.spend(&cost(Glyph::Wall).unwrap()));
+        assert!(!can_afford_anything(&store));
+    }
+
+    #[test]
+    fn the_player_cuts_wood_from_trees() {
+        let outcome = Scenario::new(
+            "
+            h2 . .  . .
+            .  . t2 @5 g
+            ",
+        )
+        .moves(&[
+            Direction::Left,
+            Direction::Left,
+            Direction::Left,
+            Direction::Left,
+        ])
+        .run();
+
+        // Two blows, two pieces of wood, and the tree is gone.
+        assert_eq!(outcome.stats.resources.wood, 2);
+        assert!(!outcome.board.contains('t'));
+    }
+}
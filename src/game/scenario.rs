@@ -0,0 +1,227 @@
+//! Scenario test harness.
+//!
+//! A scenario starts from a board (see [`board`](super::board)), applies a sequence of player
+//! inputs through the same [`WorldState`] entry points as the UI, and captures the resulting
+//! board, logs, and stats:
+//!
+//! ```ignore
+//! let outcome = Scenario::new(
+//!     "
+//!     h2 . .
+//!     @5 . g
+//!     ",
+//! )
+//! .input(Input::Move(Direction::Right))
+//! .run();
+//!
+//! outcome.assert_board(
+//!     "
+//!     h2 . .
+//!     .  @5 g
+//!     ",
+//! );
+//! ```
+
+use bracket_lib::random::RandomNumberGenerator;
+
+use super::{
+    board,
+    components::Faction,
+    faction::{Relation, Relations},
+    logger::LogMessage,
+    map::Map,
+    Direction, GameStats, Glyph, MovementDenied, Resources, RunState, WorldState,
+};
+
+/// Seed used for scenarios that do not specify one.
+const DEFAULT_SEED: u64 = 52;
+
+/// Upper bound on ticks per input, in case the state machine never settles.
+const MAX_TICKS_PER_INPUT: usize = 8;
+
+/// A single player input.
+#[derive(Clone, Debug)]
+pub enum Input {
+    /// Move (or attack) in a direction, as if an arrow key was pressed.
+    Move(Direction),
+
+    /// Build a structure, as if the map was clicked during the building phase.
+    Build((i32, i32), Glyph),
+
+    /// Recruit an allied unit, as if a house was clicked during the building phase.
+    Recruit((i32, i32), Glyph),
+}
+
+/// The result of applying a single [`Input`].
+#[derive(Debug)]
+pub enum InputResult {
+    Moved(Result<(), MovementDenied>),
+    Built(bool),
+    Recruited(bool),
+}
+
+/// A starting board, a sequence of inputs, and settings to run them with.
+pub struct Scenario {
+    board: String,
+    seed: u64,
+    resources: Resources,
+    diagonal: bool,
+    relations: Vec<(Faction, Faction, Relation)>,
+    inputs: Vec<Input>,
+}
+
+/// What a [`Scenario`] looked like after all inputs were applied.
+pub struct Outcome {
+    /// The final board.
+    pub board: String,
+
+    /// Every log message, in order.
+    pub logs: Vec<LogMessage>,
+
+    /// The final stats.
+    pub stats: GameStats,
+
+    /// The result of each input, in order.
+    pub results: Vec<InputResult>,
+}
+
+impl Scenario {
+    /// Creates a scenario starting from the given board.
+    ///
+    /// Leading indentation and blank lines are ignored, so boards can be written inline.
+    pub fn new(board: &str) -> Self {
+        Self {
+            board: board.to_string(),
+            seed: DEFAULT_SEED,
+            resources: Resources::default(),
+            diagonal: false,
+            relations: Vec::new(),
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Sets the seed of the random number generator.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets the resources the player starts with.
+    pub fn resources(mut self, resources: Resources) -> Self {
+        self.resources = resources;
+        self
+    }
+
+    /// Enables eight-directional movement.
+    pub fn diagonal(mut self) -> Self {
+        self.diagonal = true;
+        self
+    }
+
+    /// Sets how two factions treat each other (see [`Relations::set`]).
+    pub fn relation(mut self, a: Faction, b: Faction, relation: Relation) -> Self {
+        self.relations.push((a, b, relation));
+        self
+    }
+
+    /// Adds an input.
+    pub fn input(mut self, input: Input) -> Self {
+        self.inputs.push(input);
+        self
+    }
+
+    /// Adds one [`Input::Move`] per direction.
+    pub fn moves(mut self, directions: &[Direction]) -> Self {
+        self.inputs
+            .extend(directions.iter().cloned().map(Input::Move));
+        self
+    }
+
+    /// Runs the scenario.
+    ///
+    /// # Panics
+    ///
+    /// If the board cannot be parsed, or the game never settles after an input.
+    pub fn run(self) -> Outcome {
+        let mut world = import(&self.board);
+        if self.diagonal {
+            world = world.with_diagonal_movement();
+        }
+        world.ecs.insert(RandomNumberGenerator::seeded(self.seed));
+        world.ecs.fetch_mut::<Map>().resources = self.resources;
+        for (a, b, relation) in self.relations {
+            world.ecs.fetch_mut::<Relations>().set(a, b, relation);
+        }
+
+        // Index the map and wait for input, as the first frame of a game would.
+        settle(&mut world);
+
+        let mut results = Vec::new();
+        for input in self.inputs {
+            results.push(match input {
+                Input::Move(direction) => InputResult::Moved(world.player_move(direction)),
+                Input::Build(position, what) => {
+                    InputResult::Built(world.player_build(position, what))
+                }
+                Input::Recruit(position, what) => {
+                    InputResult::Recruited(world.player_recruit(position, what))
+                }
+            });
+            settle(&mut world);
+        }
+
+        Outcome {
+            board: world.export_board(),
+            logs: world.get_logs(),
+            stats: world.get_stats(),
+            results,
+        }
+    }
+}
+
+/// Imports a board as a new world, like [`WorldState::import_board`], but of any size.
+///
+/// Scenarios are usually far smaller than a playable map, to keep them readable.
+///
+/// # Panics
+///
+/// If the board cannot be parsed.
+pub fn import(board: &str) -> WorldState {
+    match board::parse(board) {
+        Ok(board) => WorldState::from_board(board),
+        Err(e) => panic!("invalid scenario board: {}", e),
+    }
+}
+
+/// Ticks until the game is waiting on the player again.
+fn settle(world: &mut WorldState) {
+    for _ in 0..MAX_TICKS_PER_INPUT {
+        world.tick();
+        let run_state = *world.ecs.fetch::<RunState>();
+        if run_state == RunState::AwaitingInput || run_state == RunState::BuildingTurn {
+            return;
+        }
+    }
+    panic!(
+        "scenario did not settle after {} ticks",
+        MAX_TICKS_PER_INPUT
+    );
+}
+
+impl Outcome {
+    /// Asserts that the final board matches, ignoring whitespace differences.
+    #[track_caller]
+    pub fn assert_board(&self, expected: &str) {
+        assert_eq!(normalize(&self.board), normalize(expected));
+    }
+}
+
+/// Normalizes a board to single-space separated tokens without blank lines.
+fn normalize(board: &str) -> String {
+    board
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
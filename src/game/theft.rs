@@ -0,0 +1,244 @@
+//! Thieves.
+//!
+//! Thieves (see [`AI::Thief`]) steal crops from farms instead of attacking them, then run for
+//! the edge of the map. If one escapes, the crops are lost; if it is defeated first, it drops
+//! them for the player to pick up. Either way, the farm has to start growing again.
+
+use specs::prelude::*;
+
+use super::{
+    components::{
+        Attacking, DeathEffect, Growth, Moving, OnDeath, Player, Position, Raided, Renderable,
+        Stolen, AI,
+    },
+    logger::{LogMessage, Logs},
+    map::Map,
+    Glyph, HARVEST_FOOD,
+};
+
+/// A system that turns thieves attacking farms into thefts, and stops them attacking anything else.
+///
+/// Must run before [`ApplyAttackSystem`](super::combat::ApplyAttackSystem), so thieves never deal
+/// damage.
+pub struct StealSystem;
+
+impl<'a> System<'a> for StealSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, AI>,
+        ReadStorage<'a, Renderable>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, Attacking>,
+        WriteStorage<'a, Stolen>,
+        WriteStorage<'a, Raided>,
+        WriteStorage<'a, OnDeath>,
+        WriteStorage<'a, Growth>,
+        WriteExpect<'a, Logs>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Unpack the system data.
+        let (
+            entities,
+            ai,
+            renderables,
+            positions,
+            mut attacking,
+            mut stolen,
+            mut raided,
+            mut on_death,
+            mut growth,
+            mut logs,
+        ) = data;
+
+        // Find every thief bumping into something.
+        let bumps: Vec<(Entity, Entity)> = (&entities, &ai, &attacking)
+            .join()
+            .filter(|(_, ai, _)| matches!(ai, AI::Thief))
+            .map(|(thief, _, attack)| (thief, attack.target()))
+            .collect();
+
+        for (thief, farm) in bumps {
+            attacking.remove(thief);
+
+            // Only farms that were not raided yet have anything to steal.
+            let is_farm = renderables
+                .get(farm)
+                .is_some_and(|r| r.glyph() == Glyph::Farm);
+            if !is_farm || stolen.contains(thief) || raided.contains(farm) {
+                continue;
+            }
+
+            stolen.insert(thief, Stolen).unwrap();
+            raided.insert(farm, Raided).unwrap();
+            if let Some(growth) = growth.get_mut(farm) {
+                growth.ruin();
+            }
+
+            // If the thief is defeated, the crops are dropped where it stood.
+            on_death
+                .entry(thief)
+                .unwrap()
+                .or_insert_with(OnDeath::default)
+                .push(DeathEffect::Drop(Glyph::Crops));
+            if let Some(position) = positions.get(farm) {
+                logs.add(LogMessage::Stole {
+                    position: (position.x, position.y),
+                });
+            }
+        }
+    }
+}
+
+/// A system that removes thieves that move off the edge of the map, along with what they stole.
+///
+/// Must run before [`MovementSystem`](super::movement::MovementSystem).
+pub struct EscapeSystem;
+
+impl<'a> System<'a> for EscapeSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, Map>,
+        ReadStorage<'a, AI>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Moving>,
+        WriteStorage<'a, Stolen>,
+        WriteExpect<'a, Logs>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Unpack the system data.
+        let (entities, map, ai, mut positions, mut moving, mut stolen, mut logs) = data;
+
+        // Find every thief stepping off the map.
+        let escaped: Vec<(Entity, Position)> = (&entities, &ai, &positions, &moving)
+            .join()
+            .filter(|(_, ai, _, _)| matches!(ai, AI::Thief))
+            .filter(|(_, _, position, direction)| {
+                let next = position.after(direction);
+                !map.in_bounds(next.x, next.y)
+            })
+            .map(|(thief, _, position, _)| (thief, position.clone()))
+            .collect();
+
+        for (thief, position) in escaped {
+            logs.add(LogMessage::Escaped {
+                position: (position.x, position.y),
+                stolen: stolen.remove(thief).is_some(),
+            });
+
+            // Take it off the map right away, so nothing else moves into or attacks it.
+            positions.remove(thief);
+            moving.remove(thief);
+            entities.delete(thief).unwrap();
+        }
+    }
+}
+
+/// A system that lets the player pick up crops by walking onto them, earning their worth.
+///
+/// Must run after [`MovementSystem`](super::movement::MovementSystem).
+pub struct PickUpSystem;
+
+impl<'a> System<'a> for PickUpSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, Map>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Renderable>,
+        ReadStorage<'a, Position>,
+        WriteExpect<'a, Logs>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Unpack the system data.
+        let (entities, mut map, players, renderables, positions, mut logs) = data;
+
+        let Some((_, player)) = (&players, &positions).join().next() else {
+            return;
+        };
+
+        // Only what lies on the ground where the player stands can be picked up.
+        let crops: Vec<Entity> = map
+            .get_items(player.x, player.y)
+            .iter()
+            .copied()
+            .filter(|e| {
+                renderables
+                    .get(*e)
+                    .is_some_and(|r| r.glyph() == Glyph::Crops)
+            })
+            .collect();
+
+        for entity in crops {
+            map.resources.food = map.resources.food.saturating_add(HARVEST_FOOD);
+            entities.delete(entity).unwrap();
+            logs.add(LogMessage::PickedUp {
+                position: (player.x, player.y),
+                food: HARVEST_FOOD,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{logger::LogMessage, scenario::Scenario, Direction, GameState, Resources};
+
+    #[test]
+    fn thieves_escape_with_the_crops() {
+        let outcome = Scenario::new(
+            "
+            h2 . . . .
+            .  f T . .
+            @5 . . . .
+            ",
+        )
+        .moves(&[Direction::Right, Direction::Right, Direction::Right])
+        .run();
+
+        // The farm is not damaged, but yields nothing this round, so the house goes hungry.
+        assert_eq!(
+            outcome.logs,
+            vec![
+                LogMessage::Stole { position: (1, 1) },
+                LogMessage::Escaped {
+                    position: (2, 0),
+                    stolen: true,
+                },
+            ]
+        );
+        assert_eq!(outcome.stats.state, GameState::WaitingForBuild);
+        assert_eq!(outcome.stats.resources, Resources::default());
+        assert!(outcome.board.contains('f') && !outcome.board.contains('T'));
+    }
+
+    #[test]
+    fn defeated_thieves_drop_the_crops() {
+        let board = "
+            h2 . . .  . . . g
+            .  . . .  . . . .
+            f  T . @5 . . . .
+            ";
+
+        // The thief steals right away, and is defeated before it can escape.
+        let outcome = Scenario::new(board)
+            .moves(&[Direction::Left, Direction::Left])
+            .run();
+        assert!(outcome
+            .logs
+            .contains(&LogMessage::Stole { position: (0, 2) }));
+        assert!(outcome.board.contains('%') && !outcome.board.contains('T'));
+
+        // Picking the crops up earns what the harvest would have.
+        let outcome = Scenario::new(board)
+            .moves(&[Direction::Left, Direction::Left, Direction::Left])
+            .run();
+        assert!(outcome.logs.contains(&LogMessage::PickedUp {
+            position: (1, 2),
+            food: 2,
+        }));
+        assert_eq!(outcome.stats.resources.food, 2);
+        assert!(!outcome.board.contains('%'));
+    }
+}
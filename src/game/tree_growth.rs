@@ -31,3 +31,37 @@ impl<'a> System<'a> for TreeGrowthSystem {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{board, scenario::Scenario, Direction, GameState, Glyph};
+
+    #[test]
+    fn trees_heal_and_never_shrink_between_rounds() {
+        let outcome = Scenario::new(
+            "
+            h2 t1/2 t t .
+            @5 g    t t .
+            ",
+        )
+        .moves(&[Direction::Right])
+        .run();
+
+        assert_eq!(outcome.stats.state, GameState::WaitingForBuild);
+        assert_eq!(outcome.stats.round.get(), 2);
+
+        let board = board::parse(&outcome.board).unwrap();
+        let trees: Vec<_> = board
+            .tiles
+            .iter()
+            .filter(|t| t.glyph == Glyph::Tree)
+            .collect();
+        assert_eq!(trees.len(), 5);
+        for tree in trees {
+            assert_eq!(tree.hp.0, tree.hp.1, "{:?} was not healed", tree);
+            if (tree.x, tree.y) == (1, 0) {
+                assert!(tree.hp.1 >= 2, "{:?} shrank", tree);
+            }
+        }
+    }
+}
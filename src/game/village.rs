@@ -0,0 +1,350 @@
+//! Villagers.
+//!
+//! Every house is home to a villager, who walks to the nearest farm and tends it: every turn of
+//! work makes its crops grow a turn faster (see [`Growth`]). Villagers never fight:
+//! when something hostile comes within [`DANGER_RADIUS`], they run home and shelter inside (see
+//! [`Occupants`]), and only come out once it is safe again. A destroyed house lets out whoever
+//! was inside, as far as there is room around it.
+
+use specs::prelude::*;
+
+use super::{
+    components::{Faction, Growth, Memory, Occupants, Position, Renderable, Sheltering, Tending},
+    demo,
+    faction::Relations,
+    logger::{LogMessage, Logs},
+    map::{Layer, Map, MapIndexingSystem},
+    Glyph, RunState,
+};
+
+/// How close something hostile must be for a villager to run home.
+pub const DANGER_RADIUS: f64 = 3.0;
+
+/// How far every hostile must be from a house before those sheltering in it come out.
+const SAFE_RADIUS: f64 = 5.0;
+
+/// A system that grows the crops villagers tend on the monster's turn.
+pub struct TendSystem;
+
+impl<'a> System<'a> for TendSystem {
+    type SystemData = (
+        ReadExpect<'a, RunState>,
+        WriteStorage<'a, Tending>,
+        WriteStorage<'a, Growth>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Unpack the system data.
+        let (state, mut tending, mut growth) = data;
+
+        // If this is not the monster's turn, do nothing.
+        if *state != RunState::MonsterTurn {
+            return;
+        }
+
+        for tending in tending.drain().join() {
+            // The farm may have been destroyed in the meantime.
+            if let Some(growth) = growth.get_mut(tending.farm()) {
+                growth.grow(1);
+            }
+        }
+    }
+}
+
+/// A system that lets villagers into their homes on the monster's turn.
+///
+/// Whoever is inside has no [`Position`], so it is off the map until it comes out again.
+pub struct ShelterSystem;
+
+impl<'a> System<'a> for ShelterSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, RunState>,
+        WriteExpect<'a, Logs>,
+        ReadStorage<'a, Memory>,
+        WriteStorage<'a, Sheltering>,
+        WriteStorage<'a, Occupants>,
+        WriteStorage<'a, Position>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        // Unpack the system data.
+        let (entities, state, mut logs, memories, mut sheltering, mut occupants, mut positions) =
+            data;
+
+        // If this is not the monster's turn, do nothing.
+        if *state != RunState::MonsterTurn {
+            return;
+        }
+
+        let sheltering: Vec<Entity> = (&entities, sheltering.drain())
+            .join()
+            .map(|(entity, _)| entity)
+            .collect();
+        for entity in sheltering {
+            let Some(home) = memories.get(entity).and_then(|memory| memory.home) else {
+                continue;
+            };
+            let Some(house) = positions.get(home).cloned() else {
+                continue;
+            };
+            if !occupants.get_mut(home).is_some_and(|o| o.enter(entity)) {
+                continue;
+            }
+
+            positions.remove(entity);
+            logs.add(LogMessage::Sheltered {
+                position: (house.x, house.y),
+            });
+        }
+    }
+}
+
+/// Lets out whoever shelters in a house onto the free tiles around it, in the order they came in.
+///
+/// Returns whoever did not fit, who stays inside.
+pub fn release(ecs: &mut World, house: Entity) -> Vec<Entity> {
+    // Make sure the map knows where everything ended up.
+    MapIndexingSystem.run_now(ecs);
+
+    let stuck = {
+        let map = ecs.fetch::<Map>();
+        let mut occupants = ecs.write_storage::<Occupants>();
+        let mut positions = ecs.write_storage::<Position>();
+        let Some(center) = positions.get(house).cloned() else {
+            return Vec::new();
+        };
+        let Some(occupants) = occupants.get_mut(house) else {
+            return Vec::new();
+        };
+
+        let mut free = map
+            .neighbors(&center, map.movement())
+            .into_iter()
+            .filter(|p| map.get_blocker(p.x, p.y, Layer::Ground).is_none())
+            .filter(|p| map.is_passable(p.x, p.y));
+        let mut stuck = Vec::new();
+        for entity in occupants.leave() {
+            match free.next() {
+                Some(p) => {
+                    positions.insert(entity, p).unwrap();
+                }
+                None => stuck.push(entity),
+            }
+        }
+        for entity in &stuck {
+            occupants.enter(*entity);
+        }
+        stuck
+    };
+
+    // Index whoever came out, so nothing else ends up on top of them.
+    MapIndexingSystem.run_now(ecs);
+
+    stuck
+}
+
+/// Lets villagers out of every house that nothing hostile is close to anymore.
+///
+/// Must run after [`World::maintain`], once the defeated are out of the way.
+pub fn leave_shelter(ecs: &mut World) {
+    let safe: Vec<Entity> = {
+        let entities = ecs.entities();
+        let relations = ecs.fetch::<Relations>();
+        let factions = ecs.read_storage::<Faction>();
+        let positions = ecs.read_storage::<Position>();
+        let occupants = ecs.read_storage::<Occupants>();
+        (&entities, &occupants, &positions)
+            .join()
+            .filter(|(_, occupants, _)| !occupants.inside().is_empty())
+            .filter(|(house, _, center)| {
+                !(&entities, &positions).join().any(|(e, p)| {
+                    relations.is_hostile(&factions, *house, e) && center.distance(p) <= SAFE_RADIUS
+                })
+            })
+            .map(|(house, _, _)| house)
+            .collect()
+    };
+
+    for house in safe {
+        release(ecs, house);
+    }
+}
+
+/// Spawns a villager next to every house that no (living) villager calls home.
+pub fn spawn_villagers(ecs: &mut World) {
+    // Make sure the map knows about every entity, so villagers only spawn on open tiles.
+    MapIndexingSystem.run_now(ecs);
+
+    let spots: Vec<(Entity, Position)> = {
+        let entities = ecs.entities();
+        let map = ecs.fetch::<Map>();
+        let renderables = ecs.read_storage::<Renderable>();
+        let positions = ecs.read_storage::<Position>();
+        let memories = ecs.read_storage::<Memory>();
+        let mut taken = Vec::new();
+        (&entities, &renderables, &positions)
+            .join()
+            .filter(|(_, renderable, _)| renderable.glyph() == Glyph::House)
+            .filter(|(house, _, _)| !memories.join().any(|m| m.home == Some(*house)))
+            .filter_map(|(house, _, center)| {
+                let spot = map
+                    .neighbors(center, map.movement())
+                    .into_iter()
+                    .filter(|p| !taken.contains(p))
+                    .find(|p| map.get_entity(p.x, p.y).is_none() && map.is_passable(p.x, p.y))?;
+                taken.push(spot.clone());
+                Some((house, spot))
+            })
+            .collect()
+    };
+
+    for (house, spot) in spots {
+        demo::configure_villager(ecs.create_entity(), spot.x, spot.y)
+            .with(Memory {
+                home: Some(house),
+                ..Memory::default()
+            })
+            .build();
+    }
+    MapIndexingSystem.run_now(ecs);
+}
+
+#[cfg(test)]
+mod tests {
+    use specs::prelude::*;
+
+    use crate::game::{
+        components::{CropStage, Faction, Growth, Occupants},
+        logger::LogMessage,
+        scenario::{self, Scenario},
+        Direction,
+    };
+
+    #[test]
+    fn villagers_tend_farms() {
+        let mut world = scenario::import(
+            "
+            h2 v . f . . . . @5 . . .
+            .  . . . . . . . .  . . .
+            .  . . . . . . . .  . . g
+            ",
+        );
+        world.tick();
+        for direction in [
+            Direction::Left,
+            Direction::Right,
+            Direction::Left,
+            Direction::Right,
+        ] {
+            world.player_move(direction).unwrap();
+            world.tick();
+            world.tick();
+        }
+
+        // It walked over to the farm and got to work, so the crops grew faster than on their own.
+        let growth = world.ecs.read_storage::<Growth>();
+        assert!(growth
+            .join()
+            .next()
+            .is_some_and(|g| g.stage() == CropStage::Sprouting));
+    }
+
+    #[test]
+    fn villagers_shelter_from_monsters() {
+        let outcome = Scenario::new(
+            "
+            h2 v . . . .
+            .  . . o2 . .
+            .  . . . . @5
+            ",
+        )
+        .moves(&[Direction::Left])
+        .run();
+
+        assert!(outcome
+            .logs
+            .contains(&LogMessage::Sheltered { position: (0, 0) }));
+        assert!(!outcome.board.contains('v'));
+    }
+
+    #[test]
+    fn villagers_shelter_from_bats() {
+        let outcome = Scenario::new(
+            "
+            h2 v . . . .
+            .  . . b . .
+            .  . . . . @5
+            ",
+        )
+        .moves(&[Direction::Left])
+        .run();
+
+        assert!(outcome
+            .logs
+            .contains(&LogMessage::Sheltered { position: (0, 0) }));
+    }
+
+    #[test]
+    fn villagers_come_out_once_it_is_safe() {
+        let mut world = scenario::import(
+            "
+            h2 v . o1 @5
+            .  . . .  .
+            ",
+        );
+        world.tick();
+        world.player_move(Direction::Down).unwrap();
+        world.tick();
+        world.tick();
+        assert_eq!(
+            world
+                .ecs
+                .read_storage::<Occupants>()
+                .join()
+                .map(|o| o.inside().len())
+                .sum::<usize>(),
+            1
+        );
+
+        // Once the orc is gone, it comes back out.
+        {
+            let entities = world.ecs.entities();
+            let factions = world.ecs.read_storage::<Faction>();
+            for (entity, faction) in (&entities, &factions).join() {
+                if *faction == Faction::Monsters {
+                    entities.delete(entity).unwrap();
+                }
+            }
+        }
+        world.ecs.maintain();
+        world.player_move(Direction::Left).unwrap();
+        world.tick();
+        assert!(world.export_board().contains('v'));
+    }
+
+    #[test]
+    fn sheltering_villagers_are_not_exported() {
+        let outcome = Scenario::new(
+            "
+            h2 v . . . .
+            .  . . o2 . .
+            .  . . . . @5
+            ",
+        )
+        .moves(&[Direction::Left])
+        .run();
+        assert!(outcome
+            .logs
+            .contains(&LogMessage::Sheltered { position: (0, 0) }));
+
+        // The house comes back empty.
+        let world = scenario::import(&outcome.board);
+        assert!(world
+            .ecs
+            .read_storage::<Occupants>()
+            .join()
+            .all(|o| o.inside().is_empty()));
+        assert!(!world.export_board().contains('v'));
+    }
+}
@@ -2,8 +2,9 @@
 add_wasm_support!();
 
 use bracket_lib::prelude::*;
-use game::{Direction, Glyph, WorldState};
-use ui::{ui2grid, UIState, UI};
+use game::level_strategy::{self, LevelStrategy};
+use game::{Direction, Glyph, WorldState, DEFAULT_MAP_SIZE, MAX_MAP_SIZE, MIN_MAP_SIZE};
+use ui::{ui2grid, Camera, UIState, UI};
 
 mod game;
 mod ui;
@@ -11,11 +12,31 @@ mod ui;
 fn main() -> BError {
     let tile_size = 16;
     let grid_res = 6;
-    let grid_size = 12;
     let aspect_ratio = 8.0 / 5.0;
-    let width = (grid_size as f64 * aspect_ratio * grid_res as f64) as i32;
-    let height = grid_size * grid_res;
     let ui_sidebar_ratio = 2.0 / 3.0;
+    let max_view_size = (20, DEFAULT_MAP_SIZE as i32);
+    let options = Options::parse(std::env::args().skip(1))?;
+
+    // Start from an exported board if one is given (e.g. `cargo run -- board.txt`).
+    let mut game = match options.board {
+        Some(path) => WorldState::import_board(&std::fs::read_to_string(path)?)?,
+        None => WorldState::new(options.map_size.0, options.map_size.1, options.level),
+    };
+    if options.fog {
+        game = game.with_fog_of_war();
+    }
+    if options.diagonal {
+        game = game.with_diagonal_movement();
+    }
+
+    // Maps larger than the view scroll with the camera.
+    let camera = Camera::new(max_view_size, game.map_size());
+    let view_size = camera.view_size();
+
+    // The sidebar is always as wide as it would be for the default map, so the text fits.
+    let sidebar_width = (DEFAULT_MAP_SIZE as f64 * (aspect_ratio - 1.0) * grid_res as f64) as i32;
+    let height = max_view_size.1 * grid_res;
+    let width = view_size.0 * grid_res + sidebar_width;
 
     // TermBuilder offers a number of helps to get up and running quickly.
     let context = BTermBuilder::simple(width, height)?
@@ -25,37 +46,142 @@ fn main() -> BError {
         .build()?;
 
     // Empty state object.
-
-    let state = State::new(height, width, grid_res, ui_sidebar_ratio);
+    let state = State::new(
+        game,
+        camera,
+        height,
+        sidebar_width,
+        grid_res,
+        ui_sidebar_ratio,
+    );
 
     main_loop(context, state)
 }
 
+/// Options that can be provided on the command line.
+struct Options {
+    /// Path to a board to start from, instead of generating one.
+    board: Option<String>,
+
+    /// Width and height of a generated map.
+    map_size: (usize, usize),
+
+    /// Strategy a generated map is laid out with.
+    level: Box<dyn LevelStrategy>,
+
+    /// Whether only what the player and houses can see is shown.
+    fog: bool,
+
+    /// Whether the player and monsters can move diagonally.
+    diagonal: bool,
+}
+
+impl Options {
+    /// Parses options, e.g. `--size 16x10`, `--level fortress`, `--fog`, `--diagonal`, or `board.txt`.
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self {
+            board: None,
+            map_size: (DEFAULT_MAP_SIZE, DEFAULT_MAP_SIZE),
+            level: Box::new(level_strategy::Village),
+            fog: false,
+            diagonal: false,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--size" => {
+                    let size = args.next().ok_or("--size requires a value, e.g. 16x10")?;
+                    options.map_size = parse_map_size(&size)?;
+                }
+                "--level" => {
+                    let level = args
+                        .next()
+                        .ok_or("--level requires a value, e.g. fortress")?;
+                    options.level = parse_level(&level)?;
+                }
+                "--fog" => options.fog = true,
+                "--diagonal" => options.diagonal = true,
+                _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+                _ => options.board = Some(arg),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Parses a map size in the form `WIDTHxHEIGHT`.
+fn parse_map_size(size: &str) -> Result<(usize, usize), String> {
+    let invalid = || {
+        format!(
+            "Invalid map size {:?}, expected WIDTHxHEIGHT between {} and {}",
+            size, MIN_MAP_SIZE, MAX_MAP_SIZE
+        )
+    };
+    let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+    let width: usize = width.parse().map_err(|_| invalid())?;
+    let height: usize = height.parse().map_err(|_| invalid())?;
+
+    let range = MIN_MAP_SIZE..=MAX_MAP_SIZE;
+    if !range.contains(&width) || !range.contains(&height) {
+        return Err(invalid());
+    }
+    Ok((width, height))
+}
+
+/// Parses the name of a level strategy.
+fn parse_level(name: &str) -> Result<Box<dyn LevelStrategy>, String> {
+    level_strategy::by_name(name).ok_or_else(|| {
+        let names: Vec<&str> = level_strategy::all().iter().map(|s| s.name()).collect();
+        format!(
+            "Unknown level {:?}, expected one of: {}",
+            name,
+            names.join(", ")
+        )
+    })
+}
+
 /// This is the game state.
 ///
 /// We are going to try and have the game state be a representation of the game at a point in time.
 /// grid_res is the resolution of each grid square, i.e., a value of 4 means we have 4 titles per grid square
 struct State {
     game: WorldState,
+    camera: Camera,
     grid_res: i32,
     sidebar: VirtualConsole,
     logger: VirtualConsole,
+
+    /// Milliseconds since the camera last scrolled because the mouse was at the edge.
+    edge_scroll_ms: f32,
 }
 
+/// Milliseconds between each tile the camera scrolls while the mouse is at the edge.
+const EDGE_SCROLL_DELAY_MS: f32 = 150.0;
+
 impl State {
     /// Create a new game state.
-    pub fn new(height: i32, width: i32, grid_res: i32, ui_sidebar_ratio: f64) -> Self {
+    pub fn new(
+        game: WorldState,
+        camera: Camera,
+        height: i32,
+        sidebar_width: i32,
+        grid_res: i32,
+        ui_sidebar_ratio: f64,
+    ) -> Self {
         Self {
-            game: WorldState::new(),
+            game,
+            camera,
             grid_res,
             sidebar: VirtualConsole::new(Point::new(
-                width - height,
+                sidebar_width,
                 (height as f64 * ui_sidebar_ratio) as i32,
             )),
             logger: VirtualConsole::new(Point::new(
-                width - height,
+                sidebar_width,
                 (height as f64 * (1.0 - ui_sidebar_ratio)) as i32,
             )),
+            edge_scroll_ms: 0.0,
         }
     }
 }
@@ -71,46 +197,96 @@ impl GameState for State {
             ctx.quit();
         }
 
+        // Print the board, e.g. to attach to a bug report.
+        if ctx.key == Some(VirtualKeyCode::F2) {
+            println!("{}", self.game.export_board());
+        }
+
         // Direction player is moving
         let direction: Option<Direction> = match ctx.key {
             None => None,
             Some(key) => match key {
-                VirtualKeyCode::Left | VirtualKeyCode::A => Some(Direction::Left),
-                VirtualKeyCode::Right | VirtualKeyCode::D => Some(Direction::Right),
-                VirtualKeyCode::Up | VirtualKeyCode::W => Some(Direction::Up),
-                VirtualKeyCode::Down | VirtualKeyCode::S => Some(Direction::Down),
+                VirtualKeyCode::Left | VirtualKeyCode::A | VirtualKeyCode::Numpad4 => {
+                    Some(Direction::Left)
+                }
+                VirtualKeyCode::Right | VirtualKeyCode::D | VirtualKeyCode::Numpad6 => {
+                    Some(Direction::Right)
+                }
+                VirtualKeyCode::Up | VirtualKeyCode::W | VirtualKeyCode::Numpad8 => {
+                    Some(Direction::Up)
+                }
+                VirtualKeyCode::Down | VirtualKeyCode::S | VirtualKeyCode::Numpad2 => {
+                    Some(Direction::Down)
+                }
+                VirtualKeyCode::Q | VirtualKeyCode::Numpad7 => Some(Direction::UpLeft),
+                VirtualKeyCode::E | VirtualKeyCode::Numpad9 => Some(Direction::UpRight),
+                VirtualKeyCode::Z | VirtualKeyCode::Numpad1 => Some(Direction::DownLeft),
+                VirtualKeyCode::C | VirtualKeyCode::Numpad3 => Some(Direction::DownRight),
                 _ => None,
             },
         };
 
-        // Handle Player Movement
-        if let Some(direction) = direction {
-            let result = self.game.player_move(direction);
-            match result {
-                Ok(_) => {}
-                Err(e) => {
-                    // TODO: Show this error to the player.
-                    eprintln!("Error: {:?}", e);
+        // Pan the camera if SHIFT is held down, otherwise handle Player Movement.
+        match direction {
+            Some(direction) if ctx.shift => {
+                let (dx, dy) = direction.delta();
+                self.camera.pan(dx, dy);
+            }
+            Some(direction) => {
+                self.camera.resume();
+                let result = self.game.player_move(direction);
+                match result {
+                    Ok(_) => {}
+                    Err(e) => {
+                        // TODO: Show this error to the player.
+                        eprintln!("Error: {:?}", e);
+                    }
                 }
             }
+            None => {}
         }
 
+        // Scroll the camera while the mouse is at the edge of the map.
+        self.edge_scroll(ctx);
+
         // Get Mouse Position
-        let mouse_pos = ui2grid(ctx.mouse_pos(), self.grid_res).to_tuple();
+        let mouse_pos = ui2grid(ctx.mouse_pos(), self.grid_res, self.camera.offset()).to_tuple();
 
         // Build a Wall if the left mouse button is clicked.
         // Build a House if the SHIFT key is held down and the left mouse button is clicked.
-        if in_bounds(mouse_pos, self.game.map_size()) && ctx.left_click {
-            if ctx.shift {
-                self.game.player_build(mouse_pos, Glyph::Farm);
+        // Build a Watchtower if the CTRL key is held down and the left mouse button is clicked.
+        // Clicking a house recruits from it instead: militia, a guard with SHIFT, a ranger with CTRL.
+        if self.camera.contains(mouse_pos) && ctx.left_click {
+            let (recruit, build) = if ctx.control {
+                (Glyph::Ranger, Glyph::Tower)
+            } else if ctx.shift {
+                (Glyph::Guard, Glyph::Farm)
             } else {
-                self.game.player_build(mouse_pos, Glyph::Wall);
+                (Glyph::Militia, Glyph::Wall)
+            };
+            if !self.game.player_recruit(mouse_pos, recruit) {
+                self.game.player_build(mouse_pos, build);
             }
         }
 
         // Update the game state.
         self.game.tick();
 
+        // Create the UI state.
+        let ui_state = UIState::new(
+            self.game.to_render().into_iter().collect(),
+            self.game.terrain(),
+            self.game.visibility(),
+            self.game.get_stats(),
+            mouse_pos,
+            self.game.get_logs(),
+        );
+
+        // Keep the player in view.
+        if let Some(player) = ui_state.entities.iter().find(|e| e.glyph == Glyph::Player) {
+            self.camera.follow((player.x, player.y));
+        }
+
         // Create a UI renderer.
         let mut ui = UI::new(
             ctx,
@@ -118,15 +294,7 @@ impl GameState for State {
             &mut self.logger,
             self.grid_res,
             GRAY90,
-            self.game.map_size(),
-        );
-
-        // Create the UI state.
-        let ui_state = UIState::new(
-            self.game.to_render().into_iter().collect(),
-            self.game.get_stats(),
-            mouse_pos,
-            self.game.get_logs(),
+            &self.camera,
         );
 
         // Draw the UI.
@@ -134,6 +302,111 @@ impl GameState for State {
     }
 }
 
-fn in_bounds(pos: (i32, i32), grid_size: (i32, i32)) -> bool {
-    pos.0 >= 0 && pos.0 < grid_size.0 && pos.1 >= 0 && pos.1 < grid_size.1
+impl State {
+    /// Pans the camera one tile at a time while the mouse is within half a tile of a map edge.
+    fn edge_scroll(&mut self, ctx: &BTerm) {
+        let (x, y) = ctx.mouse_pos();
+        let view_size = self.camera.view_size();
+        let (width, height) = (view_size.0 * self.grid_res, view_size.1 * self.grid_res);
+        let edge = self.grid_res / 2;
+
+        // Ignore the mouse when it is over the sidebar.
+        if x >= width {
+            self.edge_scroll_ms = 0.0;
+            return;
+        }
+
+        let dx = if x < edge {
+            -1
+        } else if x >= width - edge {
+            1
+        } else {
+            0
+        };
+        let dy = if y < edge {
+            -1
+        } else if y >= height - edge {
+            1
+        } else {
+            0
+        };
+        if dx == 0 && dy == 0 {
+            self.edge_scroll_ms = 0.0;
+            return;
+        }
+
+        self.edge_scroll_ms += ctx.frame_time_ms;
+        if self.edge_scroll_ms >= EDGE_SCROLL_DELAY_MS {
+            self.edge_scroll_ms = 0.0;
+            self.camera.pan(dx, dy);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_map_size, Options, DEFAULT_MAP_SIZE, MAX_MAP_SIZE, MIN_MAP_SIZE};
+
+    /// Parses options from the given arguments.
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn options_default_to_a_generated_village() {
+        let options = parse(&[]).ok().unwrap();
+        assert_eq!(options.board, None);
+        assert_eq!(options.map_size, (DEFAULT_MAP_SIZE, DEFAULT_MAP_SIZE));
+        assert_eq!(options.level.name(), "village");
+        assert!(!options.fog && !options.diagonal);
+    }
+
+    #[test]
+    fn options_parse_every_flag_and_a_board() {
+        let options = parse(&[
+            "--size",
+            "16x10",
+            "--level",
+            "fortress",
+            "--fog",
+            "--diagonal",
+            "board.txt",
+        ])
+        .ok()
+        .unwrap();
+        assert_eq!(options.board.as_deref(), Some("board.txt"));
+        assert_eq!(options.map_size, (16, 10));
+        assert_eq!(options.level.name(), "fortress");
+        assert!(options.fog && options.diagonal);
+    }
+
+    #[test]
+    fn options_reject_unknown_and_incomplete_flags() {
+        assert_eq!(
+            parse(&["--wat"]).err(),
+            Some("Unknown option: --wat".to_string())
+        );
+        assert!(parse(&["--size"]).is_err());
+        assert!(parse(&["--level"]).is_err());
+        assert!(parse(&["--level", "castle"]).is_err());
+    }
+
+    #[test]
+    fn map_sizes_must_be_in_range() {
+        assert_eq!(
+            parse_map_size(&format!("{}x{}", MIN_MAP_SIZE, MAX_MAP_SIZE)),
+            Ok((MIN_MAP_SIZE, MAX_MAP_SIZE))
+        );
+        assert_eq!(parse_map_size("16x10"), Ok((16, 10)));
+
+        assert!(parse_map_size(&format!("{}x12", MIN_MAP_SIZE - 1)).is_err());
+        assert!(parse_map_size(&format!("12x{}", MAX_MAP_SIZE + 1)).is_err());
+    }
+
+    #[test]
+    fn map_sizes_must_be_width_by_height() {
+        for size in ["", "16", "16x", "x10", "16by10", "16x10x2", "-1x10", "axb"] {
+            assert!(parse_map_size(size).is_err(), "{:?} was accepted", size);
+        }
+    }
 }
@@ -1,10 +1,13 @@
-use std::cmp::min;
+use std::collections::HashMap;
 
 use bracket_lib::prelude::*;
 
 use bracket_lib::terminal::{BTerm, Console, Point, Rect, VirtualConsole};
 
-use crate::game::{logger::LogMessage, DrawEntity, GameStats, Glyph};
+use crate::game::{
+    cost, logger::LogMessage, AuraEffect, CropStage, DrawEntity, GameStats, Glyph, Terrain,
+    TileVisibility,
+};
 
 pub struct UIProperties {
     pub fg: (u8, u8, u8), // Foreground color
@@ -12,11 +15,25 @@ pub struct UIProperties {
     pub sym: char,
 }
 
+impl UIProperties {
+    /// Darken the colors, e.g. for tiles that are remembered but not in sight.
+    fn dimmed(self) -> Self {
+        let dim = |(r, g, b): (u8, u8, u8)| (r / 3, g / 3, b / 3);
+        Self {
+            fg: dim(self.fg),
+            bg: dim(self.bg),
+            sym: self.sym,
+        }
+    }
+}
+
 /// What we want to draw the screen logically.
 ///
 /// This won't know about game logic, just what to draw.
 pub struct UIState {
     pub entities: Vec<DrawEntity>,
+    pub terrain: Vec<Vec<Terrain>>,
+    pub visibility: Vec<Vec<TileVisibility>>,
     pub stats: GameStats,
     pub mouse_grid: (i32, i32),
     pub logs: Vec<LogMessage>,
@@ -25,12 +42,16 @@ pub struct UIState {
 impl UIState {
     pub fn new(
         entities: Vec<DrawEntity>,
+        terrain: Vec<Vec<Terrain>>,
+        visibility: Vec<Vec<TileVisibility>>,
         stats: GameStats,
         mouse_grid: (i32, i32),
         logs: Vec<LogMessage>,
     ) -> Self {
         Self {
             entities,
+            terrain,
+            visibility,
             stats,
             mouse_grid,
             logs,
@@ -38,6 +59,124 @@ impl UIState {
     }
 }
 
+/// Which part of the map is visible, in grid coordinates.
+///
+/// The camera follows a target (the player) unless it was panned away, and never shows anything
+/// outside of the map.
+pub struct Camera {
+    offset: (i32, i32),
+    view_size: (i32, i32),
+    map_size: (i32, i32),
+    following: bool,
+}
+
+impl Camera {
+    /// Create a camera showing up to `view_size` tiles of a map.
+    pub fn new(view_size: (i32, i32), map_size: (i32, i32)) -> Self {
+        Self {
+            offset: (0, 0),
+            view_size: (view_size.0.min(map_size.0), view_size.1.min(map_size.1)),
+            map_size,
+            following: true,
+        }
+    }
+
+    /// Returns the top-left visible tile.
+    pub fn offset(&self) -> (i32, i32) {
+        self.offset
+    }
+
+    /// Returns the number of visible tiles.
+    pub fn view_size(&self) -> (i32, i32) {
+        self.view_size
+    }
+
+    /// Whether a tile is visible.
+    pub fn contains(&self, pos: (i32, i32)) -> bool {
+        pos.0 >= self.offset.0
+            && pos.0 < self.offset.0 + self.view_size.0
+            && pos.1 >= self.offset.1
+            && pos.1 < self.offset.1 + self.view_size.1
+    }
+
+    /// Move the camera by a number of tiles, and stop following until [`Camera::resume`].
+    ///
+    /// Panning past the edge of the map does nothing, so the camera keeps following.
+    pub fn pan(&mut self, dx: i32, dy: i32) {
+        let offset = self.offset;
+        self.move_to((offset.0 + dx, offset.1 + dy));
+        if self.offset != offset {
+            self.following = false;
+        }
+    }
+
+    /// Start following the target again.
+    pub fn resume(&mut self) {
+        self.following = true;
+    }
+
+    /// Center the camera on a target, unless it was panned away.
+    pub fn follow(&mut self, target: (i32, i32)) {
+        if self.following {
+            self.move_to((
+                target.0 - self.view_size.0 / 2,
+                target.1 - self.view_size.1 / 2,
+            ));
+        }
+    }
+
+    /// Move the top-left visible tile, keeping the view within the map.
+    fn move_to(&mut self, offset: (i32, i32)) {
+        self.offset = (
+            offset.0.clamp(0, self.map_size.0 - self.view_size.0),
+            offset.1.clamp(0, self.map_size.1 - self.view_size.1),
+        );
+    }
+}
+
+/// Lines of text in the sidebar (counting the boss health bar as one), top to bottom.
+const SIDEBAR_ROWS: i32 = 14;
+
+/// Returns the console row that a sidebar row is drawn at.
+///
+/// Rows are a grid tile apart, like the logger's, unless that would not fit all
+/// [`SIDEBAR_ROWS`] inside the sidebar's border.
+fn sidebar_row_y(row: i32, sidebar_height: i32, grid_res: i32) -> i32 {
+    // The border takes up the first console row, and the last two.
+    let spacing = ((sidebar_height - 3) / SIDEBAR_ROWS).clamp(1, grid_res);
+    1 + spacing * row + (spacing - 1) / 2
+}
+
+/// Returns how many characters fit on a sidebar row, between the margin and the border.
+fn sidebar_columns(sidebar_width: i32, grid_res: i32) -> usize {
+    (sidebar_width - 2 - grid_res / 2).max(0) as usize
+}
+
+/// Returns how to build and recruit, with prices, one sidebar row per line.
+fn sidebar_help() -> Vec<String> {
+    let price = |glyph| cost(glyph).unwrap_or_default();
+    vec![
+        format!("Buy # ({}) with left click", price(Glyph::Wall)),
+        format!("Buy f ({}) on soil: shift + click", price(Glyph::Farm)),
+        format!("Buy w ({}) tower: ctrl + left click", price(Glyph::Tower)),
+        format!("Click h to recruit m ({})", price(Glyph::Militia)),
+        format!("  G ({}) with shift", price(Glyph::Guard)),
+        format!("  R ({}) with ctrl", price(Glyph::Ranger)),
+    ]
+}
+
+/// Returns the auras an entity is in, e.g. `Buffs: defense 1, regen 1`.
+fn describe_buffs(buffs: &[AuraEffect]) -> String {
+    let buffs: Vec<String> = buffs
+        .iter()
+        .map(|buff| match buff {
+            AuraEffect::Defense(amount) => format!("defense {}", amount),
+            AuraEffect::Regeneration(amount) => format!("regen {}", amount),
+        })
+        .collect();
+    format!("Buffs: {}", buffs.join(", "))
+}
+
 /// UI module draws the game to the screen.
 pub struct UI<'a> {
     ctx: &'a mut BTerm,
@@ -45,8 +184,8 @@ pub struct UI<'a> {
     logger: &'a mut VirtualConsole,
     grid_res: i32,
     grid_color: (u8, u8, u8),
-    field_size: i32,
-    _map_size: (i32, i32),
+    field_size: (i32, i32),
+    camera: &'a Camera,
 }
 
 impl<'a> UI<'a> {
@@ -57,9 +196,10 @@ impl<'a> UI<'a> {
         logger: &'a mut VirtualConsole,
         grid_res: i32,
         grid_color: (u8, u8, u8),
-        map_size: (i32, i32),
+        camera: &'a Camera,
     ) -> Self {
-        let field_size = min(map_size.0, map_size.1) * grid_res;
+        let view_size = camera.view_size();
+        let field_size = (view_size.0 * grid_res, view_size.1 * grid_res);
         Self {
             ctx,
             sidebar,
@@ -67,7 +207,7 @@ impl<'a> UI<'a> {
             grid_res,
             grid_color,
             field_size,
-            _map_size: map_size,
+            camera,
         }
     }
 
@@ -80,31 +220,193 @@ impl<'a> UI<'a> {
         self.draw_logger(state);
 
         self.draw_entities(state);
+
+        self.draw_intents(state);
+
+        self.draw_offscreen_threats(state);
     }
 
     /// Draw game entities
+    ///
+    /// Terrain is drawn first, so that it is underneath the entities. Unexplored tiles are left
+    /// blank and remembered ones are dimmed.
     fn draw_entities(&mut self, state: &UIState) {
-        for e in &state.entities {
-            let e_pos_ui = grid2ui((e.x, e.y), self.grid_res);
-
-            for dx in 1..self.grid_res {
-                for dy in 1..self.grid_res {
-                    self.ctx.print_color(
-                        e_pos_ui.x + dx,
-                        e_pos_ui.y + dy,
-                        ui_properties(&e.glyph).fg,
-                        ui_properties(&e.glyph).bg,
-                        if dx == self.grid_res / 2 && dy == self.grid_res / 2 {
-                            ui_properties(&e.glyph).sym
-                        } else {
-                            ' '
-                        },
-                    );
+        for (y, row) in state.terrain.iter().enumerate() {
+            for (x, terrain) in row.iter().enumerate() {
+                let pos = (x as i32, y as i32);
+                if !self.camera.contains(pos) {
+                    continue;
+                }
+                match state.visibility[y][x] {
+                    TileVisibility::Visible => self.draw_tile(pos, &terrain_properties(terrain)),
+                    TileVisibility::Remembered => {
+                        self.draw_tile(pos, &terrain_properties(terrain).dimmed())
+                    }
+                    TileVisibility::Unexplored => {}
                 }
             }
+        }
+
+        // Count what is on each tile, to mark tiles with more than one thing on them.
+        let mut stacked: HashMap<(i32, i32), usize> = HashMap::new();
+        for e in &state.entities {
+            *stacked.entry((e.x, e.y)).or_default() += 1;
+        }
+
+        for e in &state.entities {
+            if !self.camera.contains((e.x, e.y)) {
+                continue;
+            }
+            let mut properties = ui_properties(&e.glyph);
+            if let Some(stage) = e.crops {
+                properties.fg = crop_color(stage);
+            }
+            match state.visibility[e.y as usize][e.x as usize] {
+                TileVisibility::Visible => self.draw_tile((e.x, e.y), &properties),
+                _ => self.draw_tile((e.x, e.y), &properties.dimmed()),
+            }
+
+            let e_pos_ui = grid2ui((e.x, e.y), self.grid_res, self.camera.offset());
             if e.hp.0 > 1 {
                 self.ctx.print(e_pos_ui.x + 1, e_pos_ui.y + 1, e.hp.0)
             }
+
+            // E.g. an item under the player.
+            if stacked[&(e.x, e.y)] > 1 {
+                self.ctx
+                    .print(e_pos_ui.x + self.grid_res - 1, e_pos_ui.y + 1, "+");
+            }
+        }
+    }
+
+    /// Fill the inside of a grid tile, with the symbol in the middle.
+    fn draw_tile(&mut self, pos: (i32, i32), properties: &UIProperties) {
+        let pos_ui = grid2ui(pos, self.grid_res, self.camera.offset());
+
+        for dx in 1..self.grid_res {
+            for dy in 1..self.grid_res {
+                self.ctx.print_color(
+                    pos_ui.x + dx,
+                    pos_ui.y + dy,
+                    properties.fg,
+                    properties.bg,
+                    if dx == self.grid_res / 2 && dy == self.grid_res / 2 {
+                        properties.sym
+                    } else {
+                        ' '
+                    },
+                );
+            }
+        }
+    }
+
+    /// Draw an arrow for what every visible entity will do next turn.
+    ///
+    /// Arrows point across the edge (or corner) of the tile being moved into; if something would
+    /// be attacked there instead, its tile is marked with a `!`. Entities taking aim are marked
+    /// with a `*`, and so is what they aim at. Areas about to be smashed are marked with `!`s.
+    fn draw_intents(&mut self, state: &UIState) {
+        let offset = self.camera.offset();
+        let middle = self.grid_res / 2;
+
+        for e in &state.entities {
+            if let Some((center, turns)) = e.smash {
+                // Everything on and around the center is hit; red when it lands next turn.
+                let color = if turns <= 1 { RED } else { ORANGE };
+                for dx in -1..=1 {
+                    for dy in -1..=1 {
+                        let tile = (center.0 + dx, center.1 + dy);
+                        if self.camera.contains(tile) {
+                            let tile_ui = grid2ui(tile, self.grid_res, offset);
+                            self.ctx
+                                .print_color(tile_ui.x, tile_ui.y, color, BLACK, '!');
+                        }
+                    }
+                }
+            }
+
+            if let Some(aim) = e.aim {
+                for tile in [(e.x, e.y), aim] {
+                    if self.camera.contains(tile) {
+                        let tile_ui = grid2ui(tile, self.grid_res, offset);
+                        self.ctx
+                            .print_color(tile_ui.x + middle, tile_ui.y, RED, BLACK, '*');
+                    }
+                }
+            }
+
+            let Some(intent) = &e.intent else {
+                continue;
+            };
+            if !self.camera.contains((e.x, e.y)) {
+                continue;
+            }
+
+            let tile_ui = grid2ui((e.x, e.y), self.grid_res, offset);
+            let (dx, dy) = intent.delta();
+            let (x_ui, y_ui, arrow) = match (dx, dy) {
+                (-1, 0) => (tile_ui.x, tile_ui.y + middle, '<'),
+                (1, 0) => (tile_ui.x + self.grid_res, tile_ui.y + middle, '>'),
+                (0, -1) => (tile_ui.x + middle, tile_ui.y, '^'),
+                (0, _) => (tile_ui.x + middle, tile_ui.y + self.grid_res, 'v'),
+
+                // Diagonals point out of the corner being moved through.
+                (-1, -1) => (tile_ui.x, tile_ui.y, '\\'),
+                (1, -1) => (tile_ui.x + self.grid_res, tile_ui.y, '/'),
+                (-1, _) => (tile_ui.x, tile_ui.y + self.grid_res, '/'),
+                _ => (tile_ui.x + self.grid_res, tile_ui.y + self.grid_res, '\\'),
+            };
+            self.ctx.print_color(x_ui, y_ui, ORANGE, BLACK, arrow);
+
+            // Mark whatever is in the way, as it is about to be attacked.
+            let target = (e.x + dx, e.y + dy);
+            let attacked = state
+                .entities
+                .iter()
+                .any(|t| t.blocks && !t.hostile && (t.x, t.y) == target);
+            if attacked && self.camera.contains(target) {
+                let target_ui = grid2ui(target, self.grid_res, offset);
+                self.ctx.print_color(
+                    target_ui.x + self.grid_res - 1,
+                    target_ui.y + self.grid_res - 1,
+                    RED,
+                    BLACK,
+                    '!',
+                );
+            }
+        }
+    }
+
+    /// Draw an arrow at the edge of the screen for every hostile entity that is not visible.
+    fn draw_offscreen_threats(&mut self, state: &UIState) {
+        let offset = self.camera.offset();
+        let view_size = self.camera.view_size();
+
+        for e in &state.entities {
+            if !e.hostile || self.camera.contains((e.x, e.y)) {
+                continue;
+            }
+
+            // Find the closest visible tile, and which way the threat is from it.
+            let x = e.x.clamp(offset.0, offset.0 + view_size.0 - 1);
+            let y = e.y.clamp(offset.1, offset.1 + view_size.1 - 1);
+            let (dx, dy) = (e.x - x, e.y - y);
+            let tile_ui = grid2ui((x, y), self.grid_res, offset);
+            let middle = self.grid_res / 2;
+
+            let (x_ui, y_ui, arrow) = if dx.abs() >= dy.abs() {
+                if dx < 0 {
+                    (tile_ui.x, tile_ui.y + middle, '<')
+                } else {
+                    (tile_ui.x + self.grid_res, tile_ui.y + middle, '>')
+                }
+            } else if dy < 0 {
+                (tile_ui.x + middle, tile_ui.y, '^')
+            } else {
+                (tile_ui.x + middle, tile_ui.y + self.grid_res, 'v')
+            };
+
+            self.ctx.print_color(x_ui, y_ui, RED, BLACK, arrow);
         }
     }
 
@@ -114,7 +416,6 @@ impl<'a> UI<'a> {
         }
 
         for (i, log) in state.logs.iter().enumerate() {
-            #[allow(clippy::single_match)]
             match log {
                 LogMessage::Attacked {
                     attacker,
@@ -132,8 +433,124 @@ impl<'a> UI<'a> {
                         ),
                     );
                 }
-                #[allow(unreachable_patterns)]
-                _ => {}
+                LogMessage::Shot {
+                    attacker,
+                    target,
+                    position,
+                    ..
+                } => {
+                    self.write_row_logger(
+                        i as i32,
+                        format!(
+                            "{:?} shot {:?} from {:?}!",
+                            ui_properties(attacker).sym,
+                            ui_properties(target).sym,
+                            position
+                        ),
+                    );
+                }
+                LogMessage::BossArrived { boss } => {
+                    self.write_row_logger(
+                        i as i32,
+                        format!(
+                            "The chieftain {:?} has come for you!",
+                            ui_properties(boss).sym
+                        ),
+                    );
+                }
+                LogMessage::Smashed {
+                    attacker,
+                    position,
+                    hits,
+                } => {
+                    self.write_row_logger(
+                        i as i32,
+                        format!(
+                            "{:?} smashed {:?}, hitting {}!",
+                            ui_properties(attacker).sym,
+                            position,
+                            hits
+                        ),
+                    );
+                }
+                LogMessage::Summoned { position, count } => {
+                    self.write_row_logger(
+                        i as i32,
+                        format!("{} minions answer the call at {:?}!", count, position),
+                    );
+                }
+                LogMessage::Stole { position } => {
+                    self.write_row_logger(
+                        i as i32,
+                        format!("A thief stole the crops at {:?}!", position),
+                    );
+                }
+                LogMessage::Escaped { position, stolen } => {
+                    let message = if *stolen {
+                        format!("A thief escaped with crops at {:?}!", position)
+                    } else {
+                        format!("A thief left empty-handed at {:?}.", position)
+                    };
+                    self.write_row_logger(i as i32, message);
+                }
+                LogMessage::PickedUp { position, food } => {
+                    self.write_row_logger(
+                        i as i32,
+                        format!("Picked up crops worth {} food at {:?}.", food, position),
+                    );
+                }
+                LogMessage::Blocked {
+                    attacker,
+                    target,
+                    position,
+                } => {
+                    self.write_row_logger(
+                        i as i32,
+                        format!(
+                            "{:?} shrugged off {:?} at {:?}.",
+                            ui_properties(target).sym,
+                            ui_properties(attacker).sym,
+                            position
+                        ),
+                    );
+                }
+                LogMessage::Split {
+                    glyph,
+                    position,
+                    count,
+                } => {
+                    self.write_row_logger(
+                        i as i32,
+                        format!(
+                            "{:?} split into {} at {:?}!",
+                            ui_properties(glyph).sym,
+                            count,
+                            position
+                        ),
+                    );
+                }
+                LogMessage::Harvested { position, food } => {
+                    self.write_row_logger(
+                        i as i32,
+                        format!("Harvested crops worth {} food at {:?}.", food, position),
+                    );
+                }
+                LogMessage::Trampled { attacker, position } => {
+                    self.write_row_logger(
+                        i as i32,
+                        format!(
+                            "{:?} ruined the crops at {:?}!",
+                            ui_properties(attacker).sym,
+                            position
+                        ),
+                    );
+                }
+                LogMessage::Sheltered { position } => {
+                    self.write_row_logger(
+                        i as i32,
+                        format!("A villager hid in the house at {:?}.", position),
+                    );
+                }
             }
         }
 
@@ -149,7 +566,7 @@ impl<'a> UI<'a> {
         self.logger.print_sub_rect(
             Rect::with_size(0, 0, self.logger.width, self.logger.height),
             Rect::with_size(
-                (self._map_size.0 * self.grid_res + 1) as u32,
+                (self.field_size.0 + 1) as u32,
                 self.sidebar.height,
                 self.logger.width,
                 self.logger.height,
@@ -185,7 +602,26 @@ impl<'a> UI<'a> {
                 state.stats.houses, state.stats.farms
             ),
         );
-        self.write_row_sidebar(3, format!("Money  $ {}", state.stats.money));
+        let resources = state.stats.resources;
+        self.write_row_sidebar(
+            3,
+            format!(
+                "Gold {}  Wood {}  Food {}",
+                resources.gold, resources.wood, resources.food
+            ),
+        );
+        if let Some((amount, maximum)) = state.stats.boss {
+            self.write_row_sidebar(4, format!("Boss   {}/{}", amount, maximum));
+            self.sidebar.draw_bar_horizontal(
+                self.grid_res / 2,
+                sidebar_row_y(5, self.sidebar.height as i32, self.grid_res),
+                self.sidebar.width as i32 - self.grid_res - 2,
+                amount as i32,
+                maximum as i32,
+                RED.into(),
+                BLACK.into(),
+            );
+        }
         // self.write_row_sidebar(3, format!("Houses {}", state.stats.houses));
         // self.write_row_sidebar(5, format!("Mouse (GRID) : {:?}", state.mouse_grid));
         // for uie in &state.entities {
@@ -193,14 +629,31 @@ impl<'a> UI<'a> {
         //         self.write_row_sidebar(6, format!("Player {:?}", (uie.x, uie.y)));
         //     }
         // }
-        //TODO: get price from state here
-        self.write_row_sidebar(5, "Buy # ($1) with left click".to_string());
-        self.write_row_sidebar(6, "Buy f ($2) with shift + left click".to_string());
+        for (row, line) in (6..).zip(sidebar_help()) {
+            self.write_row_sidebar(row, line);
+        }
+
+        // Inspect whatever is under the mouse, topmost first.
+        let (x, y) = state.mouse_grid;
+        if let Some(entity) = state.entities.iter().rev().find(|e| (e.x, e.y) == (x, y)) {
+            self.write_row_sidebar(
+                12,
+                format!(
+                    "{:?} {}/{}",
+                    ui_properties(&entity.glyph).sym,
+                    entity.hp.0,
+                    entity.hp.1
+                ),
+            );
+            if !entity.buffs.is_empty() {
+                self.write_row_sidebar(13, describe_buffs(&entity.buffs));
+            }
+        }
 
         self.sidebar.print_sub_rect(
             Rect::with_size(0, 0, self.sidebar.width, self.sidebar.height),
             Rect::with_size(
-                (self._map_size.0 * self.grid_res + 1) as u32,
+                (self.field_size.0 + 1) as u32,
                 0,
                 self.sidebar.width,
                 self.sidebar.height,
@@ -209,18 +662,26 @@ impl<'a> UI<'a> {
         );
     }
     fn write_row_sidebar(&mut self, row: i32, value: String) {
+        debug_assert!(row < SIDEBAR_ROWS, "sidebar row {} is off the sidebar", row);
+        let (width, height) = (self.sidebar.width as i32, self.sidebar.height as i32);
+
+        // Cut off whatever would run over the border.
+        let value: String = value
+            .chars()
+            .take(sidebar_columns(width, self.grid_res))
+            .collect();
         self.sidebar.print(
             self.grid_res / 2,
-            (self.grid_res as f64 * (row as f64 + 0.5)) as i32,
+            sidebar_row_y(row, height, self.grid_res),
             &value,
         );
     }
 
     /// Draw a grid (debug purposes)
     fn draw_grid(&mut self) {
-        for x_ui in 0..(self.field_size / self.grid_res) {
-            for y_ui in 0..(self.field_size / self.grid_res) {
-                let grid_point = grid2ui((x_ui, y_ui), self.grid_res);
+        for x_ui in 0..(self.field_size.0 / self.grid_res) {
+            for y_ui in 0..(self.field_size.1 / self.grid_res) {
+                let grid_point = grid2ui((x_ui, y_ui), self.grid_res, (0, 0));
                 self.ctx.draw_box(
                     grid_point.x,
                     grid_point.y,
@@ -238,13 +699,73 @@ impl<'a> UI<'a> {
 /// Transforms entity coordinates differently so that they
 /// land in center of grid. Otherwise coordinates will land in
 /// upper-left corner of bounding box.
-pub fn grid2ui(pos_grid: (i32, i32), grid_res: i32) -> Point {
-    Point::new(grid_res * pos_grid.0, grid_res * pos_grid.1)
+///
+/// The offset is the top-left visible grid tile (see [`Camera::offset`]).
+pub fn grid2ui(pos_grid: (i32, i32), grid_res: i32, offset: (i32, i32)) -> Point {
+    Point::new(
+        grid_res * (pos_grid.0 - offset.0),
+        grid_res * (pos_grid.1 - offset.1),
+    )
 }
 
 /// Transforms native UI coordinates to grid coordinates
-pub fn ui2grid(pos_ui: (i32, i32), grid_res: i32) -> Point {
-    Point::new(pos_ui.0 / grid_res, pos_ui.1 / grid_res)
+///
+/// The offset is the top-left visible grid tile (see [`Camera::offset`]).
+pub fn ui2grid(pos_ui: (i32, i32), grid_res: i32, offset: (i32, i32)) -> Point {
+    Point::new(
+        pos_ui.0 / grid_res + offset.0,
+        pos_ui.1 / grid_res + offset.1,
+    )
+}
+
+/// Returns the color a farm is drawn in, by how far its crops have grown.
+fn crop_color(stage: CropStage) -> (u8, u8, u8) {
+    match stage {
+        CropStage::Sown => SADDLE_BROWN,
+        CropStage::Sprouting => DARKGREEN,
+        CropStage::Ripe => GOLDENROD,
+    }
+}
+
+/// Create UIProperites struct for all Terrain types
+fn terrain_properties(t: &Terrain) -> UIProperties {
+    match t {
+        Terrain::Grass => UIProperties {
+            fg: (60, 110, 60),
+            bg: (20, 45, 20),
+            sym: ' ',
+        },
+        Terrain::Dirt => UIProperties {
+            fg: (110, 90, 60),
+            bg: (55, 42, 28),
+            sym: ' ',
+        },
+        Terrain::Water => UIProperties {
+            fg: (140, 180, 255),
+            bg: (25, 55, 120),
+            sym: '~',
+        },
+        Terrain::Rock => UIProperties {
+            fg: (170, 170, 170),
+            bg: (70, 70, 75),
+            sym: '^',
+        },
+        Terrain::Road => UIProperties {
+            fg: (150, 140, 120),
+            bg: (90, 80, 65),
+            sym: ' ',
+        },
+        Terrain::Soil => UIProperties {
+            fg: (140, 100, 50),
+            bg: (75, 50, 25),
+            sym: ' ',
+        },
+        Terrain::Ford => UIProperties {
+            fg: (140, 180, 255),
+            bg: (50, 85, 120),
+            sym: '_',
+        },
+    }
 }
 
 /// Create UIProperites struct for all Glyph types
@@ -253,42 +774,198 @@ fn ui_properties(g: &Glyph) -> UIProperties {
         Glyph::Player => UIProperties {
             fg: WHITE,
             bg: CADET_BLUE,
-            sym: '@',
+            sym: g.symbol(),
         },
         Glyph::Goblin => UIProperties {
             fg: BLACK,
             bg: RED,
-            sym: 'g',
+            sym: g.symbol(),
         },
         Glyph::Rat => UIProperties {
             fg: BLACK,
             bg: SADDLE_BROWN,
-            sym: 'r',
+            sym: g.symbol(),
         },
         Glyph::Orc => UIProperties {
             fg: BLACK,
             bg: ORANGE,
-            sym: 'o',
+            sym: g.symbol(),
+        },
+        Glyph::Bat => UIProperties {
+            fg: WHITE,
+            bg: NAVY,
+            sym: g.symbol(),
+        },
+        Glyph::Tower => UIProperties {
+            fg: DARK_RED,
+            bg: GRAY75,
+            sym: g.symbol(),
+        },
+        Glyph::Ogre => UIProperties {
+            fg: WHITE,
+            bg: DARK_RED,
+            sym: g.symbol(),
+        },
+        Glyph::Archer => UIProperties {
+            fg: BLACK,
+            bg: YELLOW,
+            sym: g.symbol(),
+        },
+        Glyph::Slime => UIProperties {
+            fg: BLACK,
+            bg: LIME_GREEN,
+            sym: g.symbol(),
+        },
+        Glyph::Shaman => UIProperties {
+            fg: WHITE,
+            bg: DARK_GREEN,
+            sym: g.symbol(),
+        },
+        Glyph::Thief => UIProperties {
+            fg: BLACK,
+            bg: PURPLE,
+            sym: g.symbol(),
+        },
+        Glyph::Crops => UIProperties {
+            fg: DARKGREEN,
+            bg: GOLD,
+            sym: g.symbol(),
         },
         Glyph::Wall => UIProperties {
             fg: GRAY10,
             bg: GRAY75,
-            sym: '#',
+            sym: g.symbol(),
         },
         Glyph::Farm => UIProperties {
             fg: DARKGREEN,
             bg: PALE_GOLDENROD,
-            sym: 'f',
+            sym: g.symbol(),
+        },
+        Glyph::Militia | Glyph::Guard | Glyph::Ranger => UIProperties {
+            fg: WHITE,
+            bg: STEEL_BLUE,
+            sym: g.symbol(),
+        },
+        Glyph::Villager => UIProperties {
+            fg: BLACK,
+            bg: WHEAT,
+            sym: g.symbol(),
         },
         Glyph::House => UIProperties {
             fg: DARK_RED,
             bg: GRAY50,
-            sym: 'h',
+            sym: g.symbol(),
         },
         Glyph::Tree => UIProperties {
             fg: DARKGREEN,
             bg: LIGHTGREEN,
-            sym: 't',
+            sym: g.symbol(),
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        describe_buffs, grid2ui, sidebar_columns, sidebar_help, sidebar_row_y, ui2grid, Camera,
+        SIDEBAR_ROWS,
+    };
+    use crate::game::AuraEffect;
+
+    #[test]
+    fn camera_follows_within_map() {
+        let mut camera = Camera::new((10, 6), (30, 20));
+
+        camera.follow((0, 0));
+        assert_eq!(camera.offset(), (0, 0));
+
+        camera.follow((15, 10));
+        assert_eq!(camera.offset(), (10, 7));
+
+        camera.follow((29, 19));
+        assert_eq!(camera.offset(), (20, 14));
+        assert!(camera.contains((29, 19)));
+        assert!(!camera.contains((19, 19)));
+    }
+
+    #[test]
+    fn camera_stops_following_when_panned() {
+        let mut camera = Camera::new((10, 6), (30, 20));
+        camera.follow((15, 10));
+
+        camera.pan(-3, 1);
+        camera.follow((0, 0));
+        assert_eq!(camera.offset(), (7, 8));
+
+        camera.resume();
+        camera.follow((0, 0));
+        assert_eq!(camera.offset(), (0, 0));
+    }
+
+    #[test]
+    fn camera_keeps_following_when_panned_past_the_edge() {
+        let mut camera = Camera::new((10, 6), (30, 20));
+        camera.follow((0, 0));
+
+        camera.pan(-1, -1);
+        camera.follow((15, 10));
+        assert_eq!(camera.offset(), (10, 7));
+    }
+
+    #[test]
+    fn camera_never_exceeds_small_maps() {
+        let mut camera = Camera::new((20, 12), (12, 12));
+        assert_eq!(camera.view_size(), (12, 12));
+
+        camera.pan(5, 5);
+        assert_eq!(camera.offset(), (0, 0));
+    }
+
+    #[test]
+    fn ui_and_grid_coordinates_include_offset() {
+        let ui = grid2ui((12, 9), 6, (10, 7));
+        assert_eq!((ui.x, ui.y), (12, 12));
+
+        let grid = ui2grid((ui.x + 3, ui.y + 3), 6, (10, 7));
+        assert_eq!((grid.x, grid.y), (12, 9));
+    }
+
+    #[test]
+    fn sidebar_rows_fit_inside_the_border() {
+        // The default sidebar is two thirds of a 72 row window; taller ones space rows out more.
+        for height in [48, 60, 90, 120] {
+            let rows: Vec<i32> = (0..SIDEBAR_ROWS)
+                .map(|row| sidebar_row_y(row, height, 6))
+                .collect();
+            assert!(rows[0] >= 1, "{:?}", rows);
+            assert!(rows[rows.len() - 1] <= height - 3, "{:?}", rows);
+            assert!(rows.windows(2).all(|r| r[0] < r[1]), "{:?}", rows);
+        }
+
+        // With room to spare, rows are a grid tile apart.
+        assert_eq!(sidebar_row_y(0, 120, 6), 3);
+        assert_eq!(sidebar_row_y(1, 120, 6), 9);
+
+        // The default sidebar is 43 columns wide, with a border on either side.
+        assert_eq!(sidebar_columns(43, 6), 38);
+    }
+
+    #[test]
+    fn sidebar_help_fits() {
+        let help = sidebar_help();
+        assert!(help.len() <= 6, "{:?}", help);
+        for line in help {
+            assert!(line.chars().count() <= sidebar_columns(43, 6), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn buffs_fit_on_a_sidebar_row() {
+        let buffs = describe_buffs(&[
+            AuraEffect::Defense(u8::MAX),
+            AuraEffect::Regeneration(u8::MAX),
+        ]);
+        assert_eq!(buffs, "Buffs: defense 255, regen 255");
+        assert!(buffs.chars().count() <= sidebar_columns(43, 6));
+    }
+}